@@ -0,0 +1,22 @@
+; the hex digits 0-F in two rows of eight, from the built-in font
+; cargo run --bin asm -- roms/digits.asm
+
+        LD V0, 0            ; the digit
+        LD V1, 0            ; x
+        LD V2, 0            ; y
+top:    LD F, V0
+        DRW V1, V2, 5
+        ADD V1, 5
+        ADD V0, 1
+        SE V0, 8
+        JP top
+
+        LD V1, 0
+        LD V2, 6
+bottom: LD F, V0
+        DRW V1, V2, 5
+        ADD V1, 5
+        ADD V0, 1
+        SE V0, 16
+        JP bottom
+; running into the empty memory after the program halts the emulator
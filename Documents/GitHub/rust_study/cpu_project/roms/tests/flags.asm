@@ -0,0 +1,132 @@
+; the results and the VF flags of the 8xy_ arithmetic, checked one by one
+; every check draws its number and a tick when both are right, a cross
+; when not; the numbers run 1 to A, five to a row
+; cargo run --bin asm -- roms/tests/flags.asm
+
+        CLS
+        LD V7, 1            ; the check
+        LD VC, 0            ; x
+        LD VD, 0            ; y
+
+        ; 1: 8xy4 without a carry
+        LD V1, 0x10
+        LD V2, 0x20
+        ADD V1, V2
+        LD VB, VF
+        LD VA, V1
+        LD V5, 0x30
+        LD V6, 0
+        CALL check
+
+        ; 2: 8xy4 with a carry
+        LD V1, 0xF0
+        LD V2, 0x20
+        ADD V1, V2
+        LD VB, VF
+        LD VA, V1
+        LD V5, 0x10
+        LD V6, 1
+        CALL check
+
+        ; 3: 8xy5 without a borrow
+        LD V1, 0x30
+        LD V2, 0x10
+        SUB V1, V2
+        LD VB, VF
+        LD VA, V1
+        LD V5, 0x20
+        LD V6, 1
+        CALL check
+
+        ; 4: 8xy5 with a borrow
+        LD V1, 0x10
+        LD V2, 0x30
+        SUB V1, V2
+        LD VB, VF
+        LD VA, V1
+        LD V5, 0xE0
+        LD V6, 0
+        CALL check
+
+        ; 5: 8xy7 without a borrow
+        LD V1, 0x10
+        LD V2, 0x30
+        SUBN V1, V2
+        LD VB, VF
+        LD VA, V1
+        LD V5, 0x20
+        LD V6, 1
+        CALL check
+
+        ; 6: 8xy7 with a borrow
+        LD V1, 0x30
+        LD V2, 0x10
+        SUBN V1, V2
+        LD VB, VF
+        LD VA, V1
+        LD V5, 0xE0
+        LD V6, 0
+        CALL check
+
+        ; 7: 8xy6 (x and y hold the same, whatever the shifting quirk)
+        LD V1, 0x05
+        LD V2, 0x05
+        SHR V1, V2
+        LD VB, VF
+        LD VA, V1
+        LD V5, 0x02
+        LD V6, 1
+        CALL check
+
+        ; 8: 8xyE
+        LD V1, 0x81
+        LD V2, 0x81
+        SHL V1, V2
+        LD VB, VF
+        LD VA, V1
+        LD V5, 0x02
+        LD V6, 1
+        CALL check
+
+        ; 9: a carry into 0
+        LD V1, 0xFF
+        LD V2, 0x01
+        ADD V1, V2
+        LD VB, VF
+        LD VA, V1
+        LD V5, 0
+        LD V6, 1
+        CALL check
+
+        ; A: VF as the destination, the flag wins
+        LD VF, 0xF0
+        LD V2, 0x20
+        ADD VF, V2
+        LD VB, VF
+        LD VA, VF
+        LD V5, 1
+        LD V6, 1
+        CALL check
+        HALT
+
+; draw the check number V7, then a tick if VA = V5 and VB = V6
+check:  LD F, V7
+        DRW VC, VD, 5
+        ADD VC, 5
+        LD I, cross
+        SE VA, V5
+        JP mark
+        SE VB, V6
+        JP mark
+        LD I, tick
+mark:   DRW VC, VD, 5
+        ADD V7, 1
+        ADD VC, 7
+        SE VC, 60
+        RET
+        LD VC, 0
+        ADD VD, 6
+        RET
+
+tick:   DB 0b00001000, 0b00010000, 0b10100000, 0b01000000, 0b00000000
+cross:  DB 0b10001000, 0b01010000, 0b00100000, 0b01010000, 0b10001000
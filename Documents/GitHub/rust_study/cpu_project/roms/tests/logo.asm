@@ -0,0 +1,42 @@
+; a banner, CHIP-8, in sprites of its own: the first thing to get right
+; (00E0, 6xkk, 7xkk, Annn, Dxyn, 1nnn and 3xkk only)
+; cargo run --bin asm -- roms/tests/logo.asm
+
+        CLS
+        LD V0, 8            ; x
+        LD V1, 12           ; y
+        LD V2, 0            ; letters drawn
+        LD I, letters
+next:   DRW V0, V1, 8
+        ADD V0, 8
+        LD V3, 8
+        ADD I, V3           ; the next letter
+        ADD V2, 1
+        SE V2, 6
+        JP next
+
+        ; a line under it, in two halves
+        LD V0, 8
+        LD V1, 22
+        LD I, line
+        DRW V0, V1, 1
+        ADD V0, 24
+        DRW V0, V1, 1
+        ADD V0, 24
+        DRW V0, V1, 1
+        HALT
+
+letters:
+        DB 0b01111100, 0b11000110, 0b11000000, 0b11000000   ; C
+        DB 0b11000000, 0b11000000, 0b11000110, 0b01111100
+        DB 0b11000110, 0b11000110, 0b11000110, 0b11111110   ; H
+        DB 0b11000110, 0b11000110, 0b11000110, 0b11000110
+        DB 0b01111110, 0b00011000, 0b00011000, 0b00011000   ; I
+        DB 0b00011000, 0b00011000, 0b00011000, 0b01111110
+        DB 0b11111100, 0b11000110, 0b11000110, 0b11111100   ; P
+        DB 0b11000000, 0b11000000, 0b11000000, 0b11000000
+        DB 0b00000000, 0b00000000, 0b00000000, 0b01111100   ; -
+        DB 0b01111100, 0b00000000, 0b00000000, 0b00000000
+        DB 0b01111100, 0b11000110, 0b11000110, 0b01111100   ; 8
+        DB 0b11000110, 0b11000110, 0b11000110, 0b01111100
+line:   DB 0b11111111, 0b11111111, 0b11111111
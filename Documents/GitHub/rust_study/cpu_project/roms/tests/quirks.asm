@@ -0,0 +1,63 @@
+; how the quirks are set, as a digit each, and a sprite at the right edge
+;   vf_reset    0 when 8xy1 resets VF, 5 when it does not
+;   memory      C when Fx55 moves I on, 1 when it does not
+;   shifting    4 when 8xy6 shifts Vy, 0 when it shifts Vx
+;   jumping     A when Bnnn adds V0, B when it adds Vx
+;   clipping    the bar at the right edge is cut off, or wraps around to
+;               the left
+; cargo run --bin asm -- roms/tests/quirks.asm
+
+        CLS
+        LD VC, 0            ; x
+        LD VD, 0            ; y
+
+        ; vf_reset
+        LD VF, 5
+        LD V0, 1
+        LD V1, 2
+        OR V0, V1
+        LD V3, VF
+        CALL digit
+
+        ; memory: stores V0 and V1, then reads buffer + 2 or + 0
+        LD V0, 1
+        LD V1, 2
+        LD I, buffer
+        LD [I], V1
+        LD V0, [I]
+        LD V3, V0
+        CALL digit
+
+        ; shifting
+        LD V1, 1
+        LD V2, 8
+        SHR V1, V2
+        LD V3, V1
+        CALL digit
+
+        ; jumping: B2nn adds V2 with the quirk, V0 without
+        LD V0, 0
+        LD V2, 2
+        JP V0, table
+table:  JP vip
+        JP chip48
+vip:    LD V3, 0xA
+        JP jumped
+chip48: LD V3, 0xB
+jumped: CALL digit
+
+        ; clipping
+        LD V0, 60
+        LD V1, 8
+        LD I, bar
+        DRW V0, V1, 1
+        HALT
+
+; draw the digit V3 and move on
+digit:  LD F, V3
+        DRW VC, VD, 5
+        ADD VC, 6
+        RET
+
+bar:    DB 0xFF
+buffer: DB 0x00, 0x00, 0x0C
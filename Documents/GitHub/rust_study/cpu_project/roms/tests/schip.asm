@@ -0,0 +1,45 @@
+; the SUPER-CHIP extensions: hi-res, the big font, a 16x16 sprite,
+; scrolling and the RPL flags, ending with 00FD (EXIT); the screen it
+; leaves is drawn in tests/conformance.rs
+; cargo run --bin asm -- roms/tests/schip.asm
+
+        HIGH
+
+        ; a 16x16 box at (100, 0), scrolled down 20 and left 4, then right 4
+        ; twice, which leaves it at (104, 20)
+        LD V1, 100
+        LD V2, 0
+        LD I, box
+        DRW V1, V2, 0
+        SCD 15
+        SCD 5
+        SCL
+        SCR
+        SCR
+
+        ; the big digits 0-9 along the top
+        LD V0, 0            ; the digit
+        LD V1, 0            ; x
+        LD V2, 0            ; y
+digits: LD HF, V0
+        DRW V1, V2, 10
+        ADD V1, 10
+        ADD V0, 1
+        SE V0, 10
+        JP digits
+
+        ; RPL flags: save V0-V2, clear them, load them back, draw V2
+        LD V0, 3
+        LD V1, 0
+        LD V2, 7
+        LD R, V2
+        LD V2, 0
+        LD V2, R
+        LD F, V2
+        LD V1, 60
+        LD V0, 50
+        DRW V1, V0, 5
+        EXIT
+
+box:    DW 0xFFFF, 0x8001, 0x8001, 0x8001, 0x8001, 0x8001, 0x8001, 0x8181
+        DW 0x8181, 0x8001, 0x8001, 0x8001, 0x8001, 0x8001, 0x8001, 0xFFFF
@@ -0,0 +1,318 @@
+/*
+    an assembler for the mnemonics of disasm.rs 
+        one instruction per line, case does not matter for mnemonics and 
+        registers; anything after ; is a comment 
+            loop:   LD V0, K            ; labels end with a colon 
+                    DRW V1, V2, 5 
+                    JP loop 
+
+    numbers are decimal, 0x hex or 0b binary (0b1100_0000); wherever a 
+        number goes, a label or a constant can be used instead: 
+            SPEED = 4               constants are defined before use 
+            ADD V1, SPEED 
+
+    data directives put bytes between the instructions, e.g. sprites: 
+            DB 0b11110000, 0x90, 144 
+            DW 0x1234, label        16-bit words, big-endian 
+
+    the program is assembled for 0x200 (PROGRAM_START), in two passes: 
+        the first finds where every label is, the second encodes; an 
+        error names the line it was found on 
+*/
+
+use std::{collections::HashMap, fmt}; 
+
+use crate::cpu::{MAX_ROM_SIZE, PROGRAM_START}; 
+
+#[derive(Debug, PartialEq)]
+pub struct AsmError {
+    pub line: usize, 
+    pub message: String, 
+}
+
+// an operand, as written 
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Operand<'a> {
+    V(u16), 
+    I, 
+    IndirectI, 
+    DT, 
+    ST, 
+    K, 
+    F, 
+    B, 
+    HF, 
+    R, 
+    // a number or a symbol, resolved in the second pass 
+    Value(&'a str), 
+}
+
+// a line that produces bytes 
+struct Item<'a> {
+    line: usize, 
+    mnemonic: String, 
+    operands: Vec<Operand<'a>>, 
+}
+
+impl fmt::Display for AsmError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "line {}: {}", self.line, self.message)
+    }
+}
+
+impl std::error::Error for AsmError {}
+
+/// Assemble `source` into a ROM image to be loaded at `PROGRAM_START`. 
+pub fn assemble(source: &str) -> Result<Vec<u8>, AsmError> {
+    let mut symbols: HashMap<&str, u16> = HashMap::new(); 
+    let mut items = Vec::new(); 
+    let mut addr = PROGRAM_START; 
+
+    // first pass: labels, constants and the size of everything 
+    for (n, text) in source.lines().enumerate() {
+        let line = n + 1; 
+        let error = |message: String| AsmError { line, message }; 
+        let mut text = text.split(';').next().unwrap_or("").trim(); 
+
+        if let Some((name, value)) = text.split_once('=') {
+            let name = symbol_name(name.trim()).map_err(error)?; 
+            let value = resolve(value.trim(), &symbols).map_err(error)?; 
+            if symbols.insert(name, value).is_some() {
+                return Err(error(format!("`{name}` is already defined"))); 
+            }
+            continue; 
+        }
+        if let Some((label, rest)) = text.split_once(':') {
+            let label = symbol_name(label.trim()).map_err(error)?; 
+            if symbols.insert(label, addr as u16).is_some() {
+                return Err(error(format!("`{label}` is already defined"))); 
+            }
+            text = rest.trim(); 
+        }
+        if text.is_empty() {
+            continue; 
+        }
+
+        let (mnemonic, rest) = text.split_once(char::is_whitespace).unwrap_or((text, "")); 
+        let mnemonic = mnemonic.to_ascii_uppercase(); 
+        let operands: Vec<Operand> = if rest.trim().is_empty() {
+            Vec::new()
+        } else {
+            rest.split(',').map(|o| operand(o.trim())).collect()
+        }; 
+        addr += match mnemonic.as_str() {
+            "DB" => operands.len(), 
+            "DW" => operands.len() * 2, 
+            _ => 2, 
+        }; 
+        if addr > PROGRAM_START + MAX_ROM_SIZE {
+            return Err(error(format!("the program is larger than {MAX_ROM_SIZE} bytes"))); 
+        }
+        items.push(Item { line, mnemonic, operands }); 
+    }
+
+    // second pass: encoding 
+    let mut rom = Vec::new(); 
+    for item in &items {
+        let error = |message: String| AsmError { line: item.line, message }; 
+        match item.mnemonic.as_str() {
+            "DB" => {
+                for operand in &item.operands {
+                    rom.push(value(operand, 0xFF, &symbols).map_err(error)? as u8); 
+                }
+            }
+            "DW" => {
+                for operand in &item.operands {
+                    rom.extend_from_slice(&value(operand, 0xFFFF, &symbols).map_err(error)?.to_be_bytes()); 
+                }
+            }
+            _ => {
+                let opcode = encode(&item.mnemonic, &item.operands, &symbols).map_err(error)?; 
+                rom.extend_from_slice(&opcode.to_be_bytes()); 
+            }
+        }
+    }
+    Ok(rom)
+}
+
+fn encode(mnemonic: &str, operands: &[Operand], symbols: &HashMap<&str, u16>) -> Result<u16, String> {
+    use Operand::*; 
+
+    let addr = |o: &Operand| value(o, 0xFFF, symbols); 
+    let byte = |o: &Operand| value(o, 0xFF, symbols); 
+    let nibble = |o: &Operand| value(o, 0xF, symbols); 
+    let xy = |base: u16, x: u16, y: u16| base | x << 8 | y << 4; 
+
+    Ok(match (mnemonic, operands) {
+        ("HALT", []) => 0x0000, 
+        ("CLS", []) => 0x00E0, 
+        ("RET", []) => 0x00EE, 
+        ("SCD", [n]) => 0x00C0 | nibble(n)?, 
+        ("SCR", []) => 0x00FB, 
+        ("SCL", []) => 0x00FC, 
+        ("EXIT", []) => 0x00FD, 
+        ("LOW", []) => 0x00FE, 
+        ("HIGH", []) => 0x00FF, 
+        ("SYS", [a]) => addr(a)?, 
+        ("JP", [V(0), a]) => 0xB000 | addr(a)?, 
+        ("JP", [a]) => 0x1000 | addr(a)?, 
+        ("CALL", [a]) => 0x2000 | addr(a)?, 
+        ("SE", [V(x), V(y)]) => xy(0x5000, *x, *y), 
+        ("SE", [V(x), k]) => 0x3000 | x << 8 | byte(k)?, 
+        ("SNE", [V(x), V(y)]) => xy(0x9000, *x, *y), 
+        ("SNE", [V(x), k]) => 0x4000 | x << 8 | byte(k)?, 
+        ("LD", [V(x), V(y)]) => xy(0x8000, *x, *y), 
+        ("LD", [V(x), DT]) => 0xF007 | x << 8, 
+        ("LD", [V(x), K]) => 0xF00A | x << 8, 
+        ("LD", [V(x), IndirectI]) => 0xF065 | x << 8, 
+        ("LD", [V(x), R]) => 0xF085 | x << 8, 
+        ("LD", [V(x), k]) => 0x6000 | x << 8 | byte(k)?, 
+        ("LD", [I, a]) => 0xA000 | addr(a)?, 
+        ("LD", [DT, V(x)]) => 0xF015 | x << 8, 
+        ("LD", [ST, V(x)]) => 0xF018 | x << 8, 
+        ("LD", [F, V(x)]) => 0xF029 | x << 8, 
+        ("LD", [HF, V(x)]) => 0xF030 | x << 8, 
+        ("LD", [B, V(x)]) => 0xF033 | x << 8, 
+        ("LD", [R, V(x)]) => 0xF075 | x << 8, 
+        ("LD", [IndirectI, V(x)]) => 0xF055 | x << 8, 
+        ("ADD", [I, V(x)]) => 0xF01E | x << 8, 
+        ("ADD", [V(x), V(y)]) => xy(0x8004, *x, *y), 
+        ("ADD", [V(x), k]) => 0x7000 | x << 8 | byte(k)?, 
+        ("OR", [V(x), V(y)]) => xy(0x8001, *x, *y), 
+        ("AND", [V(x), V(y)]) => xy(0x8002, *x, *y), 
+        ("XOR", [V(x), V(y)]) => xy(0x8003, *x, *y), 
+        ("SUB", [V(x), V(y)]) => xy(0x8005, *x, *y), 
+        ("SHR", [V(x)]) => xy(0x8006, *x, *x), 
+        ("SHR", [V(x), V(y)]) => xy(0x8006, *x, *y), 
+        ("SUBN", [V(x), V(y)]) => xy(0x8007, *x, *y), 
+        ("SHL", [V(x)]) => xy(0x800E, *x, *x), 
+        ("SHL", [V(x), V(y)]) => xy(0x800E, *x, *y), 
+        ("RND", [V(x), k]) => 0xC000 | x << 8 | byte(k)?, 
+        ("DRW", [V(x), V(y), n]) => xy(0xD000, *x, *y) | nibble(n)?, 
+        ("SKP", [V(x)]) => 0xE09E | x << 8, 
+        ("SKNP", [V(x)]) => 0xE0A1 | x << 8, 
+        (
+            "HALT" | "CLS" | "RET" | "SCD" | "SCR" | "SCL" | "EXIT" | "LOW" | "HIGH" | "SYS" | "JP" | "CALL" | "SE" | "SNE" | "LD" | "ADD" | "OR" | "AND"
+            | "XOR" | "SUB" | "SHR" | "SUBN" | "SHL" | "RND" | "DRW" | "SKP" | "SKNP", 
+            _, 
+        ) => return Err(format!("invalid operands for {mnemonic}")), 
+        _ => return Err(format!("unknown mnemonic `{mnemonic}`")), 
+    })
+}
+
+fn operand(text: &str) -> Operand<'_> {
+    match text.to_ascii_uppercase().as_str() {
+        "I" => Operand::I, 
+        "[I]" => Operand::IndirectI, 
+        "DT" => Operand::DT, 
+        "ST" => Operand::ST, 
+        "K" => Operand::K, 
+        "F" => Operand::F, 
+        "B" => Operand::B, 
+        "HF" => Operand::HF, 
+        "R" => Operand::R, 
+        upper => match upper.strip_prefix('V').map(|x| u16::from_str_radix(x, 16)) {
+            Some(Ok(x)) if upper.len() == 2 => Operand::V(x), 
+            _ => Operand::Value(text), 
+        }, 
+    }
+}
+
+// a number or a symbol, at most `max` 
+fn value(operand: &Operand, max: u16, symbols: &HashMap<&str, u16>) -> Result<u16, String> {
+    let Operand::Value(text) = operand else {
+        return Err(format!("expected a number, found {operand:?}")); 
+    }; 
+    let value = resolve(text, symbols)?; 
+    if value > max {
+        return Err(format!("{text} is too large, at most 0x{max:X} fits")); 
+    }
+    Ok(value)
+}
+
+fn resolve(text: &str, symbols: &HashMap<&str, u16>) -> Result<u16, String> {
+    let digits = |s: &str| s.replace('_', ""); 
+    let parsed = if let Some(hex) = text.strip_prefix("0x").or_else(|| text.strip_prefix("0X")) {
+        u16::from_str_radix(&digits(hex), 16)
+    } else if let Some(binary) = text.strip_prefix("0b").or_else(|| text.strip_prefix("0B")) {
+        u16::from_str_radix(&digits(binary), 2)
+    } else if text.starts_with(|c: char| c.is_ascii_digit()) {
+        digits(text).parse()
+    } else {
+        return symbols.get(text).copied().ok_or_else(|| format!("undefined symbol `{text}`")); 
+    }; 
+    parsed.map_err(|_| format!("invalid number `{text}`"))
+}
+
+fn symbol_name(name: &str) -> Result<&str, String> {
+    let valid = name.starts_with(|c: char| c.is_ascii_alphabetic() || c == '_')
+        && name.chars().all(|c| c.is_ascii_alphanumeric() || c == '_'); 
+    if !valid {
+        return Err(format!("invalid name `{name}`")); 
+    }
+    if !matches!(operand(name), Operand::Value(_)) {
+        return Err(format!("`{name}` is a register name")); 
+    }
+    Ok(name)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*; 
+    use crate::disasm; 
+
+    #[test]
+    fn every_mnemonic_round_trips() {
+        let opcodes = [
+            0x0000, 0x00E0, 0x00EE, 0x0123, 0x1234, 0x2345, 0x3A12, 0x4B34, 0x5AB0, 0x6C56, 0x7D78, 
+            0x8120, 0x8121, 0x8122, 0x8123, 0x8124, 0x8125, 0x8126, 0x8127, 0x812E, 0x9AB0, 0xA345, 
+            0xB456, 0xC5FF, 0xD12F, 0xE39E, 0xE4A1, 0xF507, 0xF60A, 0xF715, 0xF818, 0xF91E, 0xFA29, 
+            0xFB33, 0xFC55, 0xFD65, 0x00C3, 0x00FB, 0x00FC, 0x00FD, 0x00FE, 0x00FF, 0xD120, 0xF130, 
+            0xF275, 0xF385, 
+        ]; 
+        for opcode in opcodes {
+            let text = disasm::disassemble(opcode); 
+            assert_eq!(assemble(&text), Ok(opcode.to_be_bytes().to_vec()), "{text}"); 
+        }
+    }
+
+    #[test]
+    fn labels_constants_and_data() {
+        let source = "
+            COUNT = 0x10
+            start:  LD v0, COUNT    ; comments are ignored
+                    call sub
+            end:    JP end
+            sub:    LD I, sprite
+                    RET
+            sprite: DB 0b1111_0000, 0x90, 144
+                    DW 0x1234, start
+        "; 
+        let rom = assemble(source).unwrap(); 
+        assert_eq!(
+            rom, 
+            [
+                0x60, 0x10, 0x22, 0x06, 0x12, 0x04, 0xA2, 0x0A, 0x00, 0xEE, 0xF0, 0x90, 0x90, 0x12, 0x34, 
+                0x02, 0x00, 
+            ]
+        ); 
+    }
+
+    #[test]
+    fn errors_name_the_line() {
+        let error = |source: &str| assemble(source).unwrap_err().to_string(); 
+        assert_eq!(error("CLS\nFOO V1"), "line 2: unknown mnemonic `FOO`"); 
+        assert_eq!(error("LD V0, 256"), "line 1: 256 is too large, at most 0xFF fits"); 
+        assert_eq!(error("\n\nJP nowhere"), "line 3: undefined symbol `nowhere`"); 
+        assert_eq!(error("a: CLS\na: CLS"), "line 2: `a` is already defined"); 
+        assert_eq!(error("DRW V0, V1"), "line 1: invalid operands for DRW"); 
+        assert_eq!(error("LD V0, 0xZZ"), "line 1: invalid number `0xZZ`"); 
+    }
+
+    #[test]
+    fn the_digits_rom() {
+        let source = include_str!("../roms/digits.asm"); 
+        let rom = include_bytes!("../roms/digits.ch8"); 
+        assert_eq!(assemble(source).unwrap(), rom); 
+    }
+}
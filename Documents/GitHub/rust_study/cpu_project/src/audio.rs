@@ -0,0 +1,226 @@
+/*
+    the beep: the sound timer as audio samples 
+        the CHIP-8 has a single tone, on while the sound timer is above 0; 
+        Beeper turns it into a square wave of BEEP_HZ, 16-bit mono samples 
+        at SAMPLE_RATE, and hands them to a Sink 
+
+        Beeper::play writes the samples for the emulated time since it was 
+        last called, the beep on if the sound timer was above 0 then; 
+        called after every frame, the beep starts and stops on a frame, 
+        called after every instruction (from CPU::frame_with), it follows 
+        the timer exactly 
+
+    sinks: 
+        Vec<i16>        keeps the samples, for tests 
+        WavFile         writes them to a .wav file, to listen to, or to 
+                        check the sound of a ROM without a sound card 
+        anything else (a sound card) only has to implement Sink 
+
+    the .wav file, numbers little-endian: 
+        "RIFF"          u32, the size of the rest of the file 
+        "WAVE" 
+        "fmt "          u32 16, then PCM (u16 1), mono (u16 1), the sample 
+                        rate and the bytes per second (u32 each), the bytes 
+                        and the bits per sample (u16 2 and 16) 
+        "data"          u32, the size of the samples, then the samples (i16) 
+*/
+
+use std::io::{self, Seek, SeekFrom, Write}; 
+
+use crate::cpu::CPU; 
+
+/// Samples per second. 
+pub const SAMPLE_RATE: u32 = 44_100; 
+
+/// The pitch of the beep, an A. 
+pub const BEEP_HZ: u32 = 440; 
+
+/// The level of the square wave, a quarter of full scale. 
+pub const AMPLITUDE: i16 = i16::MAX / 4; 
+
+/// Where the samples of a `Beeper` go. 
+pub trait Sink {
+    fn write(&mut self, samples: &[i16]) -> io::Result<()>; 
+}
+
+impl Sink for Vec<i16> {
+    fn write(&mut self, samples: &[i16]) -> io::Result<()> {
+        self.extend_from_slice(samples); 
+        Ok(())
+    }
+}
+
+pub struct Beeper {
+    // the cycles of the CPU when the beeper started, and the samples since 
+    start: u64, 
+    samples: u64, 
+    // whether the beep was on at the last call 
+    on: bool, 
+}
+
+/// A `Sink` writing a .wav file; `finish` puts the sizes in its header. 
+pub struct WavFile<W: Write + Seek> {
+    out: W, 
+    samples: u32, 
+}
+
+impl Beeper {
+    /// A beeper for `cpu`, which is about to run. 
+    pub fn new(cpu: &CPU) -> Beeper {
+        Beeper {
+            start: cpu.cycles(), 
+            samples: 0, 
+            on: cpu.sound_timer() > 0, 
+        }
+    }
+
+    /// Write the samples for the time `cpu` ran since the last call to `sink`. 
+    pub fn play(&mut self, cpu: &CPU, sink: &mut impl Sink) -> io::Result<()> {
+        let elapsed = cpu.cycles().saturating_sub(self.start); 
+        let due = elapsed * SAMPLE_RATE as u64 / cpu.clock_hz() as u64; 
+        let samples: Vec<i16> = (self.samples..due)
+            .map(|n| if self.on { square(n) } else { 0 })
+            .collect(); 
+        self.samples = self.samples.max(due); 
+        self.on = cpu.sound_timer() > 0; 
+        if samples.is_empty() {
+            return Ok(()); 
+        }
+        sink.write(&samples)
+    }
+}
+
+// sample `n` of the square wave, high for the first half of every period 
+fn square(n: u64) -> i16 {
+    if (n * BEEP_HZ as u64 * 2 / SAMPLE_RATE as u64).is_multiple_of(2) {
+        AMPLITUDE
+    } else {
+        -AMPLITUDE
+    }
+}
+
+impl<W: Write + Seek> WavFile<W> {
+    /// Start a .wav file in `out`. 
+    pub fn new(mut out: W) -> io::Result<WavFile<W>> {
+        out.write_all(&header(0))?; 
+        Ok(WavFile { out, samples: 0 })
+    }
+
+    /// The number of samples written. 
+    pub fn len(&self) -> u32 {
+        self.samples
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.samples == 0
+    }
+
+    /// Fill in the header, and hand back `out`. 
+    pub fn finish(mut self) -> io::Result<W> {
+        self.out.seek(SeekFrom::Start(0))?; 
+        self.out.write_all(&header(self.samples))?; 
+        self.out.seek(SeekFrom::End(0))?; 
+        self.out.flush()?; 
+        Ok(self.out)
+    }
+}
+
+impl<W: Write + Seek> Sink for WavFile<W> {
+    fn write(&mut self, samples: &[i16]) -> io::Result<()> {
+        let bytes: Vec<u8> = samples.iter().flat_map(|sample| sample.to_le_bytes()).collect(); 
+        self.out.write_all(&bytes)?; 
+        self.samples += samples.len() as u32; 
+        Ok(())
+    }
+}
+
+// the 44 bytes before `samples` samples 
+fn header(samples: u32) -> Vec<u8> {
+    let data = samples * 2; 
+    let mut bytes = Vec::with_capacity(44); 
+    bytes.extend_from_slice(b"RIFF"); 
+    bytes.extend_from_slice(&(36 + data).to_le_bytes()); 
+    bytes.extend_from_slice(b"WAVE"); 
+    bytes.extend_from_slice(b"fmt "); 
+    bytes.extend_from_slice(&16u32.to_le_bytes()); 
+    bytes.extend_from_slice(&1u16.to_le_bytes()); 
+    bytes.extend_from_slice(&1u16.to_le_bytes()); 
+    bytes.extend_from_slice(&SAMPLE_RATE.to_le_bytes()); 
+    bytes.extend_from_slice(&(SAMPLE_RATE * 2).to_le_bytes()); 
+    bytes.extend_from_slice(&2u16.to_le_bytes()); 
+    bytes.extend_from_slice(&16u16.to_le_bytes()); 
+    bytes.extend_from_slice(b"data"); 
+    bytes.extend_from_slice(&data.to_le_bytes()); 
+    bytes
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*; 
+    use std::io::Cursor; 
+
+    // half a second of beep, then nothing 
+    fn cpu() -> CPU {
+        let program: [u16; 3] = [0x6A1E, 0xFA18, 0x1204]; 
+        let rom: Vec<u8> = program.iter().flat_map(|opcode| opcode.to_be_bytes()).collect(); 
+        let mut cpu = CPU::new(); 
+        cpu.load_rom(&rom).unwrap(); 
+        cpu
+    }
+
+    #[test]
+    fn the_beep_lasts_as_long_as_the_sound_timer() {
+        let mut cpu = cpu(); 
+        let mut beeper = Beeper::new(&cpu); 
+        let mut samples: Vec<i16> = Vec::new(); 
+        for _ in 0..60 {
+            cpu.frame_with(|cpu, _| beeper.play(cpu, &mut samples).unwrap()).unwrap(); 
+        }
+        assert_eq!(samples.len(), SAMPLE_RATE as usize); 
+
+        // on from Fx18, the 2nd instruction, to the 30th tick, the 300th 
+        let per_instruction = SAMPLE_RATE as f64 / 600.0; 
+        let (on, off) = ((2.0 * per_instruction) as usize, (300.0 * per_instruction) as usize); 
+        assert!(samples[..on].iter().all(|&sample| sample == 0)); 
+        assert!(samples[on..off].iter().all(|&sample| sample.abs() == AMPLITUDE)); 
+        assert!(samples[off..].iter().all(|&sample| sample == 0)); 
+    }
+
+    #[test]
+    fn played_by_the_frame() {
+        let mut cpu = cpu(); 
+        let mut beeper = Beeper::new(&cpu); 
+        let mut samples: Vec<i16> = Vec::new(); 
+        for _ in 0..60 {
+            cpu.frame().unwrap(); 
+            beeper.play(&cpu, &mut samples).unwrap(); 
+        }
+        // from the end of the first frame to the end of the 30th 
+        let per_frame = SAMPLE_RATE as usize / 60; 
+        assert_eq!(samples.len(), 60 * per_frame); 
+        assert_eq!(samples.iter().filter(|&&sample| sample != 0).count(), 29 * per_frame); 
+    }
+
+    #[test]
+    fn square_wave_at_the_pitch() {
+        let second: Vec<i16> = (0..SAMPLE_RATE as u64).map(square).collect(); 
+        let flips = second.windows(2).filter(|pair| pair[0] != pair[1]).count(); 
+        assert_eq!(flips, 2 * BEEP_HZ as usize - 1); 
+        assert_eq!((second[0], second[SAMPLE_RATE as usize / BEEP_HZ as usize / 2 + 1]), (AMPLITUDE, -AMPLITUDE)); 
+    }
+
+    #[test]
+    fn wav_file() {
+        let mut wav = WavFile::new(Cursor::new(Vec::new())).unwrap(); 
+        wav.write(&[1, -2]).unwrap(); 
+        wav.write(&[3]).unwrap(); 
+        assert_eq!(wav.len(), 3); 
+        let bytes = wav.finish().unwrap().into_inner(); 
+
+        let u32_at = |n: usize| u32::from_le_bytes(bytes[n..n + 4].try_into().unwrap()); 
+        assert_eq!(bytes.len(), 44 + 6); 
+        assert_eq!((&bytes[..4], &bytes[8..16], &bytes[36..40]), (&b"RIFF"[..], &b"WAVEfmt "[..], &b"data"[..])); 
+        assert_eq!((u32_at(4), u32_at(24), u32_at(40)), (42, SAMPLE_RATE, 6)); 
+        assert_eq!(bytes[44..], [0x01, 0x00, 0xFE, 0xFF, 0x03, 0x00]); 
+    }
+}
@@ -0,0 +1,54 @@
+/*
+    assembling a source file into a ROM 
+        cargo run --bin asm -- roms/digits.asm              # roms/digits.ch8 
+        cargo run --bin asm -- game.asm -o build/game.ch8 
+
+    the syntax is described in asm.rs; errors are reported as 
+        file:line: message, and no ROM is written 
+*/
+
+use std::{env, fs, path::PathBuf, process}; 
+
+use cpu::asm; 
+
+fn main() {
+    let (source, output) = parse_args(env::args().skip(1)).unwrap_or_else(|err| {
+        eprintln!("Problem parsing arguments: {err}"); 
+        eprintln!("usage: asm <source> [-o <rom>]"); 
+        process::exit(1); 
+    }); 
+
+    let text = fs::read_to_string(&source).unwrap_or_else(|err| {
+        eprintln!("{}: {err}", source.display()); 
+        process::exit(1); 
+    }); 
+    let rom = asm::assemble(&text).unwrap_or_else(|err| {
+        eprintln!("{}:{}: {}", source.display(), err.line, err.message); 
+        process::exit(1); 
+    }); 
+
+    if let Err(err) = fs::write(&output, &rom) {
+        eprintln!("{}: {err}", output.display()); 
+        process::exit(1); 
+    }
+    println!("{}: {} bytes", output.display(), rom.len()); 
+}
+
+fn parse_args(mut args: impl Iterator<Item = String>) -> Result<(PathBuf, PathBuf), String> {
+    let mut source = None; 
+    let mut output = None; 
+
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "-o" => output = Some(PathBuf::from(args.next().ok_or("-o needs a value")?)), 
+            flag if flag.starts_with('-') => return Err(format!("unknown argument `{flag}`")), 
+            _ if source.is_some() => return Err(format!("more than one source given: `{arg}`")), 
+            _ => source = Some(PathBuf::from(arg)), 
+        }
+    }
+
+    let source: PathBuf = source.ok_or("no source given")?; 
+    // next to the source by default 
+    let output = output.unwrap_or_else(|| source.with_extension("ch8")); 
+    Ok((source, output))
+}
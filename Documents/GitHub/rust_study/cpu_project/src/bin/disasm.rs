@@ -0,0 +1,48 @@
+/*
+    disassembling a ROM file 
+        cargo run --bin disasm -- roms/digits.ch8 
+        cargo run --bin disasm -- dump.bin --origin 0 
+
+    prints every word with its address, opcode and mnemonic, labelling 
+        the targets of calls, jumps and LD I (see disasm.rs); the ROM is 
+        taken to be loaded at 0x200 unless --origin says otherwise 
+*/
+
+use std::{env, fs, path::PathBuf, process}; 
+
+use cpu::{cpu::PROGRAM_START, disasm}; 
+
+fn main() {
+    let (rom, origin) = parse_args(env::args().skip(1)).unwrap_or_else(|err| {
+        eprintln!("Problem parsing arguments: {err}"); 
+        eprintln!("usage: disasm <rom> [--origin <hex address>]"); 
+        process::exit(1); 
+    }); 
+
+    let bytes = fs::read(&rom).unwrap_or_else(|err| {
+        eprintln!("{}: {err}", rom.display()); 
+        process::exit(1); 
+    }); 
+    print!("{}", disasm::listing(&bytes, origin)); 
+}
+
+fn parse_args(mut args: impl Iterator<Item = String>) -> Result<(PathBuf, usize), String> {
+    let mut rom = None; 
+    let mut origin = PROGRAM_START; 
+
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "--origin" => {
+                let value = args.next().ok_or("--origin needs a value")?; 
+                let digits = value.trim_start_matches("0x"); 
+                origin = usize::from_str_radix(digits, 16).map_err(|_| format!("invalid address `{value}`"))?; 
+            }
+            flag if flag.starts_with('-') => return Err(format!("unknown argument `{flag}`")), 
+            _ if rom.is_some() => return Err(format!("more than one ROM given: `{arg}`")), 
+            _ => rom = Some(PathBuf::from(arg)), 
+        }
+    }
+
+    let rom = rom.ok_or("no ROM given")?; 
+    Ok((rom, origin))
+}
@@ -0,0 +1,187 @@
+/*
+    running a ROM without a terminal, to check what it draws (in CI) 
+        cargo run --bin headless -- roms/digits.ch8 --frames 120 
+        cargo run --bin headless -- game.ch8 --frames 600 --at 60,300 --format pbm 
+        cargo run --bin headless -- game.ch8 --script keys.txt --out shots/game 
+
+    the ROM runs for a number of frames of the same number of instructions 
+        each, as fast as it can, and the display is written as an image 
+        after every frame asked for (the last one by default), to 
+        <out>-<frame>.png, out being the ROM without its .ch8; frame 0 is 
+        the screen before the first frame 
+
+    a line is printed for every image: the file and the fingerprint of the 
+        screen (see display.rs), for a script to compare 
+
+    once the program halts no more frames run, and the images of the 
+        frames after show the screen it halted with 
+
+    options: 
+        --frames <n>        the frames to run (60, a second of emulated time) 
+        --ipf <n>           instructions per frame (10, as at 600Hz) 
+        --at <frames>       write the image after these frames, a list like 
+                            10,20,30; can be given more than once 
+        --format png|pbm    the image format (png, see image.rs) 
+        --scale <n>         every pixel as a square of n x n (1) 
+        --out <prefix>      the images are <prefix>-<frame>.png 
+        --preset <name>     the quirks of vip (the default), chip48 or schip 
+        --script <file>     play the keypad from a script (see keypad.rs) 
+*/
+
+use std::{env, fs, path::PathBuf, process}; 
+
+use cpu::{
+    cpu::{Quirks, CPU, DEFAULT_CLOCK_HZ, TIMER_HZ}, 
+    image::Format, 
+    keypad::Script, 
+}; 
+
+struct Args {
+    rom: PathBuf, 
+    frames: u64, 
+    ipf: u32, 
+    at: Vec<u64>, 
+    format: Format, 
+    scale: usize, 
+    out: PathBuf, 
+    quirks: Quirks, 
+    script: Option<PathBuf>, 
+}
+
+fn main() {
+    let args = parse_args(env::args().skip(1)).unwrap_or_else(|err| {
+        eprintln!("Problem parsing arguments: {err}"); 
+        eprintln!("usage: headless <rom> [--frames <n>] [--ipf <n>] [--at <frames>] [--format png|pbm] [--scale <n>] [--out <prefix>] [--preset vip|chip48|schip] [--script <file>]"); 
+        process::exit(1); 
+    }); 
+
+    let rom = fs::read(&args.rom).unwrap_or_else(|err| {
+        eprintln!("{}: {err}", args.rom.display()); 
+        process::exit(1); 
+    }); 
+
+    let script = args.script.as_ref().map(|path| {
+        let text = fs::read_to_string(path).unwrap_or_else(|err| {
+            eprintln!("{}: {err}", path.display()); 
+            process::exit(1); 
+        }); 
+        Script::parse(&text).unwrap_or_else(|err| {
+            eprintln!("{}:{}: {}", path.display(), err.line, err.message); 
+            process::exit(1); 
+        })
+    }); 
+
+    let mut cpu = CPU::new(); 
+    cpu.set_clock_hz(args.ipf * TIMER_HZ); 
+    cpu.set_quirks(args.quirks); 
+    if let Err(err) = cpu.load_rom(&rom) {
+        eprintln!("{}: {err}", args.rom.display()); 
+        process::exit(1); 
+    }
+
+    let mut running = true; 
+    for frame in 0..=args.frames {
+        if args.at.contains(&frame) {
+            screenshot(&cpu, frame, &args); 
+        }
+        if frame == args.frames || !running {
+            continue; 
+        }
+        cpu.set_keypad(script.as_ref().map_or(0, |script| script.keys(frame))); 
+        running = cpu.frame().unwrap_or_else(|err| {
+            eprintln!("{}: frame {}: {err}", args.rom.display(), frame + 1); 
+            process::exit(1); 
+        }); 
+        if !running {
+            eprintln!("{}: halted in frame {}", args.rom.display(), frame + 1); 
+        }
+    }
+}
+
+// write the display after `frame` as an image, and say so 
+fn screenshot(cpu: &CPU, frame: u64, args: &Args) {
+    let path = format!("{}-{frame:04}.{}", args.out.display(), args.format.extension()); 
+    let display = cpu.display(); 
+    if let Err(err) = fs::write(&path, args.format.encode(display, args.scale)) {
+        eprintln!("{path}: {err}"); 
+        process::exit(1); 
+    }
+    println!("{path} {:016x}", display.fingerprint()); 
+}
+
+fn parse_args(mut args: impl Iterator<Item = String>) -> Result<Args, String> {
+    let mut rom = None; 
+    let mut frames = TIMER_HZ as u64; 
+    let mut ipf = DEFAULT_CLOCK_HZ / TIMER_HZ; 
+    let mut at = Vec::new(); 
+    let mut format = Format::Png; 
+    let mut scale = 1; 
+    let mut out = None; 
+    let mut quirks = Quirks::default(); 
+    let mut script = None; 
+
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "--frames" => {
+                let value = args.next().ok_or("--frames needs a value")?; 
+                frames = value.parse().map_err(|_| format!("invalid number of frames `{value}`"))?; 
+            }
+            "--ipf" => {
+                let value = args.next().ok_or("--ipf needs a value")?; 
+                ipf = value
+                    .parse()
+                    .ok()
+                    .filter(|&ipf| ipf > 0 && ipf <= u32::MAX / TIMER_HZ)
+                    .ok_or(format!("invalid number of instructions per frame `{value}`"))?; 
+            }
+            "--at" => {
+                let value = args.next().ok_or("--at needs frames")?; 
+                for frame in value.split(',') {
+                    at.push(frame.trim().parse().map_err(|_| format!("invalid frame `{frame}`"))?); 
+                }
+            }
+            "--format" => {
+                let name = args.next().ok_or("--format needs a format")?; 
+                format = Format::from_extension(&name).ok_or(format!("unknown image format `{name}`"))?; 
+            }
+            "--scale" => {
+                let value = args.next().ok_or("--scale needs a value")?; 
+                scale = value
+                    .parse()
+                    .ok()
+                    .filter(|&scale| (1..=64).contains(&scale))
+                    .ok_or(format!("invalid scale `{value}`, 1 to 64"))?; 
+            }
+            "--out" => out = Some(PathBuf::from(args.next().ok_or("--out needs a prefix")?)), 
+            "--preset" => {
+                let name = args.next().ok_or("--preset needs a name")?; 
+                quirks = Quirks::preset(&name).ok_or(format!("unknown preset `{name}`"))?; 
+            }
+            "--script" => script = Some(PathBuf::from(args.next().ok_or("--script needs a file")?)), 
+            flag if flag.starts_with('-') => return Err(format!("unknown argument `{flag}`")), 
+            _ if rom.is_some() => return Err(format!("more than one ROM given: `{arg}`")), 
+            _ => rom = Some(PathBuf::from(arg)), 
+        }
+    }
+
+    let rom: PathBuf = rom.ok_or("no ROM given")?; 
+    if at.is_empty() {
+        at.push(frames); 
+    }
+    if let Some(last) = at.iter().find(|&&frame| frame > frames) {
+        return Err(format!("frame {last} is after the last frame, {frames}")); 
+    }
+    // next to the ROM by default 
+    let out = out.unwrap_or_else(|| rom.with_extension("")); 
+    Ok(Args {
+        rom, 
+        frames, 
+        ipf, 
+        at, 
+        format, 
+        scale, 
+        out, 
+        quirks, 
+        script, 
+    })
+}
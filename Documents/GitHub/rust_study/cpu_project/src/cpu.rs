@@ -0,0 +1,1221 @@
+/*
+    CHIP-8 CPU Implementation 
+        every instruction is two bytes, stored big-endian, and read as 
+        four nibbles 0xCXYD: c selects the opcode group, x and y name 
+        registers, d (or the low byte kk, or the low 12 bits nnn) is an 
+        operand 
+
+    the complete instruction set (35 opcodes): 
+        0000        halt (this emulator's own; a real 0nnn is ignored) 
+        00E0        clear the display 
+        00EE        return from a subroutine 
+        1nnn        jump to nnn 
+        2nnn        call the subroutine at nnn 
+        3xkk 4xkk   skip the next instruction if Vx == kk (!= kk) 
+        5xy0 9xy0   skip the next instruction if Vx == Vy (!= Vy) 
+        6xkk 7xkk   Vx = kk, Vx += kk (no carry flag) 
+        8xy0        Vx = Vy 
+        8xy1 8xy2 8xy3      Vx |= Vy, &= Vy, ^= Vy (VF reset to 0) 
+        8xy4 8xy5 8xy7      Vx += Vy, Vx -= Vy, Vx = Vy - Vx 
+                            (VF = carry, or VF = 1 when there is no borrow) 
+        8xy6 8xyE   Vx = Vy >> 1, Vy << 1 (VF = the bit shifted out) 
+        Annn        I = nnn 
+        Bnnn        jump to nnn + V0 
+        Cxkk        Vx = random byte & kk 
+        Dxyn        draw the n-byte sprite at I at (Vx, Vy); VF = collision 
+        Ex9E ExA1   skip if the key Vx is pressed (not pressed) 
+        Fx07 Fx15   Vx = delay timer, delay timer = Vx 
+        Fx18        sound timer = Vx 
+        Fx0A        wait for a key to be pressed and released again, 
+                    then store the key in Vx (as the COSMAC VIP did) 
+        Fx1E        I += Vx 
+        Fx29        I = address of the font sprite for the digit Vx 
+        Fx33        BCD of Vx into I, I+1, I+2 
+        Fx55 Fx65   store V0..=Vx at I, load them from I (I += x + 1) 
+
+    when an operation sets VF, the flag is written after the result, so 
+        the flag wins when VF is also the destination 
+
+    the shifts and Fx55/Fx65 follow the original COSMAC VIP interpreter 
+
+    machine state beyond the registers: 
+        I               16-bit index register (sprites, BCD, Fx55/Fx65) 
+        delay, sound    timers counting down to 0 at 60Hz; the sound timer 
+                        beeps while it is above 0 (audio.rs makes the beep) 
+        keypad          which of the 16 keys (0-F) are held down 
+        font            sprites for the hex digits, loaded at FONT_ADDR 
+                        whenever the machine is reset 
+
+    the timers run on emulated time, not on the wall clock: the CPU is 
+        given a clock speed (instructions per second) and the timers tick 
+        once every clock_hz / 60 instructions, so 60 ticks happen per 
+        emulated second however fast the instructions are executed 
+
+    programs (ROMs) are loaded at 0x200, where execution starts; the 512 
+        bytes below belonged to the interpreter itself on the COSMAC VIP, 
+        and now hold the font 
+
+    the interpreters of the past disagree on a few instructions; Quirks 
+        picks the behaviour, the COSMAC VIP one by default: 
+        vf_reset    8xy1/8xy2/8xy3 reset VF to 0 
+        memory      Fx55/Fx65 leave I pointing past the last register 
+        clipping    sprites are clipped at the edges instead of wrapping 
+        shifting    8xy6/8xyE shift Vx in place and ignore Vy (off) 
+        jumping     Bxnn jumps to xnn + Vx instead of nnn + V0 (off) 
+        super_chip  the SUPER-CHIP instructions below (off) 
+    presets for the well-known interpreters: 
+                    vf_reset memory clipping shifting jumping super_chip 
+        COSMAC_VIP  on       on     on       off      off     off 
+        CHIP_48     off      off    on       on       on      off 
+        SUPER_CHIP  off      off    on       on       on      on 
+        (CHIP-48 really added x to I in Fx55/Fx65 rather than x + 1; 
+        like SUPER-CHIP, it is treated here as leaving I alone) 
+
+    the SUPER-CHIP instructions: 
+        00Cn        scroll the display down n rows 
+        00FB 00FC   scroll right, left by 4 columns 
+        00FD        exit the interpreter (halts, like 0000) 
+        00FE 00FF   lo-res 64x32, hi-res 128x64 (the display is cleared) 
+        Dxy0        draw a 16x16 sprite, 32 bytes at I 
+        Fx30        I = address of the 8x10 font sprite for the digit Vx 
+        Fx75 Fx85   store V0..=Vx in the RPL flags, load them (x < 8) 
+
+    save_state and load_state capture and restore the whole machine, see 
+        state.rs; replay.rs records the keypad frame by frame on top of it 
+
+    a faulty program does not bring the emulator down: step returns a 
+        CpuError naming the address of the offending instruction when 
+        the stack over- or underflows, when memory outside the 4KB would 
+        be read or written, or when the opcode means nothing; the machine 
+        is left as it was just before that instruction did any harm 
+*/
+
+use std::fmt; 
+
+use crate::{
+    decode::{decode, Instruction}, 
+    display::Display, 
+    state::State, 
+}; 
+
+/// Where programs are loaded, and where execution starts. 
+pub const PROGRAM_START: usize = 0x200; 
+
+/// The largest ROM that fits between `PROGRAM_START` and the end of memory. 
+pub const MAX_ROM_SIZE: usize = 4096 - PROGRAM_START; 
+
+// where the sprites of the hex digits 0-F live, 5 bytes each 
+const FONT_ADDR: u16 = 0x050; 
+
+// the SUPER-CHIP 8x10 digits, 10 bytes each, right after the small ones 
+const BIG_FONT_ADDR: u16 = 0x0A0; 
+
+// 4x5 pixel digits, one byte per row (only the high nibble is drawn) 
+const FONT: [u8; 80] = [
+    0xF0, 0x90, 0x90, 0x90, 0xF0, // 0 
+    0x20, 0x60, 0x20, 0x20, 0x70, // 1 
+    0xF0, 0x10, 0xF0, 0x80, 0xF0, // 2 
+    0xF0, 0x10, 0xF0, 0x10, 0xF0, // 3 
+    0x90, 0x90, 0xF0, 0x10, 0x10, // 4 
+    0xF0, 0x80, 0xF0, 0x10, 0xF0, // 5 
+    0xF0, 0x80, 0xF0, 0x90, 0xF0, // 6 
+    0xF0, 0x10, 0x20, 0x40, 0x40, // 7 
+    0xF0, 0x90, 0xF0, 0x90, 0xF0, // 8 
+    0xF0, 0x90, 0xF0, 0x10, 0xF0, // 9 
+    0xF0, 0x90, 0xF0, 0x90, 0x90, // A 
+    0xE0, 0x90, 0xE0, 0x90, 0xE0, // B 
+    0xF0, 0x80, 0x80, 0x80, 0xF0, // C 
+    0xE0, 0x90, 0x90, 0x90, 0xE0, // D 
+    0xF0, 0x80, 0xF0, 0x80, 0xF0, // E 
+    0xF0, 0x80, 0xF0, 0x80, 0x80, // F 
+]; 
+
+const BIG_FONT: [u8; 160] = [
+    0xFF, 0xFF, 0xC3, 0xC3, 0xC3, 0xC3, 0xC3, 0xC3, 0xFF, 0xFF, // 0 
+    0x18, 0x78, 0x78, 0x18, 0x18, 0x18, 0x18, 0x18, 0xFF, 0xFF, // 1 
+    0xFF, 0xFF, 0x03, 0x03, 0xFF, 0xFF, 0xC0, 0xC0, 0xFF, 0xFF, // 2 
+    0xFF, 0xFF, 0x03, 0x03, 0xFF, 0xFF, 0x03, 0x03, 0xFF, 0xFF, // 3 
+    0xC3, 0xC3, 0xC3, 0xC3, 0xFF, 0xFF, 0x03, 0x03, 0x03, 0x03, // 4 
+    0xFF, 0xFF, 0xC0, 0xC0, 0xFF, 0xFF, 0x03, 0x03, 0xFF, 0xFF, // 5 
+    0xFF, 0xFF, 0xC0, 0xC0, 0xFF, 0xFF, 0xC3, 0xC3, 0xFF, 0xFF, // 6 
+    0xFF, 0xFF, 0x03, 0x03, 0x06, 0x0C, 0x18, 0x18, 0x18, 0x18, // 7 
+    0xFF, 0xFF, 0xC3, 0xC3, 0xFF, 0xFF, 0xC3, 0xC3, 0xFF, 0xFF, // 8 
+    0xFF, 0xFF, 0xC3, 0xC3, 0xFF, 0xFF, 0x03, 0x03, 0xFF, 0xFF, // 9 
+    0x7E, 0xFF, 0xC3, 0xC3, 0xC3, 0xFF, 0xFF, 0xC3, 0xC3, 0xC3, // A 
+    0xFC, 0xFC, 0xC3, 0xC3, 0xFC, 0xFC, 0xC3, 0xC3, 0xFC, 0xFC, // B 
+    0x3C, 0xFF, 0xC3, 0xC0, 0xC0, 0xC0, 0xC0, 0xC3, 0xFF, 0x3C, // C 
+    0xFC, 0xFE, 0xC3, 0xC3, 0xC3, 0xC3, 0xC3, 0xC3, 0xFE, 0xFC, // D 
+    0xFF, 0xFF, 0xC0, 0xC0, 0xFF, 0xFF, 0xC0, 0xC0, 0xFF, 0xFF, // E 
+    0xFF, 0xFF, 0xC0, 0xC0, 0xFF, 0xFF, 0xC0, 0xC0, 0xC0, 0xC0, // F 
+]; 
+
+/// Timers count down this many times per second. 
+pub const TIMER_HZ: u32 = 60; 
+
+/// A common speed for CHIP-8 games, in instructions per second. 
+pub const DEFAULT_CLOCK_HZ: u32 = 600; 
+
+/// Which of the incompatible behaviours to emulate. 
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Quirks {
+    pub vf_reset: bool, 
+    pub memory: bool, 
+    pub clipping: bool, 
+    pub shifting: bool, 
+    pub jumping: bool, 
+    pub super_chip: bool, 
+}
+
+/// A fault in the program; `pc` is the address of the instruction. 
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum CpuError {
+    StackOverflow { pc: usize }, 
+    StackUnderflow { pc: usize }, 
+    MemoryOutOfBounds { pc: usize, addr: usize }, 
+    UnknownOpcode { pc: usize, opcode: u16 }, 
+}
+
+/// The instruction that `step` executed. 
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Executed {
+    pub pc: usize, 
+    pub opcode: u16, 
+    /// This was the halt (0000, or 00FD on a SUPER-CHIP); the PC stays on it. 
+    pub halted: bool, 
+}
+
+#[derive(Debug, PartialEq)]
+pub enum RomError {
+    Empty, 
+    TooLarge(usize), 
+}
+
+// definintion of the CPU 
+#[allow(clippy::upper_case_acronyms)]
+pub struct CPU {
+    registers: [u8; 16], 
+    pc: usize, // program counter 
+    memory: [u8; 4096], 
+    stack: [u16; 16], 
+    stack_pointer: usize, 
+    i: u16, // index register, holds memory addresses 
+    delay_timer: u8, 
+    sound_timer: u8, 
+    keypad: [bool; 16], // which of the keys 0-F are held down 
+    key_wait: Option<u8>, // the key pressed during Fx0A, until it is released 
+    display: Display, 
+    rng: u32, // state of the random number generator for Cxkk 
+    clock_hz: u32, // instructions per emulated second 
+    cycles: u64, // instructions executed since the last reset 
+    quirks: Quirks, 
+    rpl: [u8; 8], // the SUPER-CHIP flag registers, for Fx75/Fx85 
+}
+
+impl Quirks {
+    pub const COSMAC_VIP: Quirks = Quirks {
+        vf_reset: true, 
+        memory: true, 
+        clipping: true, 
+        shifting: false, 
+        jumping: false, 
+        super_chip: false, 
+    }; 
+
+    pub const CHIP_48: Quirks = Quirks {
+        vf_reset: false, 
+        memory: false, 
+        clipping: true, 
+        shifting: true, 
+        jumping: true, 
+        super_chip: false, 
+    }; 
+
+    pub const SUPER_CHIP: Quirks = Quirks {
+        super_chip: true, 
+        ..Quirks::CHIP_48
+    }; 
+
+    /// The preset called `name`: vip, chip48 or schip. 
+    pub fn preset(name: &str) -> Option<Quirks> {
+        match name.to_ascii_lowercase().as_str() {
+            "vip" | "cosmac-vip" => Some(Quirks::COSMAC_VIP), 
+            "chip48" | "chip-48" => Some(Quirks::CHIP_48), 
+            "schip" | "super-chip" => Some(Quirks::SUPER_CHIP), 
+            _ => None, 
+        }
+    }
+}
+
+impl Default for Quirks {
+    fn default() -> Quirks {
+        Quirks::COSMAC_VIP 
+    }
+}
+
+impl fmt::Display for CpuError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            CpuError::StackOverflow { pc } => {
+                write!(f, "stack overflow at {pc:03X}: more than 16 nested calls")
+            }
+            CpuError::StackUnderflow { pc } => {
+                write!(f, "stack underflow at {pc:03X}: return without a call")
+            }
+            CpuError::MemoryOutOfBounds { pc, addr } => {
+                write!(f, "memory access out of bounds at {pc:03X}: address {addr:04X}")
+            }
+            CpuError::UnknownOpcode { pc, opcode } => {
+                write!(f, "unknown opcode {opcode:04X} at {pc:03X}")
+            }
+        }
+    }
+}
+
+impl std::error::Error for CpuError {}
+
+impl fmt::Display for RomError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            RomError::Empty => write!(f, "the ROM is empty"), 
+            RomError::TooLarge(size) => {
+                write!(f, "the ROM is {size} bytes, but only {MAX_ROM_SIZE} fit in memory")
+            }
+        }
+    }
+}
+
+impl std::error::Error for RomError {}
+
+impl CPU {
+    pub fn new() -> CPU {
+        let mut cpu = CPU {
+            registers: [0; 16], 
+            memory: [0; 4096], 
+            pc: 0, 
+            stack: [0; 16], 
+            stack_pointer: 0, 
+            i: 0, 
+            delay_timer: 0, 
+            sound_timer: 0, 
+            keypad: [false; 16], 
+            key_wait: None, 
+            display: Display::new(), 
+            rng: 0, 
+            clock_hz: DEFAULT_CLOCK_HZ, 
+            cycles: 0, 
+            quirks: Quirks::default(), 
+            rpl: [0; 8], 
+        }; 
+        cpu.reset(); 
+        cpu
+    }
+
+    /// Back to the power-on state: everything cleared and the font loaded. 
+    /// The clock speed and the quirks are kept. 
+    pub fn reset(&mut self) {
+        self.registers = [0; 16]; 
+        self.memory = [0; 4096]; 
+        self.pc = PROGRAM_START; 
+        self.stack = [0; 16]; 
+        self.stack_pointer = 0; 
+        self.i = 0; 
+        self.delay_timer = 0; 
+        self.sound_timer = 0; 
+        self.keypad = [false; 16]; 
+        self.key_wait = None; 
+        self.display.set_hires(false); 
+        self.rng = 0x2545_F491; 
+        self.cycles = 0; 
+
+        let font = FONT_ADDR as usize; 
+        self.memory[font..font + FONT.len()].copy_from_slice(&FONT); 
+        let font = BIG_FONT_ADDR as usize; 
+        self.memory[font..font + BIG_FONT.len()].copy_from_slice(&BIG_FONT); 
+    }
+
+    /// Reset the machine and load `rom` at `PROGRAM_START`. 
+    pub fn load_rom(&mut self, rom: &[u8]) -> Result<(), RomError> {
+        if rom.is_empty() {
+            return Err(RomError::Empty); 
+        }
+        if rom.len() > MAX_ROM_SIZE {
+            return Err(RomError::TooLarge(rom.len())); 
+        }
+
+        self.reset(); 
+        self.memory[PROGRAM_START..PROGRAM_START + rom.len()].copy_from_slice(rom); 
+        Ok(())
+    }
+
+    /// Instructions per second; the timers tick every `hz / 60` of them. 
+    /// 
+    /// # Panics 
+    /// 
+    /// The `set_clock_hz` function will panic if `hz` is below 60. 
+    pub fn set_clock_hz(&mut self, hz: u32) {
+        assert!(hz >= TIMER_HZ, "the clock must run at {TIMER_HZ}Hz or faster"); 
+        self.clock_hz = hz; 
+    }
+
+    pub fn clock_hz(&self) -> u32 {
+        self.clock_hz
+    }
+
+    /// The instructions executed since the reset, a measure of emulated time. 
+    pub fn cycles(&self) -> u64 {
+        self.cycles
+    }
+
+    pub fn set_quirks(&mut self, quirks: Quirks) {
+        self.quirks = quirks; 
+    }
+
+    pub fn display(&self) -> &Display {
+        &self.display
+    }
+
+    pub fn pc(&self) -> usize {
+        self.pc
+    }
+
+    pub fn set_pc(&mut self, pc: usize) {
+        self.pc = pc; 
+    }
+
+    /// V0 to VF. 
+    pub fn registers(&self) -> &[u8; 16] {
+        &self.registers
+    }
+
+    pub fn registers_mut(&mut self) -> &mut [u8; 16] {
+        &mut self.registers
+    }
+
+    /// The index register I. 
+    pub fn index(&self) -> u16 {
+        self.i
+    }
+
+    pub fn set_index(&mut self, i: u16) {
+        self.i = i; 
+    }
+
+    pub fn delay_timer(&self) -> u8 {
+        self.delay_timer
+    }
+
+    pub fn sound_timer(&self) -> u8 {
+        self.sound_timer
+    }
+
+    /// The return addresses of the calls in progress, innermost last. 
+    pub fn stack(&self) -> &[u16] {
+        &self.stack[..self.stack_pointer]
+    }
+
+    pub fn memory(&self) -> &[u8] {
+        &self.memory
+    }
+
+    pub fn memory_mut(&mut self) -> &mut [u8] {
+        &mut self.memory
+    }
+
+    /// The keys held down, bit k for the key k. 
+    pub fn keypad(&self) -> u16 {
+        (0..16).filter(|&k| self.keypad[k]).fold(0, |keys, k| keys | 1 << k)
+    }
+
+    pub fn set_keypad(&mut self, keys: u16) {
+        for (k, pressed) in self.keypad.iter_mut().enumerate() {
+            *pressed = keys & 1 << k != 0; 
+        }
+    }
+
+    /// Everything about the machine, to be put back by `load_state`. 
+    pub fn save_state(&self) -> State {
+        State {
+            pc: self.pc as u16, 
+            i: self.i, 
+            registers: self.registers, 
+            stack: self.stack, 
+            stack_pointer: self.stack_pointer as u8, 
+            delay_timer: self.delay_timer, 
+            sound_timer: self.sound_timer, 
+            keypad: self.keypad(), 
+            key_wait: self.key_wait, 
+            rng: self.rng, 
+            cycles: self.cycles, 
+            clock_hz: self.clock_hz, 
+            quirks: self.quirks, 
+            rpl: self.rpl, 
+            memory: Box::new(self.memory), 
+            display: self.display.clone(), 
+        }
+    }
+
+    /// Go back to `state`, the clock speed and the quirks included. 
+    pub fn load_state(&mut self, state: &State) {
+        self.pc = state.pc as usize; 
+        self.i = state.i; 
+        self.registers = state.registers; 
+        self.stack = state.stack; 
+        self.stack_pointer = state.stack_pointer as usize; 
+        self.delay_timer = state.delay_timer; 
+        self.sound_timer = state.sound_timer; 
+        self.set_keypad(state.keypad); 
+        self.key_wait = state.key_wait; 
+        self.rng = state.rng; 
+        self.cycles = state.cycles; 
+        self.clock_hz = state.clock_hz; 
+        self.quirks = state.quirks; 
+        self.rpl = state.rpl; 
+        self.memory = *state.memory; 
+        self.display = state.display.clone(); 
+    }
+
+    // one 60Hz tick: both timers count down and stop at 0 
+    fn tick_timers(&mut self) {
+        self.delay_timer = self.delay_timer.saturating_sub(1); 
+        self.sound_timer = self.sound_timer.saturating_sub(1); 
+    }
+
+    // reading opcode from memory 
+    fn read_opcode(&self) -> Result<u16, CpuError> {
+        let p = self.pc; 
+        if p + 1 >= self.memory.len() {
+            return Err(CpuError::MemoryOutOfBounds { pc: p, addr: p + 1 }); 
+        }
+        let op_byte1 = self.memory[p] as u16; 
+        let op_byte2 = self.memory[p + 1] as u16; 
+
+        Ok(op_byte1 << 8 | op_byte2)
+    }
+
+    /// Run until the program halts, without any pacing. 
+    pub fn run(&mut self) -> Result<(), CpuError> {
+        while !self.step()?.halted {}
+        Ok(())
+    }
+
+    /// Run for a 60th of an emulated second; false once the program has halted. 
+    pub fn frame(&mut self) -> Result<bool, CpuError> {
+        self.frame_with(|_, _| {})
+    }
+
+    /// Like `frame`, calling `each` after every instruction (the halt included). 
+    pub fn frame_with(&mut self, mut each: impl FnMut(&CPU, &Executed)) -> Result<bool, CpuError> {
+        for _ in 0..self.clock_hz / TIMER_HZ {
+            let executed = self.step()?; 
+            each(self, &executed); 
+            if executed.halted {
+                return Ok(false); 
+            }
+        }
+        Ok(true)
+    }
+
+    /// Execute one instruction and tell which one it was. 
+    pub fn step(&mut self) -> Result<Executed, CpuError> {
+        let pc = self.pc; 
+        let opcode = self.read_opcode()?; 
+        self.pc += 2; 
+
+        let mut executed = Executed { pc, opcode, halted: false }; 
+        match self.execute(opcode) {
+            Ok(true) => {}
+            Ok(false) => {
+                // a halted machine stays halted 
+                self.pc = pc; 
+                executed.halted = true; 
+                return Ok(executed); 
+            }
+            Err(e) => {
+                // back on the faulty instruction 
+                self.pc = pc; 
+                return Err(e); 
+            }
+        }
+
+        // timers follow emulated time: every clock_hz / 60 instructions 
+        self.cycles += 1; 
+        if self.cycles.is_multiple_of((self.clock_hz / TIMER_HZ) as u64) {
+            self.tick_timers(); 
+        }
+        Ok(executed)
+    }
+
+    fn execute(&mut self, opcode: u16) -> Result<bool, CpuError> {
+        use Instruction::*; 
+
+        let unknown = CpuError::UnknownOpcode { pc: self.here(), opcode }; 
+        let instruction = decode(opcode).ok_or(unknown)?; 
+        let sc = self.quirks.super_chip; 
+
+        // dispatches execution 
+        match instruction {
+            Halt                    => { return Ok(false); }, // terminate execution 
+            Clear                   => self.clear(), 
+            Return                  => self.ret()?, 
+            ScrollDown(n) if sc     => self.display.scroll_down(n as usize), 
+            ScrollRight if sc       => self.display.scroll_right(4), 
+            ScrollLeft if sc        => self.display.scroll_left(4), 
+            Exit if sc              => { return Ok(false); }, 
+            LoRes if sc             => self.display.set_hires(false), 
+            HiRes if sc             => self.display.set_hires(true), 
+            // 0nnn, and 00Cn-00FF without the SUPER-CHIP: machine code routines, ignored 
+            Sys(_) | ScrollDown(_) | ScrollRight | ScrollLeft | Exit | LoRes | HiRes => {}, 
+            Jump(nnn)               => self.jump(nnn), 
+            Call(nnn)               => self.call(nnn)?, 
+            SkipEqualByte(x, kk)    => self.skip_if(self.reg(x) == kk), 
+            SkipNotEqualByte(x, kk) => self.skip_if(self.reg(x) != kk), 
+            SkipEqual(x, y)         => self.skip_if(self.reg(x) == self.reg(y)), 
+            LoadByte(x, kk)         => self.registers[x as usize] = kk, 
+            AddByte(x, kk)          => self.registers[x as usize] = self.reg(x).wrapping_add(kk), 
+            Move(x, y)              => self.registers[x as usize] = self.reg(y), 
+            Or(x, y)                => self.logic(x, self.reg(x) | self.reg(y)), 
+            And(x, y)               => self.logic(x, self.reg(x) & self.reg(y)), 
+            Xor(x, y)               => self.logic(x, self.reg(x) ^ self.reg(y)), 
+            Add(x, y)               => self.add_xy(x, y), 
+            Sub(x, y)               => self.sub_xy(x, x, y), 
+            ShiftRight(x, y)        => self.shift_right(x, self.shift_source(x, y)), 
+            SubN(x, y)              => self.sub_xy(x, y, x), 
+            ShiftLeft(x, y)         => self.shift_left(x, self.shift_source(x, y)), 
+            SkipNotEqual(x, y)      => self.skip_if(self.reg(x) != self.reg(y)), 
+            LoadIndex(nnn)          => self.i = nnn, 
+            JumpOffset(nnn)         => self.jump_with_offset(nnn), 
+            Random(x, kk)           => self.registers[x as usize] = self.random() & kk, 
+            Draw(x, y, 0) if sc     => self.draw_wide(x, y)?, 
+            Draw(x, y, n)           => self.draw(x, y, n)?, 
+            SkipKey(x)              => self.skip_if(self.key(x)), 
+            SkipNotKey(x)           => self.skip_if(!self.key(x)), 
+            GetDelay(x)             => self.registers[x as usize] = self.delay_timer, 
+            WaitKey(x)              => self.wait_key(x), 
+            SetDelay(x)             => self.delay_timer = self.reg(x), 
+            SetSound(x)             => self.sound_timer = self.reg(x), 
+            AddIndex(x)             => self.i = self.i.wrapping_add(self.reg(x) as u16), 
+            Font(x)                 => self.i = FONT_ADDR + (self.reg(x) & 0xF) as u16 * 5, 
+            BigFont(x) if sc        => self.i = BIG_FONT_ADDR + (self.reg(x) & 0xF) as u16 * 10, 
+            Bcd(x)                  => self.bcd(x)?, 
+            Store(x)                => self.store(x)?, 
+            Load(x)                 => self.load(x)?, 
+            SaveFlags(x) if sc      => self.save_flags(x), 
+            LoadFlags(x) if sc      => self.load_flags(x), 
+            BigFont(_) | SaveFlags(_) | LoadFlags(_) => return Err(unknown), 
+        }
+        Ok(true)
+    }
+
+    // address of the instruction being executed 
+    fn here(&self) -> usize {
+        self.pc - 2 
+    }
+
+    // the `len` bytes at I, or a fault if they run past the end of memory 
+    fn at_index(&self, len: usize) -> Result<std::ops::Range<usize>, CpuError> {
+        let start = self.i as usize; 
+        if start + len > self.memory.len() {
+            // the first address that does not exist 
+            let addr = start.max(self.memory.len()); 
+            return Err(CpuError::MemoryOutOfBounds { pc: self.here(), addr }); 
+        }
+        Ok(start..start + len)
+    }
+
+    fn reg(&self, x: u8) -> u8 {
+        self.registers[x as usize]
+    }
+
+    fn key(&self, x: u8) -> bool {
+        self.keypad[(self.reg(x) & 0xF) as usize]
+    }
+
+    fn jump(&mut self, addr: u16) {
+        self.pc = addr as usize; 
+    }
+
+    // Bnnn, or Bxnn with the jumping quirk 
+    fn jump_with_offset(&mut self, nnn: u16) {
+        let x = (nnn >> 8) as u8; 
+        let offset = if self.quirks.jumping { self.reg(x) } else { self.reg(0) }; 
+        self.jump(nnn + offset as u16); 
+    }
+
+    // skip over the next instruction 
+    fn skip_if(&mut self, condition: bool) {
+        if condition {
+            self.pc += 2; 
+        }
+    }
+
+    // OR, AND and XOR also reset VF on the COSMAC VIP 
+    fn logic(&mut self, x: u8, val: u8) {
+        self.registers[x as usize] = val; 
+        if self.quirks.vf_reset {
+            self.registers[0xF] = 0; 
+        }
+    }
+
+    // add two numbers at register x and y   
+    fn add_xy(&mut self, x: u8, y:u8) {
+        let vx = self.registers[x as usize]; 
+        let vy = self.registers[y as usize]; 
+
+        let (val, overflow) = vx.overflowing_add(vy); 
+        self.registers[x as usize] = val; 
+
+        // handle overflow 
+        if overflow {
+            // last register as a carry flag 
+            self.registers[0xF] = 1; 
+        } else {
+            self.registers[0xF] = 0; 
+        }
+    }
+
+    // Vx = Va - Vb; VF is 1 when there was no borrow 
+    fn sub_xy(&mut self, x: u8, a: u8, b: u8) {
+        let (val, borrow) = self.reg(a).overflowing_sub(self.reg(b)); 
+        self.registers[x as usize] = val; 
+        self.registers[0xF] = !borrow as u8; 
+    }
+
+    // the register that is shifted into Vx 
+    fn shift_source(&self, x: u8, y: u8) -> u8 {
+        if self.quirks.shifting { x } else { y }
+    }
+
+    fn shift_right(&mut self, x: u8, y: u8) {
+        let vy = self.reg(y); 
+        self.registers[x as usize] = vy >> 1; 
+        self.registers[0xF] = vy & 1; 
+    }
+
+    fn shift_left(&mut self, x: u8, y: u8) {
+        let vy = self.reg(y); 
+        self.registers[x as usize] = vy << 1; 
+        self.registers[0xF] = vy >> 7; 
+    }
+
+    // xorshift32: small, fast and good enough for games 
+    fn random(&mut self) -> u8 {
+        let mut state = self.rng; 
+        state ^= state << 13; 
+        state ^= state >> 17; 
+        state ^= state << 5; 
+        self.rng = state; 
+        (state >> 24) as u8
+    }
+
+    fn clear(&mut self) {
+        self.display.clear(); 
+    }
+
+    // XOR the n-byte sprite at I onto the display, VF = collision 
+    fn draw(&mut self, x: u8, y: u8, n: u8) -> Result<(), CpuError> {
+        let sprite = &self.memory[self.at_index(n as usize)?]; 
+
+        let (x, y) = (self.reg(x) as usize, self.reg(y) as usize); 
+        let collision = self.display.draw(x, y, sprite, self.quirks.clipping); 
+        self.registers[0xF] = collision as u8; 
+        Ok(())
+    }
+
+    // the 16x16 sprite of 32 bytes at I 
+    fn draw_wide(&mut self, x: u8, y: u8) -> Result<(), CpuError> {
+        let sprite = &self.memory[self.at_index(32)?]; 
+
+        let (x, y) = (self.reg(x) as usize, self.reg(y) as usize); 
+        let collision = self.display.draw_wide(x, y, sprite, self.quirks.clipping); 
+        self.registers[0xF] = collision as u8; 
+        Ok(())
+    }
+
+    // there are only 8 flags; a larger x stops at V7 
+    fn save_flags(&mut self, x: u8) {
+        let count = (x as usize + 1).min(self.rpl.len()); 
+        self.rpl[..count].copy_from_slice(&self.registers[..count]); 
+    }
+
+    fn load_flags(&mut self, x: u8) {
+        let count = (x as usize + 1).min(self.rpl.len()); 
+        self.registers[..count].copy_from_slice(&self.rpl[..count]); 
+    }
+
+    // run this instruction again until a key has gone down and up; 
+    // the first key pressed is the one that counts 
+    fn wait_key(&mut self, x: u8) {
+        match self.key_wait {
+            Some(key) if !self.keypad[key as usize] => {
+                self.registers[x as usize] = key; 
+                self.key_wait = None; 
+                return; 
+            }
+            Some(_) => {}
+            None => self.key_wait = self.keypad.iter().position(|&pressed| pressed).map(|key| key as u8), 
+        }
+        self.pc -= 2; 
+    }
+
+    // hundreds, tens and ones of Vx 
+    fn bcd(&mut self, x: u8) -> Result<(), CpuError> {
+        let vx = self.reg(x); 
+        let range = self.at_index(3)?; 
+        self.memory[range].copy_from_slice(&[vx / 100, vx / 10 % 10, vx % 10]); 
+        Ok(())
+    }
+
+    fn store(&mut self, x: u8) -> Result<(), CpuError> {
+        let count = x as usize + 1; 
+        let range = self.at_index(count)?; 
+        self.memory[range].copy_from_slice(&self.registers[..count]); 
+        if self.quirks.memory {
+            self.i += count as u16; 
+        }
+        Ok(())
+    }
+
+    fn load(&mut self, x: u8) -> Result<(), CpuError> {
+        let count = x as usize + 1; 
+        let range = self.at_index(count)?; 
+        self.registers[..count].copy_from_slice(&self.memory[range]); 
+        if self.quirks.memory {
+            self.i += count as u16; 
+        }
+        Ok(())
+    }
+
+    // calling memory location  at addr 
+    fn call(&mut self, addr: u16) -> Result<(), CpuError> {
+        let sp = self.stack_pointer; 
+        let stack = &mut self.stack; 
+
+        // all 16 slots taken, sp would index past the stack 
+        if sp >= stack.len() {
+            return Err(CpuError::StackOverflow { pc: self.here() }); 
+        }
+
+        stack[sp] = self.pc as u16; // current position 
+        self.stack_pointer += 1;    // increments stack pointer 
+        self.pc = addr as usize; 
+        Ok(())
+    }
+
+    // retruning from a function 
+    fn ret(&mut self) -> Result<(), CpuError> {
+        if self.stack_pointer == 0 {
+            return Err(CpuError::StackUnderflow { pc: self.here() }); 
+        }
+
+        self.stack_pointer -= 1; // to earlier position 
+        let call_addr = self.stack[self.stack_pointer]; 
+        self.pc = call_addr as usize; 
+        Ok(())
+    }
+}
+
+impl Default for CPU {
+    fn default() -> CPU {
+        CPU::new()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*; 
+
+    // a CPU with `program` at 0x200, followed by a halt 
+    fn cpu_with(program: &[u16]) -> CPU {
+        let rom: Vec<u8> = program.iter().flat_map(|opcode| opcode.to_be_bytes()).collect(); 
+        let mut cpu = CPU::new(); 
+        cpu.load_rom(&rom).unwrap(); 
+        cpu
+    }
+
+    fn run(program: &[u16]) -> CPU {
+        let mut cpu = cpu_with(program); 
+        cpu.run().unwrap(); 
+        cpu
+    }
+
+    #[test]
+    fn halts_and_ignores_machine_routines() {
+        let cpu = run(&[0x0123, 0x6005]); 
+        assert_eq!(cpu.registers[0], 5); 
+        assert_eq!(cpu.pc, 0x204); 
+    }
+
+    #[test]
+    fn clear_screen() {
+        let mut cpu = cpu_with(&[0x00E0]); 
+        cpu.display.draw(4, 3, &[0x80], true); 
+        cpu.run().unwrap(); 
+        assert!(cpu.display == Display::new()); 
+    }
+
+    #[test]
+    fn call_and_return() {
+        // 0x200: call 0x206; 0x202: V1 = 2; halt; 0x206: V0 = 1; return 
+        let cpu = run(&[0x2206, 0x6102, 0x0000, 0x6001, 0x00EE]); 
+        assert_eq!(cpu.registers[0..2], [1, 2]); 
+        assert_eq!(cpu.stack_pointer, 0); 
+    }
+
+    #[test]
+    fn jump() {
+        // skips the V0 = 1 at 0x202 
+        let cpu = run(&[0x1204, 0x6001, 0x6102]); 
+        assert_eq!(cpu.registers[0..2], [0, 2]); 
+    }
+
+    #[test]
+    fn jump_with_offset() {
+        // 0x200: V0 = 2; 0x202: jump 0x204 + 2; 0x204: V1 = 1; 0x206: V2 = 1 
+        let cpu = run(&[0x6002, 0xB204, 0x6101, 0x6201]); 
+        assert_eq!(cpu.registers[0..3], [2, 0, 1]); 
+
+        // B2nn jumps to 0x2nn + V2 with the jumping quirk 
+        let mut cpu = cpu_with(&[0x6002, 0x6204, 0xB204, 0x6101, 0x6301]); 
+        cpu.quirks.jumping = true; 
+        cpu.run().unwrap(); 
+        assert_eq!(cpu.registers[1..4], [0, 4, 1]); 
+    }
+
+    #[test]
+    fn skip_on_byte() {
+        let cpu = run(&[0x6007, 0x3007, 0x6101, 0x4007, 0x6201]); 
+        assert_eq!(cpu.registers[1..3], [0, 1]); 
+
+        let cpu = run(&[0x6007, 0x3008, 0x6101, 0x4008, 0x6201]); 
+        assert_eq!(cpu.registers[1..3], [1, 0]); 
+    }
+
+    #[test]
+    fn skip_on_register() {
+        let cpu = run(&[0x6003, 0x6103, 0x5010, 0x6201, 0x9010, 0x6301]); 
+        assert_eq!(cpu.registers[2..4], [0, 1]); 
+
+        let cpu = run(&[0x6003, 0x6104, 0x5010, 0x6201, 0x9010, 0x6301]); 
+        assert_eq!(cpu.registers[2..4], [1, 0]); 
+    }
+
+    #[test]
+    fn load_and_add_byte() {
+        // 7xkk wraps around and leaves VF alone 
+        let cpu = run(&[0x60FF, 0x7002, 0x6142]); 
+        assert_eq!(cpu.registers[0], 1); 
+        assert_eq!(cpu.registers[1], 0x42); 
+        assert_eq!(cpu.registers[0xF], 0); 
+    }
+
+    #[test]
+    fn copy_register() {
+        let cpu = run(&[0x6109, 0x8010]); 
+        assert_eq!(cpu.registers[0], 9); 
+    }
+
+    #[test]
+    fn logic_ops_reset_vf() {
+        let cpu = run(&[0x600C, 0x610A, 0x6F05, 0x8011]); 
+        assert_eq!((cpu.registers[0], cpu.registers[0xF]), (0x0E, 0)); 
+
+        let cpu = run(&[0x600C, 0x610A, 0x6F05, 0x8012]); 
+        assert_eq!((cpu.registers[0], cpu.registers[0xF]), (0x08, 0)); 
+
+        let cpu = run(&[0x600C, 0x610A, 0x6F05, 0x8013]); 
+        assert_eq!((cpu.registers[0], cpu.registers[0xF]), (0x06, 0)); 
+
+        let mut cpu = cpu_with(&[0x600C, 0x610A, 0x6F05, 0x8011]); 
+        cpu.quirks.vf_reset = false; 
+        cpu.run().unwrap(); 
+        assert_eq!(cpu.registers[0xF], 5); 
+    }
+
+    #[test]
+    fn add_sets_carry() {
+        let cpu = run(&[0x60F0, 0x6120, 0x8014]); 
+        assert_eq!((cpu.registers[0], cpu.registers[0xF]), (0x10, 1)); 
+
+        let cpu = run(&[0x6010, 0x6120, 0x8014]); 
+        assert_eq!((cpu.registers[0], cpu.registers[0xF]), (0x30, 0)); 
+    }
+
+    #[test]
+    fn sub_sets_not_borrow() {
+        let cpu = run(&[0x6005, 0x6103, 0x8015]); 
+        assert_eq!((cpu.registers[0], cpu.registers[0xF]), (2, 1)); 
+
+        let cpu = run(&[0x6003, 0x6105, 0x8015]); 
+        assert_eq!((cpu.registers[0], cpu.registers[0xF]), (0xFE, 0)); 
+
+        // 8xy7 subtracts the other way around 
+        let cpu = run(&[0x6003, 0x6105, 0x8017]); 
+        assert_eq!((cpu.registers[0], cpu.registers[0xF]), (2, 1)); 
+    }
+
+    #[test]
+    fn shifts_use_vy_and_keep_the_lost_bit() {
+        let cpu = run(&[0x6105, 0x8016]); 
+        assert_eq!((cpu.registers[0], cpu.registers[0xF]), (2, 1)); 
+
+        let cpu = run(&[0x6181, 0x801E]); 
+        assert_eq!((cpu.registers[0], cpu.registers[0xF]), (2, 1)); 
+
+        // with the shifting quirk, Vx is shifted in place 
+        let mut cpu = cpu_with(&[0x6006, 0x6105, 0x8016]); 
+        cpu.quirks.shifting = true; 
+        cpu.run().unwrap(); 
+        assert_eq!((cpu.registers[0], cpu.registers[0xF]), (3, 0)); 
+    }
+
+    #[test]
+    fn flag_wins_over_result_in_vf() {
+        let cpu = run(&[0x6FFF, 0x6101, 0x8F14]); 
+        assert_eq!(cpu.registers[0xF], 1); 
+    }
+
+    #[test]
+    fn set_index() {
+        let cpu = run(&[0xA123]); 
+        assert_eq!(cpu.i, 0x123); 
+    }
+
+    #[test]
+    fn random_is_masked() {
+        let cpu = run(&[0xC00F, 0xC100]); 
+        assert!(cpu.registers[0] <= 0x0F); 
+        assert_eq!(cpu.registers[1], 0); 
+    }
+
+    #[test]
+    fn draw_xors_and_reports_collisions() {
+        // an 8x1 sprite of 0b1100_0000 at (2, 3), drawn twice 
+        let mut cpu = cpu_with(&[0xA300, 0x6002, 0x6103, 0xD011, 0x6200, 0x8F20, 0xD011]); 
+        cpu.memory[0x300] = 0b1100_0000; 
+        cpu.run().unwrap(); 
+
+        // the second draw erased the first one and reported it 
+        assert!(!cpu.display.get(2, 3) && !cpu.display.get(3, 3)); 
+        assert_eq!(cpu.registers[0xF], 1); 
+    }
+
+    #[test]
+    fn draw_wraps_the_start_and_clips_the_rest() {
+        // at (66, 33), which wraps to (2, 1), and at (62, 31), clipped 
+        let mut cpu = cpu_with(&[0xA300, 0x6042, 0x6121, 0xD012, 0x603E, 0x611F, 0xD012]); 
+        cpu.memory[0x300] = 0xFF; 
+        cpu.memory[0x301] = 0xFF; 
+        cpu.run().unwrap(); 
+
+        assert!(cpu.display.get(2, 1) && cpu.display.get(9, 2)); 
+        assert!(cpu.display.get(62, 31) && cpu.display.get(63, 31)); 
+        assert!(!cpu.display.get(0, 0) && !cpu.display.get(0, 31)); 
+        assert_eq!(cpu.registers[0xF], 0); 
+    }
+
+    #[test]
+    fn skip_on_key() {
+        let mut cpu = cpu_with(&[0x6005, 0xE09E, 0x6101, 0xE0A1, 0x6201]); 
+        cpu.keypad[5] = true; 
+        cpu.run().unwrap(); 
+        assert_eq!(cpu.registers[1..3], [0, 1]); 
+
+        let cpu = run(&[0x6005, 0xE09E, 0x6101, 0xE0A1, 0x6201]); 
+        assert_eq!(cpu.registers[1..3], [1, 0]); 
+    }
+
+    #[test]
+    fn wait_for_key() {
+        let mut cpu = cpu_with(&[0xF30A]); 
+        cpu.step().unwrap(); 
+        cpu.set_keypad(1 << 0xB); 
+        for _ in 0..5 {
+            cpu.step().unwrap(); 
+        }
+        // still held down, so still waiting; pressing another key changes nothing 
+        cpu.set_keypad(1 << 0xB | 1 << 2); 
+        cpu.step().unwrap(); 
+        assert_eq!((cpu.pc, cpu.registers[3]), (0x200, 0)); 
+
+        cpu.set_keypad(1 << 2); 
+        cpu.run().unwrap(); 
+        assert_eq!((cpu.pc, cpu.registers[3]), (0x202, 0xB)); 
+    }
+
+    #[test]
+    fn timers() {
+        let cpu = run(&[0x6030, 0xF015, 0xF018, 0xF107]); 
+        assert_eq!(cpu.delay_timer, 0x30); 
+        assert_eq!(cpu.sound_timer, 0x30); 
+        assert_eq!(cpu.registers[1], 0x30); 
+    }
+
+    #[test]
+    fn timers_tick_at_60hz_of_emulated_time() {
+        // at 600Hz, the timers tick every 10 instructions 
+        let mut cpu = cpu_with(&[0x6014, 0xF015, 0xF018, 0x1206]); // 1206: jump to itself 
+        for _ in 0..103 {
+            cpu.step().unwrap(); 
+        }
+        assert_eq!(cpu.delay_timer, 0x14 - 10); 
+        assert_eq!(cpu.sound_timer, 0x14 - 10); 
+
+        // twice the clock, half the ticks for the same number of instructions 
+        let mut cpu = cpu_with(&[0x6014, 0xF015, 0x1204]); 
+        cpu.clock_hz = 1200; 
+        for _ in 0..102 {
+            cpu.step().unwrap(); 
+        }
+        assert_eq!(cpu.delay_timer, 0x14 - 5); 
+    }
+
+    #[test]
+    fn timers_stop_at_zero() {
+        let mut cpu = CPU::new(); 
+        cpu.delay_timer = 1; 
+        cpu.tick_timers(); 
+        cpu.tick_timers(); 
+        assert_eq!((cpu.delay_timer, cpu.sound_timer), (0, 0)); 
+    }
+
+    #[test]
+    fn font_is_loaded_at_reset() {
+        let mut cpu = CPU::new(); 
+        assert_eq!(cpu.memory[0x050..0x055], [0xF0, 0x90, 0x90, 0x90, 0xF0]); 
+        assert_eq!(cpu.memory[0x09B..0x0A0], [0xF0, 0x80, 0xF0, 0x80, 0x80]); 
+
+        cpu.memory[0x050] = 0; 
+        cpu.registers[3] = 9; 
+        cpu.reset(); 
+        assert_eq!(cpu.memory[0x050], 0xF0); 
+        assert_eq!(cpu.registers[3], 0); 
+    }
+
+    #[test]
+    fn add_to_index() {
+        let cpu = run(&[0xA0FF, 0x6002, 0xF01E]); 
+        assert_eq!(cpu.i, 0x101); 
+    }
+
+    #[test]
+    fn font_address() {
+        let cpu = run(&[0x600A, 0xF029]); 
+        assert_eq!(cpu.i, FONT_ADDR + 50); 
+        // the sprite for A is really there 
+        assert_eq!(cpu.memory[cpu.i as usize], 0xF0); 
+        assert_eq!(cpu.memory[cpu.i as usize + 4], 0x90); 
+    }
+
+    #[test]
+    fn binary_coded_decimal() {
+        let cpu = run(&[0x60FE, 0xA300, 0xF033]); 
+        assert_eq!(cpu.memory[0x300..0x303], [2, 5, 4]); 
+    }
+
+    #[test]
+    fn store_and_load_registers() {
+        let mut cpu = cpu_with(&[0x6001, 0x6102, 0x6203, 0xA300, 0xF255, 0xA400, 0xF265]); 
+        cpu.memory[0x400..0x403].copy_from_slice(&[7, 8, 9]); 
+        cpu.run().unwrap(); 
+
+        assert_eq!(cpu.memory[0x300..0x303], [1, 2, 3]); 
+        assert_eq!(cpu.registers[0..3], [7, 8, 9]); 
+        assert_eq!(cpu.i, 0x403); 
+
+        let mut cpu = cpu_with(&[0x6001, 0xA300, 0xF055]); 
+        cpu.quirks.memory = false; 
+        cpu.run().unwrap(); 
+        assert_eq!(cpu.i, 0x300); 
+    }
+
+    #[test]
+    fn stack_faults() {
+        // 0x200 calls itself: the 17th call has nowhere to go 
+        let mut cpu = cpu_with(&[0x2200]); 
+        assert_eq!(cpu.run(), Err(CpuError::StackOverflow { pc: 0x200 })); 
+        assert_eq!(cpu.stack_pointer, 16); 
+
+        let mut cpu = cpu_with(&[0x6001, 0x00EE]); 
+        assert_eq!(cpu.run(), Err(CpuError::StackUnderflow { pc: 0x202 })); 
+        assert_eq!(cpu.pc, 0x202); 
+    }
+
+    #[test]
+    fn memory_faults() {
+        // Fx55 of all registers at 0xFF8; the fault names the first bad address 
+        let mut cpu = cpu_with(&[0xAFF8, 0xFF55]); 
+        let fault = CpuError::MemoryOutOfBounds { pc: 0x202, addr: 0x1000 }; 
+        assert_eq!(cpu.run(), Err(fault)); 
+
+        // I can go past the memory entirely 
+        let mut cpu = cpu_with(&[0xAFFF, 0x6010, 0xF01E, 0xD015]); 
+        let fault = CpuError::MemoryOutOfBounds { pc: 0x206, addr: 0x100F }; 
+        assert_eq!(cpu.run(), Err(fault)); 
+
+        // running off the end of memory 
+        let mut cpu = cpu_with(&[0x1FFF]); 
+        let fault = CpuError::MemoryOutOfBounds { pc: 0xFFF, addr: 0x1000 }; 
+        assert_eq!(cpu.run(), Err(fault)); 
+    }
+
+    #[test]
+    fn unknown_opcodes() {
+        let mut cpu = cpu_with(&[0x6001, 0x5121]); 
+        let fault = CpuError::UnknownOpcode { pc: 0x202, opcode: 0x5121 }; 
+        assert_eq!(cpu.run(), Err(fault)); 
+        assert_eq!(fault.to_string(), "unknown opcode 5121 at 202"); 
+    }
+
+    #[test]
+    fn presets() {
+        assert_eq!(Quirks::default(), Quirks::COSMAC_VIP); 
+        assert_eq!(Quirks::preset("schip"), Some(Quirks::SUPER_CHIP)); 
+        assert_eq!(Quirks::preset("CHIP-48"), Some(Quirks::CHIP_48)); 
+        assert_eq!(Quirks::preset("xo-chip"), None); 
+    }
+
+    #[test]
+    fn super_chip_display() {
+        // hi-res, a 16x16 sprite from the big font area, scroll down 2 and right 4 
+        let mut cpu = cpu_with(&[0x00FF, 0xA0A0, 0x6010, 0x6105, 0xD010, 0x00C2, 0x00FB, 0x00FD]); 
+        cpu.set_quirks(Quirks::SUPER_CHIP); 
+        cpu.run().unwrap(); 
+
+        assert!(cpu.display.is_hires()); 
+        // the big 0 starts with 0xFF, 0xFF: the first two rows, 16 wide 
+        assert!(cpu.display.get(20, 7) && cpu.display.get(35, 7)); 
+        assert!(!cpu.display.get(20, 5)); 
+        assert_eq!(cpu.pc, 0x20E); 
+
+        // without SUPER-CHIP, 00FF is a machine routine and Dxy0 draws nothing 
+        let cpu = run(&[0x00FF, 0xA0A0, 0xD010]); 
+        assert!(!cpu.display.is_hires()); 
+        assert!(cpu.display == Display::new()); 
+    }
+
+    #[test]
+    fn super_chip_fonts_and_flags() {
+        let mut cpu = cpu_with(&[0x6A0B, 0xFA30, 0x6107, 0x6209, 0xF275, 0x6100, 0x6200, 0xF285]); 
+        cpu.set_quirks(Quirks::SUPER_CHIP); 
+        cpu.run().unwrap(); 
+
+        assert_eq!(cpu.i, BIG_FONT_ADDR + 110); 
+        assert_eq!(cpu.memory[cpu.i as usize], 0xFC); 
+        assert_eq!(cpu.registers[1..3], [7, 9]); 
+    }
+
+    #[test]
+    fn roms_are_loaded_at_0x200() {
+        let mut cpu = CPU::new(); 
+        cpu.registers[4] = 4; 
+        cpu.load_rom(&[0x60, 0x01]).unwrap(); 
+        assert_eq!(cpu.pc, PROGRAM_START); 
+        assert_eq!(cpu.memory[0x200..0x202], [0x60, 0x01]); 
+        // loading resets the rest of the machine 
+        assert_eq!(cpu.registers[4], 0); 
+
+        assert_eq!(cpu.load_rom(&[]), Err(RomError::Empty)); 
+        assert!(cpu.load_rom(&[0xAA; MAX_ROM_SIZE]).is_ok()); 
+        assert_eq!(cpu.load_rom(&[0xAA; MAX_ROM_SIZE + 1]), Err(RomError::TooLarge(3585))); 
+    }
+}
@@ -0,0 +1,444 @@
+/*
+    an interactive debugger for ROMs 
+        Debugger wraps a CPU with breakpoints (stop before the instruction 
+        at an address runs) and watchpoints (stop after an instruction 
+        changed the byte at an address); the program only runs when told 
+        to, one instruction or until something stops it 
+
+    repl reads commands line by line; addresses and values are in hex, 
+        with or without 0x: 
+            s, step [n]         run one (or n) instructions 
+            c, continue         run until a breakpoint, a watchpoint, the 
+                                halt or a fault (at most 1000000 steps) 
+            b, break <addr>     set a breakpoint; without addr, list them 
+            w, watch <addr>     set a watchpoint; without addr, list them 
+            d, delete <addr>    remove the breakpoint and watchpoint there 
+            r, regs             registers, I, PC, timers 
+            set <reg> <value>   change V0-VF, I or PC 
+            m, mem <addr> [n]   hex dump of n bytes (64) 
+            poke <addr> <byte>  change a byte of memory 
+            l, list [addr] [n]  disassemble n instructions (8) from addr (PC) 
+            bt, stack           the return addresses on the call stack 
+            screen              the display, in half blocks 
+            save <file>         write a save state of the machine 
+            load <file>         go back to a save state (state.rs) 
+            q, quit             leave the debugger 
+        an empty line repeats the last command 
+*/
+
+use std::{
+    collections::BTreeSet, 
+    fmt, 
+    fs, 
+    io::{self, BufRead, Write}, 
+}; 
+
+use crate::{
+    cpu::{CpuError, Executed, CPU}, 
+    disasm, 
+    state::State, 
+}; 
+
+// how long continue runs before giving control back 
+const CONTINUE_LIMIT: usize = 1_000_000; 
+
+pub struct Debugger {
+    cpu: CPU, 
+    breakpoints: BTreeSet<usize>, 
+    watchpoints: BTreeSet<usize>, 
+}
+
+/// Why the program stopped running. 
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Stop {
+    /// The requested number of instructions ran. 
+    Stepped(Executed), 
+    /// The next instruction is at a breakpoint. 
+    Breakpoint(usize), 
+    /// The byte at `addr` changed from `old` to `new`. 
+    Watchpoint { addr: usize, old: u8, new: u8 }, 
+    Halted, 
+    /// `continue` ran out of steps, the program may be waiting in a loop. 
+    Limit, 
+}
+
+impl Debugger {
+    pub fn new(cpu: CPU) -> Debugger {
+        Debugger {
+            cpu, 
+            breakpoints: BTreeSet::new(), 
+            watchpoints: BTreeSet::new(), 
+        }
+    }
+
+    pub fn cpu(&self) -> &CPU {
+        &self.cpu
+    }
+
+    pub fn cpu_mut(&mut self) -> &mut CPU {
+        &mut self.cpu
+    }
+
+    pub fn add_breakpoint(&mut self, addr: usize) {
+        self.breakpoints.insert(addr); 
+    }
+
+    pub fn add_watchpoint(&mut self, addr: usize) {
+        self.watchpoints.insert(addr); 
+    }
+
+    /// Remove the breakpoint and the watchpoint at `addr`; false if there were none. 
+    pub fn remove(&mut self, addr: usize) -> bool {
+        let breakpoint = self.breakpoints.remove(&addr); 
+        let watchpoint = self.watchpoints.remove(&addr); 
+        breakpoint || watchpoint
+    }
+
+    /// Run `count` instructions, stopping early at a watchpoint, the halt 
+    /// or a breakpoint (but not at the one the PC is on now). 
+    pub fn step(&mut self, count: usize) -> Result<Stop, CpuError> {
+        let mut stop = Stop::Limit; 
+        for n in 0..count {
+            if n > 0 && self.breakpoints.contains(&self.cpu.pc()) {
+                return Ok(Stop::Breakpoint(self.cpu.pc())); 
+            }
+            let before: Vec<(usize, u8)> = self
+                .watchpoints
+                .iter()
+                .filter_map(|&addr| self.cpu.memory().get(addr).map(|&b| (addr, b)))
+                .collect(); 
+
+            let executed = self.cpu.step()?; 
+            if executed.halted {
+                return Ok(Stop::Halted); 
+            }
+            for (addr, old) in before {
+                let new = self.cpu.memory()[addr]; 
+                if new != old {
+                    return Ok(Stop::Watchpoint { addr, old, new }); 
+                }
+            }
+            stop = Stop::Stepped(executed); 
+        }
+        Ok(stop)
+    }
+
+    /// Run until something stops the program. 
+    pub fn resume(&mut self) -> Result<Stop, CpuError> {
+        match self.step(CONTINUE_LIMIT)? {
+            Stop::Stepped(_) => Ok(Stop::Limit), 
+            stop => Ok(stop), 
+        }
+    }
+
+    /// Read commands from `input` until it ends or says quit. 
+    pub fn repl(&mut self, input: impl BufRead, mut output: impl Write) -> io::Result<()> {
+        let mut last = String::new(); 
+        writeln!(output, "at {:03X}, type help for the commands", self.cpu.pc())?; 
+        write!(output, "> ")?; 
+        output.flush()?; 
+
+        for line in input.lines() {
+            let line = line?; 
+            let line = if line.trim().is_empty() { last.clone() } else { line }; 
+            if !self.command(&line, &mut output)? {
+                return Ok(()); 
+            }
+            last = line; 
+            write!(output, "> ")?; 
+            output.flush()?; 
+        }
+        writeln!(output)
+    }
+
+    // run one command; false to quit 
+    fn command(&mut self, line: &str, out: &mut impl Write) -> io::Result<bool> {
+        let words: Vec<&str> = line.split_whitespace().collect(); 
+        let Some((&name, args)) = words.split_first() else {
+            return Ok(true); 
+        }; 
+
+        match self.dispatch(name, args, out) {
+            Ok(quit) => Ok(!quit), 
+            Err(CommandError::Io(e)) => Err(e), 
+            Err(CommandError::Usage(message)) => {
+                writeln!(out, "{message}")?; 
+                Ok(true)
+            }
+        }
+    }
+
+    // true when the debugger should quit 
+    fn dispatch(&mut self, name: &str, args: &[&str], out: &mut impl Write) -> Result<bool, CommandError> {
+        match (name, args) {
+            ("s" | "step", []) => self.report(|d| d.step(1), out)?, 
+            ("s" | "step", [n]) => {
+                let n = n.parse().map_err(|_| usage(format!("not a count: {n}")))?; 
+                self.report(|d| d.step(n), out)?
+            }
+            ("c" | "continue", []) => self.report(Debugger::resume, out)?, 
+            ("b" | "break", []) => list(out, "breakpoints", &self.breakpoints)?, 
+            ("b" | "break", [addr]) => self.add_breakpoint(parse_hex(addr)?), 
+            ("w" | "watch", []) => list(out, "watchpoints", &self.watchpoints)?, 
+            ("w" | "watch", [addr]) => self.add_watchpoint(parse_hex(addr)?), 
+            ("d" | "delete", [addr]) => {
+                if !self.remove(parse_hex(addr)?) {
+                    writeln!(out, "nothing at {addr}")?; 
+                }
+            }
+            ("r" | "regs", []) => self.registers(out)?, 
+            ("set", [register, value]) => self.set(register, parse_hex(value)?)?, 
+            ("m" | "mem", [addr]) => self.dump(out, parse_hex(addr)?, 64)?, 
+            ("m" | "mem", [addr, len]) => self.dump(out, parse_hex(addr)?, parse_hex(len)?)?, 
+            ("poke", [addr, value]) => {
+                let addr = parse_hex(addr)?; 
+                let value = u8::try_from(parse_hex(value)?).map_err(|_| usage("a byte is 00-FF"))?; 
+                let byte = self
+                    .cpu
+                    .memory_mut()
+                    .get_mut(addr)
+                    .ok_or_else(|| usage(format!("no memory at {addr:03X}")))?; 
+                *byte = value; 
+            }
+            ("l" | "list", []) => self.list(out, self.cpu.pc(), 8)?, 
+            ("l" | "list", [addr]) => self.list(out, parse_hex(addr)?, 8)?, 
+            ("l" | "list", [addr, n]) => self.list(out, parse_hex(addr)?, parse_hex(n)?)?, 
+            ("bt" | "stack", []) => self.backtrace(out)?, 
+            ("screen", []) => write!(out, "{}", self.cpu.display().to_half_blocks())?, 
+            ("save", [path]) => {
+                let bytes = self.cpu.save_state().to_bytes(); 
+                fs::write(path, bytes).map_err(|e| usage(format!("cannot write {path}: {e}")))?; 
+            }
+            ("load", [path]) => {
+                let bytes = fs::read(path).map_err(|e| usage(format!("cannot read {path}: {e}")))?; 
+                let state = State::from_bytes(&bytes).map_err(|e| usage(format!("{path}: {e}")))?; 
+                self.cpu.load_state(&state); 
+                writeln!(out, "at {:03X}", self.cpu.pc())?; 
+            }
+            ("h" | "help", []) => writeln!(out, "{HELP}")?, 
+            ("q" | "quit", []) => return Ok(true), 
+            _ => {
+                let command = [&[name][..], args].concat().join(" "); 
+                return Err(usage(format!("unknown command `{command}`, type help"))); 
+            }
+        }
+        Ok(false)
+    }
+
+    fn report(
+        &mut self, 
+        run: impl FnOnce(&mut Debugger) -> Result<Stop, CpuError>, 
+        out: &mut impl Write, 
+    ) -> io::Result<()> {
+        match run(self) {
+            Ok(Stop::Stepped(executed)) => writeln!(
+                out, 
+                "{:03X}: {:04X}  {}, now at {:03X}", 
+                executed.pc, 
+                executed.opcode, 
+                disasm::disassemble(executed.opcode), 
+                self.cpu.pc()
+            ), 
+            Ok(Stop::Breakpoint(pc)) => writeln!(out, "breakpoint at {pc:03X}"), 
+            Ok(Stop::Watchpoint { addr, old, new }) => writeln!(
+                out, 
+                "watchpoint at {addr:03X}: {old:02X} -> {new:02X}, now at {:03X}", 
+                self.cpu.pc()
+            ), 
+            Ok(Stop::Halted) => writeln!(out, "halted at {:03X}", self.cpu.pc()), 
+            Ok(Stop::Limit) => writeln!(out, "still running after {CONTINUE_LIMIT} steps, at {:03X}", self.cpu.pc()), 
+            Err(e) => writeln!(out, "fault: {e}"), 
+        }
+    }
+
+    fn registers(&self, out: &mut impl Write) -> io::Result<()> {
+        let cpu = &self.cpu; 
+        for (row, registers) in cpu.registers().chunks(8).enumerate() {
+            let line: Vec<String> = registers
+                .iter()
+                .enumerate()
+                .map(|(n, v)| format!("V{:X}={v:02X}", row * 8 + n))
+                .collect(); 
+            writeln!(out, "{}", line.join(" "))?; 
+        }
+        writeln!(
+            out, 
+            "I={:03X} PC={:03X} DT={:02X} ST={:02X}", 
+            cpu.index(), 
+            cpu.pc(), 
+            cpu.delay_timer(), 
+            cpu.sound_timer()
+        )
+    }
+
+    fn set(&mut self, register: &str, value: usize) -> Result<(), CommandError> {
+        let register = register.to_ascii_uppercase(); 
+        match register.as_str() {
+            "I" => self.cpu.set_index(u16::try_from(value).map_err(|_| usage("I is 16 bits"))?), 
+            "PC" => self.cpu.set_pc(value), 
+            _ => {
+                let x = register
+                    .strip_prefix('V')
+                    .and_then(|x| usize::from_str_radix(x, 16).ok())
+                    .filter(|&x| x < 16 && register.len() == 2)
+                    .ok_or_else(|| usage(format!("no register {register}")))?; 
+                let value = u8::try_from(value).map_err(|_| usage("a register is 00-FF"))?; 
+                self.cpu.registers_mut()[x] = value; 
+            }
+        }
+        Ok(())
+    }
+
+    fn dump(&self, out: &mut impl Write, addr: usize, len: usize) -> Result<(), CommandError> {
+        let memory = self.cpu.memory(); 
+        let end = (addr + len).min(memory.len()); 
+        if addr >= end {
+            return Err(usage(format!("no memory at {addr:03X}"))); 
+        }
+
+        for start in (addr..end).step_by(16) {
+            let bytes = &memory[start..(start + 16).min(end)]; 
+            let hex: Vec<String> = bytes.iter().map(|b| format!("{b:02X}")).collect(); 
+            writeln!(out, "{start:03X}: {}", hex.join(" "))?; 
+        }
+        Ok(())
+    }
+
+    fn list(&self, out: &mut impl Write, addr: usize, count: usize) -> io::Result<()> {
+        let memory = self.cpu.memory(); 
+        for pc in (addr..memory.len() - 1).step_by(2).take(count) {
+            let opcode = u16::from_be_bytes([memory[pc], memory[pc + 1]]); 
+            let marker = if pc == self.cpu.pc() { '>' } else { ' ' }; 
+            writeln!(out, "{marker} {pc:03X}: {opcode:04X}  {}", disasm::disassemble(opcode))?; 
+        }
+        Ok(())
+    }
+
+    fn backtrace(&self, out: &mut impl Write) -> io::Result<()> {
+        writeln!(out, "at {:03X}", self.cpu.pc())?; 
+        // each return address follows the call that pushed it 
+        for &addr in self.cpu.stack().iter().rev() {
+            writeln!(out, "called from {:03X}", addr.wrapping_sub(2))?; 
+        }
+        Ok(())
+    }
+}
+
+const HELP: &str = "\
+s, step [n]          run one (or n) instructions
+c, continue          run until something stops the program
+b, break [addr]      set (or list) breakpoints
+w, watch [addr]      set (or list) watchpoints on a byte
+d, delete <addr>     remove the breakpoint and watchpoint there
+r, regs              show the registers
+set <reg> <value>    change V0-VF, I or PC
+m, mem <addr> [n]    dump n bytes of memory (64)
+poke <addr> <byte>   change a byte of memory
+l, list [addr] [n]   disassemble n instructions from addr (PC)
+bt, stack            show the call stack
+screen               show the display
+save <file>          save the state of the machine
+load <file>          load a saved state
+q, quit              leave the debugger
+numbers are hex; an empty line repeats the last command"; 
+
+// a command that cannot be carried out, or the output is gone 
+enum CommandError {
+    Usage(String), 
+    Io(io::Error), 
+}
+
+impl From<io::Error> for CommandError {
+    fn from(e: io::Error) -> CommandError {
+        CommandError::Io(e)
+    }
+}
+
+fn usage(message: impl fmt::Display) -> CommandError {
+    CommandError::Usage(message.to_string())
+}
+
+fn parse_hex(s: &str) -> Result<usize, CommandError> {
+    let digits = s.strip_prefix("0x").or_else(|| s.strip_prefix("0X")).unwrap_or(s); 
+    usize::from_str_radix(digits, 16).map_err(|_| usage(format!("not a hex number: {s}")))
+}
+
+fn list(out: &mut impl Write, what: &str, addrs: &BTreeSet<usize>) -> io::Result<()> {
+    if addrs.is_empty() {
+        return writeln!(out, "no {what}"); 
+    }
+    let addrs: Vec<String> = addrs.iter().map(|a| format!("{a:03X}")).collect(); 
+    writeln!(out, "{what}: {}", addrs.join(" "))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*; 
+
+    // a Debugger on a CPU running `program` 
+    fn debugger(program: &[u16]) -> Debugger {
+        let rom: Vec<u8> = program.iter().flat_map(|opcode| opcode.to_be_bytes()).collect(); 
+        let mut cpu = CPU::new(); 
+        cpu.load_rom(&rom).unwrap(); 
+        Debugger::new(cpu)
+    }
+
+    #[test]
+    fn breakpoints_stop_before_the_instruction() {
+        let mut debugger = debugger(&[0x6001, 0x6102, 0x6203]); 
+        debugger.add_breakpoint(0x204); 
+
+        assert_eq!(debugger.resume(), Ok(Stop::Breakpoint(0x204))); 
+        assert_eq!(debugger.cpu().registers()[1..3], [2, 0]); 
+        // continuing leaves the breakpoint behind 
+        assert_eq!(debugger.resume(), Ok(Stop::Halted)); 
+        assert_eq!(debugger.cpu().registers()[2], 3); 
+    }
+
+    #[test]
+    fn watchpoints_stop_after_a_change() {
+        // store V0 at 0x300, twice; the second time changes nothing 
+        let mut debugger = debugger(&[0x6007, 0xA300, 0xF055, 0xA300, 0xF055, 0x6101]); 
+        debugger.add_watchpoint(0x300); 
+
+        let stop = debugger.resume(); 
+        assert_eq!(stop, Ok(Stop::Watchpoint { addr: 0x300, old: 0, new: 7 })); 
+        assert_eq!(debugger.cpu().pc(), 0x206); 
+        assert_eq!(debugger.resume(), Ok(Stop::Halted)); 
+    }
+
+    #[test]
+    fn repl_session() {
+        let mut debugger = debugger(&[0x6001, 0x2206, 0x0000, 0x6142, 0x00EE]); 
+        let input = "b 206\nc\nbt\nset v3 ff\n\nr\nmem 200 4\nl 206 2\nstep 2\nc\nbogus\nq\n"; 
+        let mut output = Vec::new(); 
+        debugger.repl(input.as_bytes(), &mut output).unwrap(); 
+        let output = String::from_utf8(output).unwrap(); 
+
+        assert!(output.contains("breakpoint at 206"), "{output}"); 
+        assert!(output.contains("at 206\ncalled from 202\n"), "{output}"); 
+        assert!(output.contains("V0=01 V1=00 V2=00 V3=FF"), "{output}"); 
+        assert!(output.contains("I=000 PC=206"), "{output}"); 
+        assert!(output.contains("200: 60 01 22 06"), "{output}"); 
+        assert!(output.contains("> 206: 6142  LD V1, 0x42\n  208: 00EE  RET\n"), "{output}"); 
+        assert!(output.contains("208: 00EE  RET, now at 204"), "{output}"); 
+        assert!(output.contains("halted at 204"), "{output}"); 
+        assert!(output.contains("unknown command `bogus`"), "{output}"); 
+    }
+
+    #[test]
+    fn save_and_load() {
+        let path = std::env::temp_dir().join(format!("debugger-{}.state", std::process::id())); 
+        let path = path.to_str().unwrap(); 
+        let mut debugger = debugger(&[0x6001, 0x6102, 0x6203]); 
+        let input = format!("s\nsave {path}\nc\nload {path}\nr\nload /nonexistent/state\nq\n"); 
+        let mut output = Vec::new(); 
+        debugger.repl(input.as_bytes(), &mut output).unwrap(); 
+        let output = String::from_utf8(output).unwrap(); 
+        fs::remove_file(path).unwrap(); 
+
+        assert!(output.contains("halted at 206\n> at 202\n"), "{output}"); 
+        assert!(output.contains("V0=01 V1=00 V2=00"), "{output}"); 
+        assert!(output.contains("cannot read /nonexistent/state"), "{output}"); 
+    }
+}
@@ -0,0 +1,278 @@
+/*
+    decoding opcodes into instructions 
+        decode turns the four nibbles 0xCXYD into an Instruction once, so 
+        that the CPU and the disassembler agree on what an opcode means; 
+        encode goes the other way 
+
+        x and y are register numbers, kk a byte, nnn an address and n a 
+        nibble, as in the usual notation; the SUPER-CHIP instructions are 
+        decoded as well, whether the CPU runs them is up to its quirks 
+        (Dxy0 is Draw with n = 0, which the SUPER-CHIP draws 16x16) 
+
+        opcodes that mean nothing (5xy1, Fx99 and the like) decode to None 
+*/
+
+/// A CHIP-8 (or SUPER-CHIP) instruction. 
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Instruction {
+    /// 0000, this emulator's own 
+    Halt, 
+    /// 00E0 
+    Clear, 
+    /// 00EE 
+    Return, 
+    /// 00Cn 
+    ScrollDown(u8), 
+    /// 00FB 
+    ScrollRight, 
+    /// 00FC 
+    ScrollLeft, 
+    /// 00FD 
+    Exit, 
+    /// 00FE 
+    LoRes, 
+    /// 00FF 
+    HiRes, 
+    /// 0nnn, a machine code routine 
+    Sys(u16), 
+    /// 1nnn 
+    Jump(u16), 
+    /// 2nnn 
+    Call(u16), 
+    /// 3xkk 
+    SkipEqualByte(u8, u8), 
+    /// 4xkk 
+    SkipNotEqualByte(u8, u8), 
+    /// 5xy0 
+    SkipEqual(u8, u8), 
+    /// 6xkk 
+    LoadByte(u8, u8), 
+    /// 7xkk 
+    AddByte(u8, u8), 
+    /// 8xy0 
+    Move(u8, u8), 
+    /// 8xy1 
+    Or(u8, u8), 
+    /// 8xy2 
+    And(u8, u8), 
+    /// 8xy3 
+    Xor(u8, u8), 
+    /// 8xy4 
+    Add(u8, u8), 
+    /// 8xy5 
+    Sub(u8, u8), 
+    /// 8xy6 
+    ShiftRight(u8, u8), 
+    /// 8xy7 
+    SubN(u8, u8), 
+    /// 8xyE 
+    ShiftLeft(u8, u8), 
+    /// 9xy0 
+    SkipNotEqual(u8, u8), 
+    /// Annn 
+    LoadIndex(u16), 
+    /// Bnnn 
+    JumpOffset(u16), 
+    /// Cxkk 
+    Random(u8, u8), 
+    /// Dxyn 
+    Draw(u8, u8, u8), 
+    /// Ex9E 
+    SkipKey(u8), 
+    /// ExA1 
+    SkipNotKey(u8), 
+    /// Fx07 
+    GetDelay(u8), 
+    /// Fx0A 
+    WaitKey(u8), 
+    /// Fx15 
+    SetDelay(u8), 
+    /// Fx18 
+    SetSound(u8), 
+    /// Fx1E 
+    AddIndex(u8), 
+    /// Fx29 
+    Font(u8), 
+    /// Fx30 
+    BigFont(u8), 
+    /// Fx33 
+    Bcd(u8), 
+    /// Fx55 
+    Store(u8), 
+    /// Fx65 
+    Load(u8), 
+    /// Fx75 
+    SaveFlags(u8), 
+    /// Fx85 
+    LoadFlags(u8), 
+}
+
+/// The instruction `opcode` stands for, if any. 
+pub fn decode(opcode: u16) -> Option<Instruction> {
+    use Instruction::*; 
+
+    let c = (opcode >> 12) as u8; 
+    let x = (opcode >> 8 & 0xF) as u8; 
+    let y = (opcode >> 4 & 0xF) as u8; 
+    let n = (opcode & 0xF) as u8; 
+    let nnn = opcode & 0x0FFF; 
+    let kk = (opcode & 0x00FF) as u8; 
+
+    Some(match (c, x, y, n) {
+        (0, 0, 0, 0) => Halt, 
+        (0, 0, 0xE, 0) => Clear, 
+        (0, 0, 0xE, 0xE) => Return, 
+        (0, 0, 0xC, _) => ScrollDown(n), 
+        (0, 0, 0xF, 0xB) => ScrollRight, 
+        (0, 0, 0xF, 0xC) => ScrollLeft, 
+        (0, 0, 0xF, 0xD) => Exit, 
+        (0, 0, 0xF, 0xE) => LoRes, 
+        (0, 0, 0xF, 0xF) => HiRes, 
+        (0, _, _, _) => Sys(nnn), 
+        (0x1, _, _, _) => Jump(nnn), 
+        (0x2, _, _, _) => Call(nnn), 
+        (0x3, _, _, _) => SkipEqualByte(x, kk), 
+        (0x4, _, _, _) => SkipNotEqualByte(x, kk), 
+        (0x5, _, _, 0) => SkipEqual(x, y), 
+        (0x6, _, _, _) => LoadByte(x, kk), 
+        (0x7, _, _, _) => AddByte(x, kk), 
+        (0x8, _, _, 0x0) => Move(x, y), 
+        (0x8, _, _, 0x1) => Or(x, y), 
+        (0x8, _, _, 0x2) => And(x, y), 
+        (0x8, _, _, 0x3) => Xor(x, y), 
+        (0x8, _, _, 0x4) => Add(x, y), 
+        (0x8, _, _, 0x5) => Sub(x, y), 
+        (0x8, _, _, 0x6) => ShiftRight(x, y), 
+        (0x8, _, _, 0x7) => SubN(x, y), 
+        (0x8, _, _, 0xE) => ShiftLeft(x, y), 
+        (0x9, _, _, 0) => SkipNotEqual(x, y), 
+        (0xA, _, _, _) => LoadIndex(nnn), 
+        (0xB, _, _, _) => JumpOffset(nnn), 
+        (0xC, _, _, _) => Random(x, kk), 
+        (0xD, _, _, _) => Draw(x, y, n), 
+        (0xE, _, 0x9, 0xE) => SkipKey(x), 
+        (0xE, _, 0xA, 0x1) => SkipNotKey(x), 
+        (0xF, _, 0x0, 0x7) => GetDelay(x), 
+        (0xF, _, 0x0, 0xA) => WaitKey(x), 
+        (0xF, _, 0x1, 0x5) => SetDelay(x), 
+        (0xF, _, 0x1, 0x8) => SetSound(x), 
+        (0xF, _, 0x1, 0xE) => AddIndex(x), 
+        (0xF, _, 0x2, 0x9) => Font(x), 
+        (0xF, _, 0x3, 0x0) => BigFont(x), 
+        (0xF, _, 0x3, 0x3) => Bcd(x), 
+        (0xF, _, 0x5, 0x5) => Store(x), 
+        (0xF, _, 0x6, 0x5) => Load(x), 
+        (0xF, _, 0x7, 0x5) => SaveFlags(x), 
+        (0xF, _, 0x8, 0x5) => LoadFlags(x), 
+        _ => return None, 
+    })
+}
+
+impl Instruction {
+    /// The opcode for the instruction; the inverse of `decode`. 
+    pub fn encode(self) -> u16 {
+        use Instruction::*; 
+
+        let xkk = |base: u16, x: u8, kk: u8| base | (x as u16) << 8 | kk as u16; 
+        let xy = |base: u16, x: u8, y: u8| base | (x as u16) << 8 | (y as u16) << 4; 
+        let x = |base: u16, x: u8| base | (x as u16) << 8; 
+
+        match self {
+            Halt => 0x0000, 
+            Clear => 0x00E0, 
+            Return => 0x00EE, 
+            ScrollDown(n) => 0x00C0 | n as u16, 
+            ScrollRight => 0x00FB, 
+            ScrollLeft => 0x00FC, 
+            Exit => 0x00FD, 
+            LoRes => 0x00FE, 
+            HiRes => 0x00FF, 
+            Sys(nnn) => nnn, 
+            Jump(nnn) => 0x1000 | nnn, 
+            Call(nnn) => 0x2000 | nnn, 
+            SkipEqualByte(vx, kk) => xkk(0x3000, vx, kk), 
+            SkipNotEqualByte(vx, kk) => xkk(0x4000, vx, kk), 
+            SkipEqual(vx, vy) => xy(0x5000, vx, vy), 
+            LoadByte(vx, kk) => xkk(0x6000, vx, kk), 
+            AddByte(vx, kk) => xkk(0x7000, vx, kk), 
+            Move(vx, vy) => xy(0x8000, vx, vy), 
+            Or(vx, vy) => xy(0x8001, vx, vy), 
+            And(vx, vy) => xy(0x8002, vx, vy), 
+            Xor(vx, vy) => xy(0x8003, vx, vy), 
+            Add(vx, vy) => xy(0x8004, vx, vy), 
+            Sub(vx, vy) => xy(0x8005, vx, vy), 
+            ShiftRight(vx, vy) => xy(0x8006, vx, vy), 
+            SubN(vx, vy) => xy(0x8007, vx, vy), 
+            ShiftLeft(vx, vy) => xy(0x800E, vx, vy), 
+            SkipNotEqual(vx, vy) => xy(0x9000, vx, vy), 
+            LoadIndex(nnn) => 0xA000 | nnn, 
+            JumpOffset(nnn) => 0xB000 | nnn, 
+            Random(vx, kk) => xkk(0xC000, vx, kk), 
+            Draw(vx, vy, n) => xy(0xD000, vx, vy) | n as u16, 
+            SkipKey(vx) => x(0xE09E, vx), 
+            SkipNotKey(vx) => x(0xE0A1, vx), 
+            GetDelay(vx) => x(0xF007, vx), 
+            WaitKey(vx) => x(0xF00A, vx), 
+            SetDelay(vx) => x(0xF015, vx), 
+            SetSound(vx) => x(0xF018, vx), 
+            AddIndex(vx) => x(0xF01E, vx), 
+            Font(vx) => x(0xF029, vx), 
+            BigFont(vx) => x(0xF030, vx), 
+            Bcd(vx) => x(0xF033, vx), 
+            Store(vx) => x(0xF055, vx), 
+            Load(vx) => x(0xF065, vx), 
+            SaveFlags(vx) => x(0xF075, vx), 
+            LoadFlags(vx) => x(0xF085, vx), 
+        }
+    }
+
+    /// Whether only the SUPER-CHIP has it. 
+    pub fn is_super_chip(self) -> bool {
+        use Instruction::*; 
+
+        matches!(
+            self, 
+            ScrollDown(_)
+                | ScrollRight
+                | ScrollLeft
+                | Exit
+                | LoRes
+                | HiRes
+                | Draw(_, _, 0)
+                | BigFont(_)
+                | SaveFlags(_)
+                | LoadFlags(_)
+        )
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*; 
+
+    #[test]
+    fn every_opcode_encodes_back() {
+        let mut known = 0; 
+        for opcode in 0..=u16::MAX {
+            if let Some(instruction) = decode(opcode) {
+                assert_eq!(instruction.encode(), opcode, "{instruction:?}"); 
+                known += 1; 
+            }
+        }
+        // all of 0nnn, 1nnn, ... with 5xy0, 9xy0, the nine 8xy_, two Ex__ and twelve Fx__ 
+        assert_eq!(known, 11 * 4096 + 2 * 256 + 9 * 256 + 2 * 16 + 12 * 16); 
+    }
+
+    #[test]
+    fn decoding() {
+        assert_eq!(decode(0x00E0), Some(Instruction::Clear)); 
+        assert_eq!(decode(0x00C3), Some(Instruction::ScrollDown(3))); 
+        assert_eq!(decode(0x0123), Some(Instruction::Sys(0x123))); 
+        assert_eq!(decode(0x8AB6), Some(Instruction::ShiftRight(0xA, 0xB))); 
+        assert_eq!(decode(0xD120), Some(Instruction::Draw(1, 2, 0))); 
+        assert_eq!(decode(0xF765), Some(Instruction::Load(7))); 
+        assert_eq!(decode(0x5121), None); 
+        assert_eq!(decode(0xF199), None); 
+        assert!(Instruction::Draw(1, 2, 0).is_super_chip() && !Instruction::Draw(1, 2, 5).is_super_chip()); 
+    }
+}
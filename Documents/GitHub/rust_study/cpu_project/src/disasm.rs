@@ -0,0 +1,185 @@
+/*
+    turning opcodes back into mnemonics 
+        the usual CHIP-8 assembly syntax: registers are V0-VF, numbers are 
+        hex with 0x, and the destination comes first 
+            00E0 CLS            8xy4 ADD Vx, Vy         Fx07 LD Vx, DT 
+            00EE RET            8xy5 SUB Vx, Vy         Fx0A LD Vx, K 
+            1nnn JP nnn         8xy6 SHR Vx, Vy         Fx15 LD DT, Vx 
+            2nnn CALL nnn       8xy7 SUBN Vx, Vy        Fx18 LD ST, Vx 
+            3xkk SE Vx, kk      8xyE SHL Vx, Vy         Fx1E ADD I, Vx 
+            4xkk SNE Vx, kk     9xy0 SNE Vx, Vy         Fx29 LD F, Vx 
+            5xy0 SE Vx, Vy      Annn LD I, nnn          Fx33 LD B, Vx 
+            6xkk LD Vx, kk      Bnnn JP V0, nnn         Fx55 LD [I], Vx 
+            7xkk ADD Vx, kk     Cxkk RND Vx, kk         Fx65 LD Vx, [I] 
+            8xy0 LD Vx, Vy      Dxyn DRW Vx, Vy, n 
+            8xy1 OR, 8xy2 AND, 8xy3 XOR Vx, Vy          Ex9E SKP Vx 
+            0000 HALT, 0nnn SYS nnn                     ExA1 SKNP Vx 
+        and the SUPER-CHIP instructions: 
+            00Cn SCD n          00FE LOW                Fx30 LD HF, Vx 
+            00FB SCR            00FF HIGH               Fx75 LD R, Vx 
+            00FC SCL            00FD EXIT               Fx85 LD Vx, R 
+            (Dxy0 is DRW Vx, Vy, 0) 
+        anything else is a data word, DW 0x5121; the opcodes are decoded 
+        by decode.rs, just like the CPU does 
+
+    listing disassembles a whole ROM, one word per line with its address 
+        and opcode; the targets of calls and jumps inside the ROM get 
+        labels (sub_XXX, label_XXX), and so does sprite data loaded into I 
+        (data_XXX), which then stand in for the bare addresses: 
+            0x200: 2206  CALL sub_206 
+            0x202: 1202  JP label_202 
+            ... 
+        the ROM is read as one instruction after another; data between the 
+        instructions is shown as whatever it happens to decode to 
+*/
+
+use std::collections::BTreeMap; 
+
+use crate::decode::{decode, Instruction}; 
+
+/// The mnemonic for `opcode`. 
+pub fn disassemble(opcode: u16) -> String {
+    format(opcode, |addr| format!("0x{addr:03X}"))
+}
+
+/// `rom` as loaded at `origin`: one line per word, with labels. 
+pub fn listing(rom: &[u8], origin: usize) -> String {
+    let words: Vec<(usize, u16)> = rom
+        .chunks_exact(2)
+        .enumerate()
+        .map(|(n, pair)| (origin + n * 2, u16::from_be_bytes([pair[0], pair[1]])))
+        .collect(); 
+
+    // only addresses where a line starts can carry a label 
+    let mut labels = BTreeMap::new(); 
+    for &(_, opcode) in &words {
+        if let Some((addr, kind)) = target(opcode) {
+            if addr >= origin && addr < origin + words.len() * 2 && (addr - origin).is_multiple_of(2) {
+                labels.entry(addr).or_insert(kind); 
+            }
+        }
+    }
+    let name = |addr: u16| match labels.get(&(addr as usize)) {
+        Some(kind) => format!("{kind}_{addr:03X}"), 
+        None => format!("0x{addr:03X}"), 
+    }; 
+
+    let mut text = String::new(); 
+    for &(addr, opcode) in &words {
+        if let Some(kind) = labels.get(&addr) {
+            text.push_str(&format!("{kind}_{addr:03X}:\n")); 
+        }
+        text.push_str(&format!("0x{addr:03X}: {opcode:04X}  {}\n", format(opcode, name))); 
+    }
+    if !rom.len().is_multiple_of(2) {
+        let addr = origin + rom.len() - 1; 
+        let byte = rom[rom.len() - 1]; 
+        text.push_str(&format!("0x{addr:03X}: {byte:02X}    DB 0x{byte:02X}\n")); 
+    }
+    text
+}
+
+// where an instruction points to, and what kind of label that deserves 
+fn target(opcode: u16) -> Option<(usize, &'static str)> {
+    match decode(opcode)? {
+        Instruction::Jump(nnn) => Some((nnn as usize, "label")), 
+        Instruction::Call(nnn) => Some((nnn as usize, "sub")), 
+        Instruction::LoadIndex(nnn) => Some((nnn as usize, "data")), 
+        _ => None, 
+    }
+}
+
+// the mnemonic, with `addr` naming the 12-bit addresses 
+fn format(opcode: u16, addr: impl Fn(u16) -> String) -> String {
+    use Instruction::*; 
+
+    let Some(instruction) = decode(opcode) else {
+        return format!("DW 0x{opcode:04X}"); 
+    }; 
+    match instruction {
+        Halt => String::from("HALT"), 
+        Clear => String::from("CLS"), 
+        Return => String::from("RET"), 
+        ScrollDown(n) => format!("SCD {n}"), 
+        ScrollRight => String::from("SCR"), 
+        ScrollLeft => String::from("SCL"), 
+        Exit => String::from("EXIT"), 
+        LoRes => String::from("LOW"), 
+        HiRes => String::from("HIGH"), 
+        Sys(nnn) => format!("SYS {}", addr(nnn)), 
+        Jump(nnn) => format!("JP {}", addr(nnn)), 
+        Call(nnn) => format!("CALL {}", addr(nnn)), 
+        SkipEqualByte(x, kk) => format!("SE V{x:X}, 0x{kk:02X}"), 
+        SkipNotEqualByte(x, kk) => format!("SNE V{x:X}, 0x{kk:02X}"), 
+        SkipEqual(x, y) => format!("SE V{x:X}, V{y:X}"), 
+        LoadByte(x, kk) => format!("LD V{x:X}, 0x{kk:02X}"), 
+        AddByte(x, kk) => format!("ADD V{x:X}, 0x{kk:02X}"), 
+        Move(x, y) => format!("LD V{x:X}, V{y:X}"), 
+        Or(x, y) => format!("OR V{x:X}, V{y:X}"), 
+        And(x, y) => format!("AND V{x:X}, V{y:X}"), 
+        Xor(x, y) => format!("XOR V{x:X}, V{y:X}"), 
+        Add(x, y) => format!("ADD V{x:X}, V{y:X}"), 
+        Sub(x, y) => format!("SUB V{x:X}, V{y:X}"), 
+        ShiftRight(x, y) => format!("SHR V{x:X}, V{y:X}"), 
+        SubN(x, y) => format!("SUBN V{x:X}, V{y:X}"), 
+        ShiftLeft(x, y) => format!("SHL V{x:X}, V{y:X}"), 
+        SkipNotEqual(x, y) => format!("SNE V{x:X}, V{y:X}"), 
+        LoadIndex(nnn) => format!("LD I, {}", addr(nnn)), 
+        JumpOffset(nnn) => format!("JP V0, 0x{nnn:03X}"), 
+        Random(x, kk) => format!("RND V{x:X}, 0x{kk:02X}"), 
+        Draw(x, y, n) => format!("DRW V{x:X}, V{y:X}, {n}"), 
+        SkipKey(x) => format!("SKP V{x:X}"), 
+        SkipNotKey(x) => format!("SKNP V{x:X}"), 
+        GetDelay(x) => format!("LD V{x:X}, DT"), 
+        WaitKey(x) => format!("LD V{x:X}, K"), 
+        SetDelay(x) => format!("LD DT, V{x:X}"), 
+        SetSound(x) => format!("LD ST, V{x:X}"), 
+        AddIndex(x) => format!("ADD I, V{x:X}"), 
+        Font(x) => format!("LD F, V{x:X}"), 
+        BigFont(x) => format!("LD HF, V{x:X}"), 
+        Bcd(x) => format!("LD B, V{x:X}"), 
+        Store(x) => format!("LD [I], V{x:X}"), 
+        Load(x) => format!("LD V{x:X}, [I]"), 
+        SaveFlags(x) => format!("LD R, V{x:X}"), 
+        LoadFlags(x) => format!("LD V{x:X}, R"), 
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*; 
+
+    #[test]
+    fn mnemonics() {
+        assert_eq!(disassemble(0x2100), "CALL 0x100"); 
+        assert_eq!(disassemble(0x8014), "ADD V0, V1"); 
+        assert_eq!(disassemble(0x00E0), "CLS"); 
+        assert_eq!(disassemble(0x6A2F), "LD VA, 0x2F"); 
+        assert_eq!(disassemble(0xD125), "DRW V1, V2, 5"); 
+        assert_eq!(disassemble(0xF355), "LD [I], V3"); 
+        assert_eq!(disassemble(0xB2F0), "JP V0, 0x2F0"); 
+        assert_eq!(disassemble(0x00C4), "SCD 4"); 
+        assert_eq!(disassemble(0xF385), "LD V3, R"); 
+        // 5xy1 means nothing 
+        assert_eq!(disassemble(0x5121), "DW 0x5121"); 
+    }
+
+    #[test]
+    fn listing_with_labels() {
+        // call 0x206, loop forever at 0x202, sprite data at 0x208, an odd byte at the end 
+        let rom = [0x22, 0x06, 0x12, 0x02, 0x00, 0x00, 0xA2, 0x08, 0x00, 0xEE, 0xFF]; 
+        let text = listing(&rom, 0x200); 
+        assert_eq!(
+            text, 
+            "0x200: 2206  CALL sub_206\n\
+             label_202:\n\
+             0x202: 1202  JP label_202\n\
+             0x204: 0000  HALT\n\
+             sub_206:\n\
+             0x206: A208  LD I, data_208\n\
+             data_208:\n\
+             0x208: 00EE  RET\n\
+             0x20A: FF    DB 0xFF\n"
+        ); 
+    }
+}
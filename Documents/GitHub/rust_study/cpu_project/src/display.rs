@@ -0,0 +1,270 @@
+/*
+    the monochrome framebuffer, 64x32 or (SUPER-CHIP hi-res) 128x64 
+        pixels are either on or off; sprites are XORed onto the screen, so 
+        drawing the same sprite twice erases it again, and drawing over a 
+        lit pixel (turning it off) is a collision 
+
+    a sprite is up to 15 bytes, one byte per row, most significant bit on 
+        the left; its starting position wraps around the screen, and the 
+        rest of the sprite is either clipped at the right and bottom edges 
+        or wraps around to the other side as well 
+        draw_wide draws the SUPER-CHIP 16x16 sprites, two bytes per row 
+
+    switching between lo-res and hi-res clears the screen; scrolling moves 
+        everything by whole pixels of the current resolution, and what is 
+        scrolled in is blank 
+
+    fingerprint hashes the screen, so a test can compare a whole screen 
+        against what it should be with a single number 
+
+    to_half_blocks renders two pixel rows per line of text with the 
+        Unicode half blocks, so a 64x32 screen fits in 64x16 characters: 
+            both on '█', top only '▀', bottom only '▄', neither ' ' 
+*/
+
+// display size in pixels 
+pub const WIDTH: usize = 64; 
+pub const HEIGHT: usize = 32; 
+
+// in the SUPER-CHIP hi-res mode 
+pub const HIRES_WIDTH: usize = 128; 
+pub const HIRES_HEIGHT: usize = 64; 
+
+#[derive(Clone, PartialEq)]
+pub struct Display {
+    width: usize, 
+    height: usize, 
+    // row after row 
+    pixels: Vec<bool>, 
+}
+
+impl Display {
+    pub fn new() -> Display {
+        Display {
+            width: WIDTH, 
+            height: HEIGHT, 
+            pixels: vec![false; WIDTH * HEIGHT], 
+        }
+    }
+
+    pub fn width(&self) -> usize {
+        self.width
+    }
+
+    pub fn height(&self) -> usize {
+        self.height
+    }
+
+    pub fn is_hires(&self) -> bool {
+        self.width == HIRES_WIDTH
+    }
+
+    /// Switch to 128x64 (or back to 64x32), clearing the screen. 
+    pub fn set_hires(&mut self, hires: bool) {
+        (self.width, self.height) = if hires { (HIRES_WIDTH, HIRES_HEIGHT) } else { (WIDTH, HEIGHT) }; 
+        self.pixels = vec![false; self.width * self.height]; 
+    }
+
+    pub fn clear(&mut self) {
+        self.pixels.fill(false); 
+    }
+
+    /// Whether the pixel at (`x`, `y`) is on. 
+    /// 
+    /// # Panics 
+    /// 
+    /// The `get` function will panic if (`x`, `y`) is off the screen. 
+    pub fn get(&self, x: usize, y: usize) -> bool {
+        assert!(x < self.width && y < self.height, "({x}, {y}) is off the screen"); 
+        self.pixels[y * self.width + x]
+    }
+
+    /// Turn the pixel at (`x`, `y`) on or off. 
+    /// 
+    /// # Panics 
+    /// 
+    /// The `set` function will panic if (`x`, `y`) is off the screen. 
+    pub fn set(&mut self, x: usize, y: usize, on: bool) {
+        assert!(x < self.width && y < self.height, "({x}, {y}) is off the screen"); 
+        self.pixels[y * self.width + x] = on; 
+    }
+
+    /// XOR `sprite` onto the screen at (`x`, `y`); true on a collision. 
+    /// With `clip`, what goes past the edges is cut off instead of wrapping. 
+    pub fn draw(&mut self, x: usize, y: usize, sprite: &[u8], clip: bool) -> bool {
+        let rows: Vec<u16> = sprite.iter().map(|&bits| (bits as u16) << 8).collect(); 
+        self.blit(x, y, &rows, 8, clip)
+    }
+
+    /// Like `draw`, for a 16x16 sprite of 32 bytes (two per row). 
+    pub fn draw_wide(&mut self, x: usize, y: usize, sprite: &[u8], clip: bool) -> bool {
+        let rows: Vec<u16> = sprite
+            .chunks_exact(2)
+            .map(|pair| u16::from_be_bytes([pair[0], pair[1]]))
+            .collect(); 
+        self.blit(x, y, &rows, 16, clip)
+    }
+
+    // XOR the leftmost `width` bits of every row 
+    fn blit(&mut self, x: usize, y: usize, rows: &[u16], width: usize, clip: bool) -> bool {
+        let left = x % self.width; 
+        let top = y % self.height; 
+        let mut collision = false; 
+
+        for (row, bits) in rows.iter().enumerate() {
+            let py = top + row; 
+            if py >= self.height && clip {
+                break; 
+            }
+            let py = py % self.height; 
+            for col in 0..width {
+                let px = left + col; 
+                if px >= self.width && clip {
+                    break; 
+                }
+                let px = px % self.width; 
+                if bits & (0x8000 >> col) != 0 {
+                    let pixel = &mut self.pixels[py * self.width + px]; 
+                    collision |= *pixel; 
+                    *pixel = !*pixel; 
+                }
+            }
+        }
+        collision
+    }
+
+    /// Move everything down by `n` rows. 
+    pub fn scroll_down(&mut self, n: usize) {
+        let shift = (n * self.width).min(self.pixels.len()); 
+        self.pixels.rotate_right(shift); 
+        self.pixels[..shift].fill(false); 
+    }
+
+    /// Move everything left by `n` columns. 
+    pub fn scroll_left(&mut self, n: usize) {
+        let n = n.min(self.width); 
+        for row in self.pixels.chunks_mut(self.width) {
+            row.rotate_left(n); 
+            let width = row.len(); 
+            row[width - n..].fill(false); 
+        }
+    }
+
+    /// Move everything right by `n` columns. 
+    pub fn scroll_right(&mut self, n: usize) {
+        let n = n.min(self.width); 
+        for row in self.pixels.chunks_mut(self.width) {
+            row.rotate_right(n); 
+            row[..n].fill(false); 
+        }
+    }
+
+    /// A 64-bit FNV-1a hash of the resolution and the pixels, the same on 
+    /// every machine and with every version of Rust, for tests to compare. 
+    pub fn fingerprint(&self) -> u64 {
+        let mut hash: u64 = 0xCBF2_9CE4_8422_2325; 
+        let size = [self.width as u8, self.height as u8]; 
+        for byte in size.into_iter().chain(self.pixels.iter().map(|&on| on as u8)) {
+            hash ^= byte as u64; 
+            hash = hash.wrapping_mul(0x0000_0100_0000_01B3); 
+        }
+        hash
+    }
+
+    /// The screen as lines of half-block characters, one per two rows. 
+    pub fn to_half_blocks(&self) -> String {
+        let mut text = String::with_capacity((self.width * 3 + 1) * self.height / 2); 
+        for y in (0..self.height).step_by(2) {
+            for x in 0..self.width {
+                text.push(match (self.get(x, y), self.get(x, y + 1)) {
+                    (true, true) => '█', 
+                    (true, false) => '▀', 
+                    (false, true) => '▄', 
+                    (false, false) => ' ', 
+                }); 
+            }
+            text.push('\n'); 
+        }
+        text
+    }
+}
+
+impl Default for Display {
+    fn default() -> Display {
+        Display::new()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*; 
+
+    #[test]
+    fn half_blocks() {
+        let mut display = Display::new(); 
+        // a 2x2 pattern: top row both on, bottom row only the right one 
+        display.draw(0, 0, &[0b1100_0000, 0b0100_0000], true); 
+        display.draw(0, 3, &[0b1000_0000], true); 
+
+        let text = display.to_half_blocks(); 
+        let lines: Vec<&str> = text.lines().collect(); 
+        assert_eq!(lines.len(), HEIGHT / 2); 
+        assert!(lines.iter().all(|line| line.chars().count() == WIDTH)); 
+        assert!(lines[0].starts_with("▀█ ")); 
+        assert!(lines[1].starts_with("▄ ")); 
+        assert_eq!(lines[2].trim(), ""); 
+    }
+
+    #[test]
+    fn draw_reports_collisions_only_when_a_pixel_goes_off() {
+        let mut display = Display::new(); 
+        assert!(!display.draw(10, 10, &[0b1010_0000], true)); 
+        assert!(!display.draw(10, 10, &[0b0101_0000], true)); 
+        assert!(display.draw(10, 10, &[0b1000_0000], true)); 
+        assert!(!display.get(10, 10) && display.get(11, 10) && display.get(12, 10)); 
+    }
+
+    #[test]
+    fn clipped_or_wrapped() {
+        let mut display = Display::new(); 
+        display.draw(62, 31, &[0xFF, 0xFF], true); 
+        assert!(display.get(63, 31) && !display.get(0, 31) && !display.get(62, 0)); 
+
+        let mut display = Display::new(); 
+        display.draw(62, 31, &[0xFF, 0xFF], false); 
+        assert!(display.get(63, 31) && display.get(5, 31) && display.get(62, 0)); 
+        assert!(!display.get(6, 31)); 
+    }
+
+    #[test]
+    fn hires_and_wide_sprites() {
+        let mut display = Display::new(); 
+        display.draw(0, 0, &[0x80], true); 
+        display.set_hires(true); 
+        assert_eq!((display.width(), display.height()), (128, 64)); 
+        assert!(!display.get(0, 0)); 
+
+        // the top left and the top right corner of the sprite, and its 
+        // bottom left, which is clipped 
+        let mut sprite = [0u8; 32]; 
+        sprite[0] = 0x80; 
+        sprite[1] = 0x01; 
+        sprite[30] = 0x80; 
+        display.draw_wide(112, 50, &sprite, true); 
+        assert!(display.get(112, 50) && display.get(127, 50)); 
+        assert!(!display.get(112, 1)); 
+        assert_eq!(display.to_half_blocks().lines().count(), 32); 
+    }
+
+    #[test]
+    fn scrolling() {
+        let mut display = Display::new(); 
+        display.draw(4, 0, &[0x80], true); 
+        display.scroll_down(3); 
+        assert!(display.get(4, 3) && !display.get(4, 0)); 
+        display.scroll_right(4); 
+        assert!(display.get(8, 3)); 
+        display.scroll_left(10); 
+        assert!(!(0..WIDTH).any(|x| display.get(x, 3))); 
+    }
+}
@@ -0,0 +1,266 @@
+/*
+    screenshots: the display as an image file 
+        on pixels are black and off pixels white, as in PBM, where 1 is 
+        black; every pixel can be blown up to a square of scale x scale 
+
+    PBM (the binary P4 kind): "P4", the width and the height in text, then 
+        the pixels row after row, 8 to a byte, most significant bit first, 
+        every row starting on a new byte 
+
+    PNG, written here without a library: the signature, then the chunks 
+        IHDR        the size, 8-bit grayscale, no interlacing 
+        IDAT        the rows, each after a 0 (no filter), in a zlib stream 
+                    of stored (uncompressed) deflate blocks 
+        IEND 
+        every chunk is its length, its type, its data and the CRC-32 of 
+        type and data; the zlib stream ends with the Adler-32 of the rows 
+        the files are bigger than they need be, but a screen is small 
+*/
+
+use crate::display::Display; 
+
+// gray levels in the PNG 
+const ON: u8 = 0x00; 
+const OFF: u8 = 0xFF; 
+
+// the most a stored deflate block holds 
+const BLOCK: usize = 0xFFFF; 
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Format {
+    Png, 
+    Pbm, 
+}
+
+impl Format {
+    /// The format for a file name extension, png or pbm. 
+    pub fn from_extension(extension: &str) -> Option<Format> {
+        match extension.to_ascii_lowercase().as_str() {
+            "png" => Some(Format::Png), 
+            "pbm" => Some(Format::Pbm), 
+            _ => None, 
+        }
+    }
+
+    pub fn extension(self) -> &'static str {
+        match self {
+            Format::Png => "png", 
+            Format::Pbm => "pbm", 
+        }
+    }
+
+    /// `display` as a file of this format, every pixel `scale` x `scale`. 
+    pub fn encode(self, display: &Display, scale: usize) -> Vec<u8> {
+        match self {
+            Format::Png => png(display, scale), 
+            Format::Pbm => pbm(display, scale), 
+        }
+    }
+}
+
+// the pixels of `display` blown up by `scale`, row after row 
+fn rows(display: &Display, scale: usize) -> Vec<Vec<bool>> {
+    let mut rows = Vec::with_capacity(display.height() * scale); 
+    for y in 0..display.height() {
+        let row: Vec<bool> = (0..display.width() * scale).map(|x| display.get(x / scale, y)).collect(); 
+        for _ in 0..scale {
+            rows.push(row.clone()); 
+        }
+    }
+    rows
+}
+
+/// `display` as a binary PBM image. 
+/// 
+/// # Panics 
+/// 
+/// The `pbm` function will panic if `scale` is 0. 
+pub fn pbm(display: &Display, scale: usize) -> Vec<u8> {
+    assert!(scale > 0, "the scale must be 1 or more"); 
+    let mut bytes = format!("P4\n{} {}\n", display.width() * scale, display.height() * scale).into_bytes(); 
+    for row in rows(display, scale) {
+        for eight in row.chunks(8) {
+            let byte = eight.iter().enumerate().fold(0u8, |byte, (n, &on)| byte | (on as u8) << (7 - n)); 
+            bytes.push(byte); 
+        }
+    }
+    bytes
+}
+
+/// `display` as a grayscale PNG image. 
+/// 
+/// # Panics 
+/// 
+/// The `png` function will panic if `scale` is 0. 
+pub fn png(display: &Display, scale: usize) -> Vec<u8> {
+    assert!(scale > 0, "the scale must be 1 or more"); 
+    let (width, height) = ((display.width() * scale) as u32, (display.height() * scale) as u32); 
+
+    let mut raw = Vec::with_capacity((width as usize + 1) * height as usize); 
+    for row in rows(display, scale) {
+        raw.push(0); 
+        raw.extend(row.iter().map(|&on| if on { ON } else { OFF })); 
+    }
+
+    let mut header = Vec::with_capacity(13); 
+    header.extend_from_slice(&width.to_be_bytes()); 
+    header.extend_from_slice(&height.to_be_bytes()); 
+    // 8 bits of gray, the standard compression and filters, no interlacing 
+    header.extend_from_slice(&[8, 0, 0, 0, 0]); 
+
+    let mut bytes = b"\x89PNG\r\n\x1A\n".to_vec(); 
+    chunk(&mut bytes, b"IHDR", &header); 
+    chunk(&mut bytes, b"IDAT", &zlib(&raw)); 
+    chunk(&mut bytes, b"IEND", &[]); 
+    bytes
+}
+
+fn chunk(bytes: &mut Vec<u8>, kind: &[u8; 4], data: &[u8]) {
+    bytes.extend_from_slice(&(data.len() as u32).to_be_bytes()); 
+    let start = bytes.len(); 
+    bytes.extend_from_slice(kind); 
+    bytes.extend_from_slice(data); 
+    let crc = crc32(&bytes[start..]); 
+    bytes.extend_from_slice(&crc.to_be_bytes()); 
+}
+
+// `data` in a zlib stream of stored blocks 
+fn zlib(data: &[u8]) -> Vec<u8> {
+    // deflate with a 32K window, no dictionary; 0x7801 is a multiple of 31 
+    let mut stream = vec![0x78, 0x01]; 
+    let blocks: Vec<&[u8]> = data.chunks(BLOCK).collect(); 
+    if blocks.is_empty() {
+        stream.extend_from_slice(&[1, 0, 0, 0xFF, 0xFF]); 
+    }
+    for (n, block) in blocks.iter().enumerate() {
+        let last = n == blocks.len() - 1; 
+        let len = block.len() as u16; 
+        stream.push(last as u8); 
+        stream.extend_from_slice(&len.to_le_bytes()); 
+        stream.extend_from_slice(&(!len).to_le_bytes()); 
+        stream.extend_from_slice(block); 
+    }
+    stream.extend_from_slice(&adler32(data).to_be_bytes()); 
+    stream
+}
+
+// the CRC-32 of PNG (and zip), bit by bit 
+fn crc32(data: &[u8]) -> u32 {
+    let mut crc = !0u32; 
+    for &byte in data {
+        crc ^= byte as u32; 
+        for _ in 0..8 {
+            crc = if crc & 1 != 0 { crc >> 1 ^ 0xEDB8_8320 } else { crc >> 1 }; 
+        }
+    }
+    !crc
+}
+
+fn adler32(data: &[u8]) -> u32 {
+    let (mut a, mut b) = (1u32, 0u32); 
+    for &byte in data {
+        a = (a + byte as u32) % 65521; 
+        b = (b + a) % 65521; 
+    }
+    b << 16 | a
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*; 
+
+    // a display with its corners and one pixel next to them lit 
+    fn display() -> Display {
+        let mut display = Display::new(); 
+        for (x, y) in [(0, 0), (1, 0), (63, 0), (0, 31), (63, 31)] {
+            display.set(x, y, true); 
+        }
+        display
+    }
+
+    // the rows of a PNG written by `png`, read back 
+    fn unpng(bytes: &[u8]) -> (u32, u32, Vec<u8>) {
+        assert_eq!(&bytes[..8], b"\x89PNG\r\n\x1A\n"); 
+        let (mut size, mut raw, mut rest) = ((0, 0), Vec::new(), &bytes[8..]); 
+        while !rest.is_empty() {
+            let len = u32::from_be_bytes(rest[..4].try_into().unwrap()) as usize; 
+            let (kind, data) = (&rest[4..8], &rest[8..8 + len]); 
+            let crc = u32::from_be_bytes(rest[8 + len..12 + len].try_into().unwrap()); 
+            assert_eq!(crc, crc32(&rest[4..8 + len])); 
+            match kind {
+                b"IHDR" => {
+                    size = (u32::from_be_bytes(data[..4].try_into().unwrap()), u32::from_be_bytes(data[4..8].try_into().unwrap())); 
+                    assert_eq!(data[8..], [8, 0, 0, 0, 0]); 
+                }
+                b"IDAT" => {
+                    let mut stream = &data[2..]; 
+                    loop {
+                        let len = u16::from_le_bytes([stream[1], stream[2]]) as usize; 
+                        raw.extend_from_slice(&stream[5..5 + len]); 
+                        let last = stream[0] & 1 == 1; 
+                        stream = &stream[5 + len..]; 
+                        if last {
+                            break; 
+                        }
+                    }
+                    assert_eq!(stream, adler32(&raw).to_be_bytes()); 
+                }
+                _ => {}
+            }
+            rest = &rest[12 + len..]; 
+        }
+        (size.0, size.1, raw)
+    }
+
+    #[test]
+    fn checksums() {
+        assert_eq!(crc32(b"123456789"), 0xCBF4_3926); 
+        assert_eq!(adler32(b"Wikipedia"), 0x11E6_0398); 
+    }
+
+    #[test]
+    fn pbm_image() {
+        let bytes = pbm(&display(), 1); 
+        let header = b"P4\n64 32\n"; 
+        assert_eq!(&bytes[..header.len()], header); 
+        let pixels = &bytes[header.len()..]; 
+        assert_eq!(pixels.len(), 8 * 32); 
+        assert_eq!(pixels[..8], [0xC0, 0, 0, 0, 0, 0, 0, 0x01]); 
+        assert_eq!(pixels[8 * 31..], [0x80, 0, 0, 0, 0, 0, 0, 0x01]); 
+        assert!(pixels[8..8 * 31].iter().all(|&byte| byte == 0)); 
+
+        // twice the size: every pixel a 2x2 square 
+        let bytes = pbm(&display(), 2); 
+        assert!(bytes.starts_with(b"P4\n128 64\n")); 
+        assert_eq!(bytes[10..10 + 16], bytes[10 + 16..10 + 32]); 
+        assert_eq!(bytes[10], 0xF0); 
+    }
+
+    #[test]
+    fn png_image() {
+        let (width, height, raw) = unpng(&png(&display(), 1)); 
+        assert_eq!((width, height), (64, 32)); 
+        assert_eq!(raw.len(), 65 * 32); 
+        let row = |y: usize| &raw[y * 65..(y + 1) * 65]; 
+        assert_eq!(row(0)[..4], [0, ON, ON, OFF]); 
+        assert_eq!(row(0)[64], ON); 
+        assert!(row(1)[1..].iter().all(|&gray| gray == OFF)); 
+
+        // more rows than a stored block holds 
+        let mut hires = display(); 
+        hires.set_hires(true); 
+        hires.set(127, 63, true); 
+        let (width, height, raw) = unpng(&png(&hires, 8)); 
+        assert_eq!((width, height), (1024, 512)); 
+        assert_eq!(raw.len(), 1025 * 512); 
+        assert_eq!(raw[raw.len() - 8..], [ON; 8]); 
+    }
+
+    #[test]
+    fn formats() {
+        assert_eq!(Format::from_extension("PNG"), Some(Format::Png)); 
+        assert_eq!(Format::from_extension("pbm"), Some(Format::Pbm)); 
+        assert_eq!(Format::from_extension("gif"), None); 
+        assert_eq!(Format::Pbm.encode(&display(), 1), pbm(&display(), 1)); 
+    }
+}
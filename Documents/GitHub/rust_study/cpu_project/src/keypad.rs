@@ -0,0 +1,225 @@
+/*
+    where the keys of the hex keypad come from 
+        the COSMAC VIP keypad, and the keys on the left of a QWERTY 
+        keyboard it is usually mapped to: 
+            1 2 3 C         1 2 3 4 
+            4 5 6 D         q w e r 
+            7 8 9 E         a s d f 
+            A 0 B F         z x c v 
+        a Keymap can use any other 16 characters instead, given in the 
+        order of the keypad read row by row (1 2 3 C 4 5 6 D ...) 
+
+    a terminal only tells when a key is typed, never when it is let go, 
+        so Held keeps every typed key down for HOLD_FRAMES frames; the 
+        autorepeat of the keyboard keeps a key that is held down pressed 
+        (after a short gap, the autorepeat delay) 
+
+    a Script plays the keys instead, for tests and demos: one line per 
+        change, the frame it happens at and the keys held down from then 
+        on (hex digits, or - for none); # starts a comment 
+            60 5            hold 5 from frame 60 
+            64 -            and let go at 64 
+            120 4 6         then hold 4 and 6 
+*/
+
+use std::fmt; 
+
+// the keys of the keypad, row by row 
+const KEYPAD: [u8; 16] = [0x1, 0x2, 0x3, 0xC, 0x4, 0x5, 0x6, 0xD, 0x7, 0x8, 0x9, 0xE, 0xA, 0x0, 0xB, 0xF]; 
+
+/// How long a typed key stays down: 6 frames, a tenth of a second. 
+pub const HOLD_FRAMES: u32 = 6; 
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Keymap {
+    // the character for each key of KEYPAD 
+    layout: [char; 16], 
+}
+
+#[derive(Debug, Default)]
+pub struct Held {
+    // frames to go for every key, 0 when it is up 
+    frames_left: [u32; 16], 
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Script {
+    // (frame, keys) with the frames in order 
+    changes: Vec<(u64, u16)>, 
+}
+
+#[derive(Debug, PartialEq)]
+pub struct ScriptError {
+    pub line: usize, 
+    pub message: String, 
+}
+
+impl fmt::Display for ScriptError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "line {}: {}", self.line, self.message)
+    }
+}
+
+impl std::error::Error for ScriptError {}
+
+impl Keymap {
+    pub fn qwerty() -> Keymap {
+        Keymap::from_layout("1234qwerasdfzxcv").unwrap()
+    }
+
+    /// A keymap from 16 different characters for 1 2 3 C 4 5 6 D 7 8 9 E A 0 B F. 
+    pub fn from_layout(layout: &str) -> Result<Keymap, String> {
+        let chars: Vec<char> = layout.chars().map(|c| c.to_ascii_lowercase()).collect(); 
+        let layout: [char; 16] = chars
+            .try_into()
+            .map_err(|_| format!("a layout has 16 keys, `{layout}` does not"))?; 
+        for (n, c) in layout.iter().enumerate() {
+            if layout[..n].contains(c) {
+                return Err(format!("`{c}` is in the layout twice")); 
+            }
+        }
+        Ok(Keymap { layout })
+    }
+
+    /// The keypad key for the typed character `c`, if any. 
+    pub fn key(&self, c: char) -> Option<u8> {
+        let c = c.to_ascii_lowercase(); 
+        self.layout.iter().position(|&k| k == c).map(|n| KEYPAD[n])
+    }
+}
+
+impl Default for Keymap {
+    fn default() -> Keymap {
+        Keymap::qwerty()
+    }
+}
+
+impl Held {
+    pub fn new() -> Held {
+        Held::default()
+    }
+
+    /// `key` was typed: down for the next `HOLD_FRAMES` frames. 
+    pub fn press(&mut self, key: u8) {
+        self.frames_left[key as usize & 0xF] = HOLD_FRAMES; 
+    }
+
+    /// The keys down during the next frame, as for `CPU::set_keypad`. 
+    pub fn next_frame(&mut self) -> u16 {
+        let mut keys = 0; 
+        for (k, left) in self.frames_left.iter_mut().enumerate() {
+            if *left > 0 {
+                keys |= 1 << k; 
+                *left -= 1; 
+            }
+        }
+        keys
+    }
+}
+
+impl Script {
+    pub fn parse(text: &str) -> Result<Script, ScriptError> {
+        let mut changes: Vec<(u64, u16)> = Vec::new(); 
+        for (n, line) in text.lines().enumerate() {
+            let error = |message: String| ScriptError { line: n + 1, message }; 
+            let mut words = line.split('#').next().unwrap_or("").split_whitespace(); 
+            let Some(frame) = words.next() else {
+                continue; 
+            }; 
+
+            let frame: u64 = frame.parse().map_err(|_| error(format!("not a frame number: {frame}")))?; 
+            if let Some(&(last, _)) = changes.last().filter(|&&(last, _)| frame <= last) {
+                return Err(error(format!("frame {frame} comes after frame {last}"))); 
+            }
+            let mut held = 0u16; 
+            for key in words {
+                if key == "-" {
+                    continue; 
+                }
+                let key = u8::from_str_radix(key, 16)
+                    .ok()
+                    .filter(|&k| k < 16)
+                    .ok_or_else(|| error(format!("not a key: {key}")))?; 
+                held |= 1 << key; 
+            }
+            changes.push((frame, held)); 
+        }
+        Ok(Script { changes })
+    }
+
+    /// The keys held down during `frame`. 
+    pub fn keys(&self, frame: u64) -> u16 {
+        self.changes
+            .iter()
+            .take_while(|&&(at, _)| at <= frame)
+            .last()
+            .map_or(0, |&(_, keys)| keys)
+    }
+
+    /// The frame of the last change. 
+    pub fn end(&self) -> u64 {
+        self.changes.last().map_or(0, |&(frame, _)| frame)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*; 
+    use crate::cpu::CPU; 
+
+    #[test]
+    fn qwerty_layout() {
+        let keymap = Keymap::qwerty(); 
+        assert_eq!(keymap.key('1'), Some(0x1)); 
+        assert_eq!(keymap.key('4'), Some(0xC)); 
+        assert_eq!(keymap.key('X'), Some(0x0)); 
+        assert_eq!(keymap.key('v'), Some(0xF)); 
+        assert_eq!(keymap.key('p'), None); 
+
+        // the same keys on an AZERTY keyboard 
+        let azerty = Keymap::from_layout("&é\"'azerqsdfwxcv").unwrap(); 
+        assert_eq!(azerty.key('w'), Some(0xA)); 
+        assert!(Keymap::from_layout("1234").is_err()); 
+        assert!(Keymap::from_layout("1234qwerasdfzxcc").is_err()); 
+    }
+
+    #[test]
+    fn typed_keys_are_held_for_a_while() {
+        let mut held = Held::new(); 
+        held.press(0xA); 
+        for _ in 0..HOLD_FRAMES {
+            assert_eq!(held.next_frame(), 1 << 0xA); 
+        }
+        assert_eq!(held.next_frame(), 0); 
+    }
+
+    #[test]
+    fn scripts() {
+        let script = Script::parse("# a tap on 5\n2 5\n3 - \n\n10 4 f # two keys\n").unwrap(); 
+        assert_eq!(script.keys(0), 0); 
+        assert_eq!(script.keys(2), 1 << 5); 
+        assert_eq!(script.keys(3), 0); 
+        assert_eq!(script.keys(99), 1 << 4 | 1 << 0xF); 
+        assert_eq!(script.end(), 10); 
+
+        assert_eq!(Script::parse("5 1\n4 2").unwrap_err().line, 2); 
+        assert_eq!(Script::parse("1 G").unwrap_err().message, "not a key: G"); 
+    }
+
+    #[test]
+    fn a_script_answers_fx0a() {
+        // wait for a key into V0, then halt 
+        let mut cpu = CPU::new(); 
+        cpu.load_rom(&[0xF0, 0x0A]).unwrap(); 
+        let script = Script::parse("2 7\n3 -").unwrap(); 
+
+        let mut frame = 0; 
+        cpu.set_keypad(script.keys(frame)); 
+        while cpu.frame().unwrap() {
+            frame += 1; 
+            cpu.set_keypad(script.keys(frame)); 
+        }
+        assert_eq!(frame, 3); 
+        assert_eq!(cpu.registers()[0], 7); 
+    }
+}
@@ -0,0 +1,35 @@
+/*
+    a CHIP-8 emulator 
+        cpu (the machine: memory, registers, timers and the instruction 
+        set), display (the 64x32 framebuffer), terminal (draws the 
+        display with ANSI escape codes and reads the keyboard), keypad 
+        (keyboard layouts and scripted keys), debugger (breakpoints, 
+        watchpoints and a command line to drive them), decode (opcodes to 
+        instructions, for both the cpu and disasm), disasm (opcodes back 
+        to mnemonics), asm (mnemonics to opcodes), state (save states), 
+        replay (recording the keypad to replay a run) and trace (a log of 
+        every instruction, and a profile of the hot spots), audio (the 
+        beep of the sound timer, as samples or a .wav file) and image 
+        (the display as a PNG or PBM file) 
+
+    the test ROMs in roms/tests are run by tests/conformance.rs 
+
+    the cpu binary (main.rs) runs a ROM file in the terminal, the disasm 
+        binary (bin/disasm.rs) prints a ROM's listing, the asm binary 
+        (bin/asm.rs) builds a ROM from source, and the headless binary 
+        (bin/headless.rs) runs a ROM without a terminal and saves what it 
+        draws as images 
+*/
+pub mod asm; 
+pub mod audio; 
+pub mod cpu; 
+pub mod debugger; 
+pub mod decode; 
+pub mod disasm; 
+pub mod display; 
+pub mod image; 
+pub mod keypad; 
+pub mod replay; 
+pub mod state; 
+pub mod terminal; 
+pub mod trace; 
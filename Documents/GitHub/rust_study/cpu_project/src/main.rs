@@ -1,113 +1,276 @@
 /*
-    CHIP-8 CPU Implementation 
+    running a ROM in the terminal 
+        cargo run -- roms/digits.ch8 
+        cargo run -- game.ch8 --hz 1000 --shifting --no-clipping 
+        cargo run -- game.ch8 --debug       # see debugger.rs 
+        cargo run -- game.ch8 --preset schip --no-clipping 
+        cargo run -- game.ch8 --script keys.txt  # see keypad.rs 
+        cargo run -- game.ch8 --trace trace.txt --profile  # see trace.rs 
+        cargo run -- game.ch8 --wav beep.wav     # see audio.rs 
+
+    the program runs a frame (1/60 s of emulated time) at a time, and the 
+        display is drawn after every frame; it ends when the program halts 
+        (opcode 0000) or with Esc or Ctrl-C 
+
+    the keypad is played on the keyboard, 1234 qwer asdf zxcv for the rows 
+        of 123C 456D 789E A0BF (see keypad.rs), or by a script 
+
+    options: 
+        --hz <n>            instructions per second (600, at least 60) 
+        --debug             step through the program instead of running it 
+        --preset <name>     the quirks of vip (the default), chip48 or schip; 
+                            the flags below change the preset, in any order 
+        --no-vf-reset       8xy1/8xy2/8xy3 leave VF alone 
+        --no-memory         Fx55/Fx65 leave I alone 
+        --no-clipping       sprites wrap around the edges 
+        --shifting          8xy6/8xyE shift Vx in place 
+        --jumping           Bxnn jumps to xnn + Vx 
+        --keys <layout>     the 16 keys for 123C456D789EA0BF (1234qwerasdfzxcv) 
+        --script <file>     play the keypad from a script, not the keyboard 
+        --trace <file>      write every instruction executed to file 
+        --profile           print the hot addresses and the opcodes used 
+                            when the program ends 
+        --wav <file>        write the sound to a .wav file 
 */
 
-// definintion of the CPU 
-struct CPU {
-    registers: [u8; 16], 
-    pc: usize, // program counter 
-    memory: [u8; 4096], 
-    stack: [u16; 16], 
-    stack_pointer: usize, 
+use std::{
+    env, 
+    error::Error, 
+    fs::{self, File}, 
+    io::{self, BufWriter, Write}, 
+    path::PathBuf, 
+    process, 
+    thread, 
+    time::{Duration, Instant}, 
+}; 
+
+use cpu::{
+    audio::{Beeper, WavFile}, 
+    cpu::{Quirks, CPU, DEFAULT_CLOCK_HZ, TIMER_HZ}, 
+    debugger::Debugger, 
+    keypad::{Held, Keymap, Script}, 
+    terminal::{Keyboard, Terminal}, 
+    trace::{Profiler, Tracer}, 
+}; 
+
+// the hot addresses the profile shows 
+const PROFILE_TOP: usize = 10; 
+
+// the bytes that end the program in raw mode 
+const CTRL_C: u8 = 0x03; 
+const ESC: u8 = 0x1B; 
+
+struct Args {
+    rom: PathBuf, 
+    hz: u32, 
+    quirks: Quirks, 
+    debug: bool, 
+    keymap: Keymap, 
+    script: Option<PathBuf>, 
+    trace: Option<PathBuf>, 
+    profile: bool, 
+    wav: Option<PathBuf>, 
 }
 
-impl CPU {
-    // reading opcode from memory 
-    fn read_opcode(&self) -> u16 {
-        let p = self.pc; 
-        let op_byte1 = self.memory[p] as u16; 
-        let op_byte2 = self.memory[p + 1] as u16; 
+fn main() {
+    let args = parse_args(env::args().skip(1)).unwrap_or_else(|err| {
+        eprintln!("Problem parsing arguments: {err}"); 
+        eprintln!("usage: cpu <rom> [--hz <n>] [--debug] [--preset vip|chip48|schip] [--no-vf-reset] [--no-memory] [--no-clipping] [--shifting] [--jumping] [--keys <layout>] [--script <file>] [--trace <file>] [--profile] [--wav <file>]"); 
+        process::exit(1); 
+    }); 
+
+    let rom = fs::read(&args.rom).unwrap_or_else(|err| {
+        eprintln!("{}: {err}", args.rom.display()); 
+        process::exit(1); 
+    }); 
+
+    let script = args.script.as_ref().map(|path| {
+        let text = fs::read_to_string(path).unwrap_or_else(|err| {
+            eprintln!("{}: {err}", path.display()); 
+            process::exit(1); 
+        }); 
+        Script::parse(&text).unwrap_or_else(|err| {
+            eprintln!("{}:{}: {}", path.display(), err.line, err.message); 
+            process::exit(1); 
+        })
+    }); 
 
-        op_byte1 << 8 | op_byte2 
+    let mut cpu = CPU::new(); 
+    cpu.set_clock_hz(args.hz); 
+    cpu.set_quirks(args.quirks); 
+    if let Err(err) = cpu.load_rom(&rom) {
+        eprintln!("{}: {err}", args.rom.display()); 
+        process::exit(1); 
     }
 
-    fn run(&mut self) {
-        loop {
-            let opcode = self.read_opcode(); 
-            self.pc += 2; 
-
-            // decoding opcode in form of 0xCXYD 
-            let c = ((opcode & 0xF000) >> 12) as u8; // opcode group 
-            let x = ((opcode & 0x0F00) >>  8) as u8; // CPU register 
-            let y = ((opcode & 0x00F0) >>  4) as u8; // CPU register 
-            let d = ((opcode & 0x000F)      ) as u8; // opcode subgroup 
-
-            let nnn = (opcode & 0x0FFF); // memory address 
-
-            // dispatches execution 
-            match (c, x, y, d) {
-                (0, 0, 0, 0)        => { return; }, // terminate execution 
-                (0, 0, 0xE, 0xE)    => self.ret(), 
-                (0x2, _, _, _)      => self.call(nnn), 
-                (0x8, _, _, 0x4)    => self.add_xy(x, y), 
-                _                   => todo! ("opcode {:04x}", opcode), 
-            }
+    if args.debug {
+        let mut debugger = Debugger::new(cpu); 
+        if let Err(e) = debugger.repl(io::stdin().lock(), io::stdout()) {
+            eprintln!("{e}"); 
+            process::exit(1); 
         }
+        return; 
     }
 
-    // add two numbers at register x and y   
-    fn add_xy(&mut self, x: u8, y:u8) {
-        let vx = self.registers[x as usize]; 
-        let vy = self.registers[y as usize]; 
-
-        let (val, overflow) = vx.overflowing_add(vy); 
-        self.registers[x as usize] = val; 
+    let mut tracer = args.trace.as_ref().map(|path| {
+        let file = File::create(path).unwrap_or_else(|err| {
+            eprintln!("{}: {err}", path.display()); 
+            process::exit(1); 
+        }); 
+        Tracer::new(BufWriter::new(file), &cpu)
+    }); 
+    let mut profiler = args.profile.then(Profiler::new); 
+    let mut wav = args.wav.as_ref().map(|path| {
+        File::create(path)
+            .and_then(|file| WavFile::new(BufWriter::new(file)))
+            .unwrap_or_else(|err| {
+                eprintln!("{}: {err}", path.display()); 
+                process::exit(1); 
+            })
+    }); 
 
-        // handle overflow 
-        if overflow {
-            // last register as a carry flag 
-            self.registers[0xF] = 1; 
-        } else {
-            self.registers[0xF] = 0; 
+    let shown = show(&mut cpu, &args.keymap, script.as_ref(), tracer.as_mut(), profiler.as_mut(), wav.as_mut()); 
+    // what led up to a fault is the interesting part of a trace 
+    if let Some(tracer) = tracer {
+        if let Err(e) = tracer.into_inner().flush() {
+            eprintln!("{}: {e}", args.trace.unwrap().display()); 
         }
     }
-
-    // calling memory location  at addr 
-    fn call(&mut self, addr: u16) {
-        let sp = self.stack_pointer; 
-        let stack = &mut self.stack; 
-
-        if sp > stack.len() {
-            panic!("Stack overflow!") 
+    if let Some(wav) = wav {
+        if let Err(e) = wav.finish() {
+            eprintln!("{}: {e}", args.wav.unwrap().display()); 
         }
-
-        stack[sp] = self.pc as u16; // current position 
-        self.stack_pointer += 1;    // increments stack pointer 
-        self.pc = addr as usize; 
     }
+    if let Some(profiler) = profiler {
+        print!("{}", profiler.report(PROFILE_TOP)); 
+    }
+    if let Err(e) = shown {
+        eprintln!("{}: {e}", args.rom.display()); 
+        process::exit(1); 
+    }
+}
 
-    // retruning from a function 
-    fn ret(&mut self) {
-        if self.stack_pointer == 0 {
-            panic!("Stack underflow"); 
+fn parse_args(mut args: impl Iterator<Item = String>) -> Result<Args, String> {
+    let mut rom = None; 
+    let mut hz = DEFAULT_CLOCK_HZ; 
+    let mut preset = Quirks::default(); 
+    let mut changes: Vec<fn(&mut Quirks)> = Vec::new(); 
+    let mut debug = false; 
+    let mut keymap = Keymap::qwerty(); 
+    let mut script = None; 
+    let mut trace = None; 
+    let mut profile = false; 
+    let mut wav = None; 
+
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "--hz" => {
+                let value = args.next().ok_or("--hz needs a value")?; 
+                hz = value.parse().map_err(|_| format!("invalid clock speed `{value}`"))?; 
+                if hz < TIMER_HZ {
+                    return Err(format!("the clock must run at {TIMER_HZ}Hz or faster")); 
+                }
+            }
+            "--debug" => debug = true, 
+            "--preset" => {
+                let name = args.next().ok_or("--preset needs a name")?; 
+                preset = Quirks::preset(&name).ok_or(format!("unknown preset `{name}`"))?; 
+            }
+            "--keys" => {
+                let layout = args.next().ok_or("--keys needs a layout")?; 
+                keymap = Keymap::from_layout(&layout)?; 
+            }
+            "--script" => script = Some(PathBuf::from(args.next().ok_or("--script needs a file")?)), 
+            "--trace" => trace = Some(PathBuf::from(args.next().ok_or("--trace needs a file")?)), 
+            "--profile" => profile = true, 
+            "--wav" => wav = Some(PathBuf::from(args.next().ok_or("--wav needs a file")?)), 
+            "--no-vf-reset" => changes.push(|quirks| quirks.vf_reset = false), 
+            "--no-memory" => changes.push(|quirks| quirks.memory = false), 
+            "--no-clipping" => changes.push(|quirks| quirks.clipping = false), 
+            "--shifting" => changes.push(|quirks| quirks.shifting = true), 
+            "--jumping" => changes.push(|quirks| quirks.jumping = true), 
+            flag if flag.starts_with('-') => return Err(format!("unknown argument `{flag}`")), 
+            _ if rom.is_some() => return Err(format!("more than one ROM given: `{arg}`")), 
+            _ => rom = Some(PathBuf::from(arg)), 
         }
+    }
 
-        self.stack_pointer -= 1; // to earlier position 
-        let call_addr = self.stack[self.stack_pointer]; 
-        self.pc = call_addr as usize; 
+    let rom = rom.ok_or("no ROM given")?; 
+    let mut quirks = preset; 
+    for change in changes {
+        change(&mut quirks); 
     }
+    Ok(Args {
+        rom, 
+        hz, 
+        quirks, 
+        debug, 
+        keymap, 
+        script, 
+        trace, 
+        profile, 
+        wav, 
+    })
 }
 
-fn main() {
-    let mut cpu = CPU {
-        registers: [0; 16], 
-        memory: [0; 4096], 
-        pc: 0, 
-        stack: [0; 16], 
-        stack_pointer: 0, 
+// run `cpu` in real time, drawing the display after every frame, with 
+// the keys from `script` or else typed on the keyboard, and every 
+// instruction shown to the tracer and the profiler, and the sound written 
+// to the .wav file; fails when the terminal or a file is gone or the 
+// program faults 
+fn show(
+    cpu: &mut CPU, 
+    keymap: &Keymap, 
+    script: Option<&Script>, 
+    mut tracer: Option<&mut Tracer<BufWriter<File>>>, 
+    mut profiler: Option<&mut Profiler>, 
+    mut wav: Option<&mut WavFile<BufWriter<File>>>, 
+) -> Result<(), Box<dyn Error>> {
+    let frame_time = Duration::from_secs(1) / TIMER_HZ; 
+    let mut terminal = Terminal::new(io::stdout())?; 
+    let mut keyboard = match script {
+        Some(_) => None, 
+        None => Some(Keyboard::new()?), 
     }; 
+    let mut held = Held::new(); 
+    let mut beeper = Beeper::new(cpu); 
 
-    cpu.registers[0] = 1;   // x
-    cpu.registers[1] = 2;   // y 
-
-    let mem = &mut cpu.memory; 
-    mem[0x000] = 0x21; mem[0x001] = 0x00; // opcode 0x2100: call 0x100 
-    mem[0x002] = 0x21; mem[0x003] = 0x00; // opcode 0x2100
-    mem[0x004] = 0x00; mem[0x005] = 0x00; // opcode 0x0000: halt   
-
-    mem[0x100] = 0x80; mem[0x101] = 0x14; // opcode 0x8014: r0+r1  
-    mem[0x102] = 0x80; mem[0x103] = 0x14; // opcode 0x8014 
-    mem[0x104] = 0x00; mem[0x105] = 0xEE; // opcode 0x00EE: ret  
+    let mut frame = 0; 
+    loop {
+        let started = Instant::now(); 
+        let keys = match &mut keyboard {
+            Some(keyboard) => {
+                let typed = keyboard.poll(); 
+                if typed.contains(&CTRL_C) || typed.contains(&ESC) {
+                    return Ok(()); 
+                }
+                for key in String::from_utf8_lossy(&typed).chars().filter_map(|c| keymap.key(c)) {
+                    held.press(key); 
+                }
+                held.next_frame()
+            }
+            None => script.map_or(0, |script| script.keys(frame)), 
+        }; 
+        cpu.set_keypad(keys); 
 
-    cpu.run(); 
+        let mut traced = Ok(()); 
+        let running = cpu.frame_with(|cpu, executed| {
+            if let Some(profiler) = profiler.as_deref_mut() {
+                profiler.record(executed); 
+            }
+            if let (Some(tracer), Ok(())) = (tracer.as_deref_mut(), &traced) {
+                traced = tracer.record(cpu, executed); 
+            }
+        }); 
+        terminal.draw(cpu.display())?; 
+        traced?; 
+        if let Some(wav) = wav.as_deref_mut() {
+            beeper.play(cpu, wav)?; 
+        }
+        if !running? {
+            return Ok(()); 
+        }
+        frame += 1; 
+        thread::sleep(frame_time.saturating_sub(started.elapsed())); 
+    }
 }
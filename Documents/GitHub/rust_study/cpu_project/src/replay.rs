@@ -0,0 +1,192 @@
+/*
+    recording a run, and playing it back exactly 
+        the machine is deterministic: the random numbers come from a seeded 
+        generator and the timers run on emulated time, so the only thing 
+        from the outside is the keypad; a Recording is the save state the 
+        run started from, plus the keys held down during every frame 
+
+        record with Recording::new and then frame instead of CPU::frame; 
+        replay starts a new CPU from the saved state and runs the same 
+        frames with the same keys, which ends in the same state, so a test 
+        can record a run once and compare against it ever after 
+
+    the file format, version 1, numbers big-endian: 
+        "CH8R"          magic 
+        1               version (u8) 
+        length          u32, the size of the save state 
+        state           the save state the run starts from (state.rs) 
+        frames          u32, the number of frames 
+        keys            u16 per frame, bit k set while key k is held down 
+*/
+
+use std::fmt; 
+
+use crate::{
+    cpu::{CpuError, CPU}, 
+    state::{State, StateError}, 
+}; 
+
+const MAGIC: &[u8; 4] = b"CH8R"; 
+pub const VERSION: u8 = 1; 
+
+pub struct Recording {
+    start: State, 
+    keys: Vec<u16>, 
+}
+
+#[derive(Debug, PartialEq)]
+pub enum RecordingError {
+    BadMagic, 
+    UnsupportedVersion(u8), 
+    WrongSize, 
+    State(StateError), 
+}
+
+impl fmt::Display for RecordingError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            RecordingError::BadMagic => write!(f, "not a recording"), 
+            RecordingError::UnsupportedVersion(version) => {
+                write!(f, "recording version {version} is not supported, only {VERSION}")
+            }
+            RecordingError::WrongSize => write!(f, "the recording is truncated or corrupt"), 
+            RecordingError::State(e) => write!(f, "the recording starts from a bad state: {e}"), 
+        }
+    }
+}
+
+impl std::error::Error for RecordingError {}
+
+impl Recording {
+    /// Start recording `cpu` from where it is now. 
+    pub fn new(cpu: &CPU) -> Recording {
+        Recording {
+            start: cpu.save_state(), 
+            keys: Vec::new(), 
+        }
+    }
+
+    /// Run a frame of `cpu` with `keys` held down, and remember them. 
+    pub fn frame(&mut self, cpu: &mut CPU, keys: u16) -> Result<bool, CpuError> {
+        self.keys.push(keys); 
+        cpu.set_keypad(keys); 
+        cpu.frame()
+    }
+
+    /// The number of frames recorded. 
+    pub fn len(&self) -> usize {
+        self.keys.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.keys.is_empty()
+    }
+
+    /// Run the recorded frames again on a new CPU, and hand it back. 
+    pub fn replay(&self) -> Result<CPU, CpuError> {
+        let mut cpu = CPU::new(); 
+        cpu.load_state(&self.start); 
+        for &keys in &self.keys {
+            cpu.set_keypad(keys); 
+            if !cpu.frame()? {
+                break; 
+            }
+        }
+        Ok(cpu)
+    }
+
+    pub fn to_bytes(&self) -> Vec<u8> {
+        let state = self.start.to_bytes(); 
+        let mut bytes = Vec::with_capacity(state.len() + self.keys.len() * 2 + 13); 
+        bytes.extend_from_slice(MAGIC); 
+        bytes.push(VERSION); 
+        bytes.extend_from_slice(&(state.len() as u32).to_be_bytes()); 
+        bytes.extend_from_slice(&state); 
+        bytes.extend_from_slice(&(self.keys.len() as u32).to_be_bytes()); 
+        for keys in &self.keys {
+            bytes.extend_from_slice(&keys.to_be_bytes()); 
+        }
+        bytes
+    }
+
+    pub fn from_bytes(bytes: &[u8]) -> Result<Recording, RecordingError> {
+        let (magic, rest) = split(bytes, 4)?; 
+        if magic != MAGIC {
+            return Err(RecordingError::BadMagic); 
+        }
+        let (version, rest) = split(rest, 1)?; 
+        if version[0] != VERSION {
+            return Err(RecordingError::UnsupportedVersion(version[0])); 
+        }
+
+        let (length, rest) = split(rest, 4)?; 
+        let (state, rest) = split(rest, u32::from_be_bytes(length.try_into().unwrap()) as usize)?; 
+        let start = State::from_bytes(state).map_err(RecordingError::State)?; 
+
+        let (frames, rest) = split(rest, 4)?; 
+        let frames = u32::from_be_bytes(frames.try_into().unwrap()) as usize; 
+        if rest.len() != frames * 2 {
+            return Err(RecordingError::WrongSize); 
+        }
+        let keys = rest
+            .chunks_exact(2)
+            .map(|pair| u16::from_be_bytes([pair[0], pair[1]]))
+            .collect(); 
+        Ok(Recording { start, keys })
+    }
+}
+
+fn split(bytes: &[u8], n: usize) -> Result<(&[u8], &[u8]), RecordingError> {
+    if bytes.len() < n {
+        return Err(RecordingError::WrongSize); 
+    }
+    Ok(bytes.split_at(n))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*; 
+
+    // a loop counting in V1 that, while the key 5 is held, draws random 
+    // bytes into V4 and adds them up in V2 
+    const PROGRAM: [u16; 6] = [0x7101, 0x6305, 0xE3A1, 0xC4FF, 0x8244, 0x1200]; 
+
+    fn cpu() -> CPU {
+        let rom: Vec<u8> = PROGRAM.iter().flat_map(|opcode| opcode.to_be_bytes()).collect(); 
+        let mut cpu = CPU::new(); 
+        cpu.load_rom(&rom).unwrap(); 
+        cpu
+    }
+
+    #[test]
+    fn replay_ends_in_the_same_state() {
+        let mut cpu = cpu(); 
+        let mut recording = Recording::new(&cpu); 
+        for frame in 0..30 {
+            let keys = if frame % 7 < 3 { 1 << 5 } else { 0 }; 
+            assert!(recording.frame(&mut cpu, keys).unwrap()); 
+        }
+        assert_eq!(recording.len(), 30); 
+
+        let replayed = recording.replay().unwrap(); 
+        assert!(replayed.save_state() == cpu.save_state()); 
+
+        let bytes = recording.to_bytes(); 
+        let loaded = Recording::from_bytes(&bytes).unwrap(); 
+        assert!(loaded.replay().unwrap().save_state() == cpu.save_state()); 
+    }
+
+    #[test]
+    fn bad_recordings_are_refused() {
+        let mut cpu = cpu(); 
+        let mut recording = Recording::new(&cpu); 
+        recording.frame(&mut cpu, 0).unwrap(); 
+        let bytes = recording.to_bytes(); 
+
+        assert_eq!(Recording::from_bytes(b"CH8S").err(), Some(RecordingError::BadMagic)); 
+        assert_eq!(Recording::from_bytes(&bytes[..bytes.len() - 1]).err(), Some(RecordingError::WrongSize)); 
+        let mut state = bytes.clone(); 
+        state[9] = b'X'; 
+        assert_eq!(Recording::from_bytes(&state).err(), Some(RecordingError::State(StateError::BadMagic))); 
+    }
+}
@@ -0,0 +1,313 @@
+/*
+    save states: the whole machine as bytes, and back 
+        CPU::save_state takes a State, CPU::load_state puts one back; 
+        to_bytes and from_bytes convert it to and from the binary format, 
+        which starts with a magic number and a version, so an old file is 
+        recognized (and refused) once the format changes 
+
+    format version 2, all numbers big-endian: 
+        "CH8S"          magic 
+        2               version (u8) 
+        pc, I           u16 each 
+        V0-VF           16 bytes 
+        stack           16 u16, then the stack pointer (u8) 
+        delay, sound    the timers (u8 each) 
+        keypad          u16, bit k set while key k is held down 
+        key wait        u8, the key pressed while Fx0A waits for it to be 
+                        released, or FF (version 2) 
+        rng             u32, the state of the random number generator 
+        cycles          u64, instructions executed since the reset 
+        clock           u32, instructions per second 
+        quirks          u8: vf_reset, memory, clipping, shifting, jumping, 
+                        super_chip from bit 0 up 
+        RPL flags       8 bytes 
+        memory          4096 bytes 
+        hires           u8, 0 or 1 
+        framebuffer     the pixels row after row, 8 to a byte, most 
+                        significant bit first (256 or 1024 bytes) 
+
+    loading a state restores everything, the clock speed and the quirks 
+        included, so that the machine carries on exactly where it was 
+*/
+
+use std::fmt; 
+
+use crate::{
+    cpu::{Quirks, TIMER_HZ}, 
+    display::Display, 
+}; 
+
+const MAGIC: &[u8; 4] = b"CH8S"; 
+pub const VERSION: u8 = 2; 
+
+/// A snapshot of a CPU, see `CPU::save_state`. 
+#[derive(Clone, PartialEq)]
+pub struct State {
+    pub(crate) pc: u16, 
+    pub(crate) i: u16, 
+    pub(crate) registers: [u8; 16], 
+    pub(crate) stack: [u16; 16], 
+    pub(crate) stack_pointer: u8, 
+    pub(crate) delay_timer: u8, 
+    pub(crate) sound_timer: u8, 
+    pub(crate) keypad: u16, 
+    pub(crate) key_wait: Option<u8>, 
+    pub(crate) rng: u32, 
+    pub(crate) cycles: u64, 
+    pub(crate) clock_hz: u32, 
+    pub(crate) quirks: Quirks, 
+    pub(crate) rpl: [u8; 8], 
+    pub(crate) memory: Box<[u8; 4096]>, 
+    pub(crate) display: Display, 
+}
+
+#[derive(Debug, PartialEq)]
+pub enum StateError {
+    /// Not a save state at all. 
+    BadMagic, 
+    /// A save state of another version of the format. 
+    UnsupportedVersion(u8), 
+    /// The data ends too early, or goes on after the end. 
+    WrongSize, 
+    /// A value no machine can have, like a PC past the memory. 
+    Invalid(&'static str), 
+}
+
+impl fmt::Display for StateError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            StateError::BadMagic => write!(f, "not a save state"), 
+            StateError::UnsupportedVersion(version) => {
+                write!(f, "save state version {version} is not supported, only {VERSION}")
+            }
+            StateError::WrongSize => write!(f, "the save state is truncated or corrupt"), 
+            StateError::Invalid(what) => write!(f, "invalid save state: {what}"), 
+        }
+    }
+}
+
+impl std::error::Error for StateError {}
+
+impl State {
+    pub fn to_bytes(&self) -> Vec<u8> {
+        let mut bytes = Vec::with_capacity(4096 + 256); 
+        bytes.extend_from_slice(MAGIC); 
+        bytes.push(VERSION); 
+        bytes.extend_from_slice(&self.pc.to_be_bytes()); 
+        bytes.extend_from_slice(&self.i.to_be_bytes()); 
+        bytes.extend_from_slice(&self.registers); 
+        for addr in self.stack {
+            bytes.extend_from_slice(&addr.to_be_bytes()); 
+        }
+        bytes.push(self.stack_pointer); 
+        bytes.push(self.delay_timer); 
+        bytes.push(self.sound_timer); 
+        bytes.extend_from_slice(&self.keypad.to_be_bytes()); 
+        bytes.push(self.key_wait.unwrap_or(0xFF)); 
+        bytes.extend_from_slice(&self.rng.to_be_bytes()); 
+        bytes.extend_from_slice(&self.cycles.to_be_bytes()); 
+        bytes.extend_from_slice(&self.clock_hz.to_be_bytes()); 
+        bytes.push(quirk_bits(&self.quirks)); 
+        bytes.extend_from_slice(&self.rpl); 
+        bytes.extend_from_slice(&self.memory[..]); 
+
+        let display = &self.display; 
+        bytes.push(display.is_hires() as u8); 
+        let pixels: Vec<bool> = (0..display.height())
+            .flat_map(|y| (0..display.width()).map(move |x| display.get(x, y)))
+            .collect(); 
+        for eight in pixels.chunks(8) {
+            bytes.push(eight.iter().fold(0, |byte, &on| byte << 1 | on as u8)); 
+        }
+        bytes
+    }
+
+    pub fn from_bytes(bytes: &[u8]) -> Result<State, StateError> {
+        let mut reader = Reader { bytes }; 
+        if reader.take(4)? != MAGIC {
+            return Err(StateError::BadMagic); 
+        }
+        let version = reader.u8()?; 
+        if version != VERSION {
+            return Err(StateError::UnsupportedVersion(version)); 
+        }
+
+        let pc = reader.u16()?; 
+        let i = reader.u16()?; 
+        let registers = reader.array()?; 
+        let mut stack = [0; 16]; 
+        for addr in &mut stack {
+            *addr = reader.u16()?; 
+        }
+        let stack_pointer = reader.u8()?; 
+        let delay_timer = reader.u8()?; 
+        let sound_timer = reader.u8()?; 
+        let keypad = reader.u16()?; 
+        let key_wait = match reader.u8()? {
+            0xFF => None, 
+            key @ 0..=0xF => Some(key), 
+            _ => return Err(StateError::Invalid("the key waited for is not 0-F")), 
+        }; 
+        let rng = u32::from_be_bytes(reader.array()?); 
+        let cycles = u64::from_be_bytes(reader.array()?); 
+        let clock_hz = u32::from_be_bytes(reader.array()?); 
+        let quirks = quirks_from_bits(reader.u8()?); 
+        let rpl = reader.array()?; 
+        let memory = Box::new(reader.array()?); 
+
+        let mut display = Display::new(); 
+        display.set_hires(match reader.u8()? {
+            0 => false, 
+            1 => true, 
+            _ => return Err(StateError::Invalid("the resolution is neither lo-res nor hi-res")), 
+        }); 
+        let (width, height) = (display.width(), display.height()); 
+        let pixels = reader.take(width * height / 8)?; 
+        for y in 0..height {
+            for x in 0..width {
+                let n = y * width + x; 
+                display.set(x, y, pixels[n / 8] & (0x80 >> (n % 8)) != 0); 
+            }
+        }
+
+        if !reader.bytes.is_empty() {
+            return Err(StateError::WrongSize); 
+        }
+        if pc as usize >= memory.len() {
+            return Err(StateError::Invalid("the PC is past the end of memory")); 
+        }
+        if stack_pointer as usize > stack.len() {
+            return Err(StateError::Invalid("the stack pointer is past the stack")); 
+        }
+        if clock_hz < TIMER_HZ {
+            return Err(StateError::Invalid("the clock runs slower than the timers")); 
+        }
+
+        Ok(State {
+            pc, 
+            i, 
+            registers, 
+            stack, 
+            stack_pointer, 
+            delay_timer, 
+            sound_timer, 
+            keypad, 
+            key_wait, 
+            rng, 
+            cycles, 
+            clock_hz, 
+            quirks, 
+            rpl, 
+            memory, 
+            display, 
+        })
+    }
+}
+
+// the bytes not read yet 
+struct Reader<'a> {
+    bytes: &'a [u8], 
+}
+
+impl<'a> Reader<'a> {
+    fn take(&mut self, n: usize) -> Result<&'a [u8], StateError> {
+        if self.bytes.len() < n {
+            return Err(StateError::WrongSize); 
+        }
+        let (taken, rest) = self.bytes.split_at(n); 
+        self.bytes = rest; 
+        Ok(taken)
+    }
+
+    fn array<const N: usize>(&mut self) -> Result<[u8; N], StateError> {
+        Ok(self.take(N)?.try_into().unwrap())
+    }
+
+    fn u8(&mut self) -> Result<u8, StateError> {
+        Ok(self.take(1)?[0])
+    }
+
+    fn u16(&mut self) -> Result<u16, StateError> {
+        Ok(u16::from_be_bytes(self.array()?))
+    }
+}
+
+fn quirk_bits(quirks: &Quirks) -> u8 {
+    [quirks.vf_reset, quirks.memory, quirks.clipping, quirks.shifting, quirks.jumping, quirks.super_chip]
+        .iter()
+        .enumerate()
+        .fold(0, |bits, (n, &on)| bits | (on as u8) << n)
+}
+
+fn quirks_from_bits(bits: u8) -> Quirks {
+    let on = |n: u8| bits & 1 << n != 0; 
+    Quirks {
+        vf_reset: on(0), 
+        memory: on(1), 
+        clipping: on(2), 
+        shifting: on(3), 
+        jumping: on(4), 
+        super_chip: on(5), 
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*; 
+    use crate::cpu::{Quirks, CPU}; 
+
+    // a machine in the middle of something: a call, a sprite, hi-res, timers 
+    fn busy_cpu() -> CPU {
+        let program: [u16; 8] = [0x00FF, 0x2206, 0x0000, 0x6A09, 0xFA29, 0xD015, 0xFA15, 0x00EE]; 
+        let rom: Vec<u8> = program.iter().flat_map(|opcode| opcode.to_be_bytes()).collect(); 
+        let mut cpu = CPU::new(); 
+        cpu.set_quirks(Quirks::SUPER_CHIP); 
+        cpu.load_rom(&rom).unwrap(); 
+        cpu.set_keypad(0b1000_0000_0010_0000); 
+        for _ in 0..6 {
+            cpu.step().unwrap(); 
+        }
+        cpu
+    }
+
+    #[test]
+    fn save_and_restore() {
+        let cpu = busy_cpu(); 
+        let bytes = cpu.save_state().to_bytes(); 
+        assert_eq!(bytes.len(), 4 + 1 + 2 + 2 + 16 + 33 + 2 + 2 + 1 + 4 + 8 + 4 + 1 + 8 + 4096 + 1 + 1024); 
+
+        let mut restored = CPU::new(); 
+        restored.load_state(&State::from_bytes(&bytes).unwrap()); 
+        assert!(restored.save_state() == cpu.save_state()); 
+        assert!(restored.display() == cpu.display() && restored.display().is_hires()); 
+        assert_eq!(restored.stack(), [0x204]); 
+        assert_eq!(restored.keypad(), 0b1000_0000_0010_0000); 
+        assert_eq!(restored.delay_timer(), 9); 
+    }
+
+    #[test]
+    fn a_restored_machine_runs_on_the_same() {
+        let mut cpu = busy_cpu(); 
+        let state = cpu.save_state(); 
+        cpu.run().unwrap(); 
+
+        let mut restored = CPU::new(); 
+        restored.load_state(&state); 
+        restored.run().unwrap(); 
+        assert!(restored.save_state() == cpu.save_state()); 
+    }
+
+    #[test]
+    fn bad_states_are_refused() {
+        let bytes = busy_cpu().save_state().to_bytes(); 
+
+        assert_eq!(State::from_bytes(b"PK\x03\x04").err(), Some(StateError::BadMagic)); 
+        let mut newer = bytes.clone(); 
+        newer[4] = 3; 
+        assert_eq!(State::from_bytes(&newer).err(), Some(StateError::UnsupportedVersion(3))); 
+        assert_eq!(State::from_bytes(&bytes[..bytes.len() - 1]).err(), Some(StateError::WrongSize)); 
+        let mut pc = bytes.clone(); 
+        pc[5] = 0x10; 
+        assert!(matches!(State::from_bytes(&pc), Err(StateError::Invalid(_)))); 
+    }
+}
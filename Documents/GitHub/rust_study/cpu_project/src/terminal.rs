@@ -0,0 +1,119 @@
+/*
+    terminal front end 
+        draws the display with half-block characters inside a box, using 
+        nothing but ANSI escape codes, so a game can be watched over ssh 
+        on a machine without any graphics 
+
+        the screen is cleared once; every frame after that moves the 
+        cursor home and overwrites the previous one, and a frame that did 
+        not change is not written again 
+
+        the cursor is hidden while the emulator runs and shown again when 
+        the Terminal is dropped 
+
+    Keyboard reads the typed keys: it puts the terminal in raw mode with 
+        stty (no line buffering, no echo, Ctrl-C is just a byte) and back 
+        when it is dropped, and a thread reads stdin, so that poll never 
+        waits for a key 
+*/
+
+use std::{
+    io::{self, Read, Write}, 
+    process::{Command, Stdio}, 
+    sync::mpsc::{self, Receiver}, 
+    thread, 
+}; 
+
+use crate::display::Display; 
+
+const CLEAR: &str = "\x1b[2J"; 
+const HOME: &str = "\x1b[H"; 
+const HIDE_CURSOR: &str = "\x1b[?25l"; 
+const SHOW_CURSOR: &str = "\x1b[?25h"; 
+
+pub struct Terminal<W: Write> {
+    out: W, 
+    last: Option<Display>, 
+}
+
+impl<W: Write> Terminal<W> {
+    pub fn new(mut out: W) -> io::Result<Terminal<W>> {
+        write!(out, "{CLEAR}{HIDE_CURSOR}")?; 
+        out.flush()?; 
+        Ok(Terminal { out, last: None })
+    }
+
+    /// Show `display`, unless it is what is already on the terminal. 
+    pub fn draw(&mut self, display: &Display) -> io::Result<()> {
+        if self.last.as_ref() == Some(display) {
+            return Ok(()); 
+        }
+
+        let border = "─".repeat(display.width()); 
+        let mut frame = format!("{HOME}┌{border}┐\r\n"); 
+        for line in display.to_half_blocks().lines() {
+            frame.push_str(&format!("│{line}│\r\n")); 
+        }
+        frame.push_str(&format!("└{border}┘\r\n")); 
+
+        self.out.write_all(frame.as_bytes())?; 
+        self.out.flush()?; 
+        self.last = Some(display.clone()); 
+        Ok(())
+    }
+}
+
+impl<W: Write> Drop for Terminal<W> {
+    fn drop(&mut self) {
+        // nothing to be done if the terminal is gone already 
+        let _ = write!(self.out, "{SHOW_CURSOR}"); 
+        let _ = self.out.flush(); 
+    }
+}
+
+pub struct Keyboard {
+    keys: Receiver<u8>, 
+    // the stty settings to go back to 
+    saved: String, 
+}
+
+impl Keyboard {
+    /// Raw mode on the terminal on stdin; fails when stdin is not a terminal. 
+    pub fn new() -> io::Result<Keyboard> {
+        let saved = stty(&["-g"])?; 
+        stty(&["raw", "-echo"])?; 
+
+        let (sender, keys) = mpsc::channel(); 
+        thread::spawn(move || {
+            for byte in io::stdin().lock().bytes() {
+                let Ok(byte) = byte else { break }; 
+                // stop once the Keyboard is gone 
+                if sender.send(byte).is_err() {
+                    break; 
+                }
+            }
+        }); 
+        Ok(Keyboard { keys, saved: saved.trim().to_string() })
+    }
+
+    /// The bytes typed since the last poll. 
+    pub fn poll(&mut self) -> Vec<u8> {
+        self.keys.try_iter().collect()
+    }
+}
+
+impl Drop for Keyboard {
+    fn drop(&mut self) {
+        let _ = stty(&[&self.saved]); 
+    }
+}
+
+// run stty on the terminal on stdin, and return what it printed 
+fn stty(args: &[&str]) -> io::Result<String> {
+    let output = Command::new("stty").args(args).stdin(Stdio::inherit()).output()?; 
+    if !output.status.success() {
+        let message = String::from_utf8_lossy(&output.stderr).trim().to_string(); 
+        return Err(io::Error::other(format!("stty: {message}"))); 
+    }
+    Ok(String::from_utf8_lossy(&output.stdout).into_owned())
+}
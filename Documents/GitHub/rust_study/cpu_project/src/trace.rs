@@ -0,0 +1,204 @@
+/*
+    watching a program run, for chasing compatibility problems 
+        Tracer writes a line per instruction: its address, the opcode, the 
+        mnemonic, and the registers (and I) it changed 
+            208: 8014  ADD V0, V1            V0 05->0C 
+            20A: A300  LD I, 0x300           I 000->300 
+        only values that changed are shown, so a flag set to what it was 
+        already (VF 00 after an ADD without a carry) does not show up 
+
+    Profiler counts how often every address was executed and how often 
+        every kind of opcode was, and reports both after the run, the 
+        hottest first; an opcode kind is its usual notation, 8xy4, Dxyn, 
+        Fx1E, with the fixed nibbles in hex 
+
+    both are fed from CPU::frame_with (or after CPU::step), and see the 
+        halt at the end as well 
+*/
+
+use std::{
+    collections::HashMap, 
+    fmt::Write as _, 
+    io::{self, Write}, 
+}; 
+
+use crate::{
+    cpu::{Executed, CPU}, 
+    disasm, 
+}; 
+
+pub struct Tracer<W: Write> {
+    out: W, 
+    // the registers and I after the last instruction 
+    registers: [u8; 16], 
+    i: u16, 
+}
+
+#[derive(Default)]
+pub struct Profiler {
+    total: u64, 
+    // executions and the opcode last seen at every address 
+    addresses: HashMap<usize, (u64, u16)>, 
+    kinds: HashMap<String, u64>, 
+}
+
+impl<W: Write> Tracer<W> {
+    /// A tracer for `cpu`, which is about to run. 
+    pub fn new(out: W, cpu: &CPU) -> Tracer<W> {
+        Tracer {
+            out, 
+            registers: *cpu.registers(), 
+            i: cpu.index(), 
+        }
+    }
+
+    /// Write the line for `executed`, which left `cpu` as it is now. 
+    pub fn record(&mut self, cpu: &CPU, executed: &Executed) -> io::Result<()> {
+        let mut changes = Vec::new(); 
+        for (n, (&old, &new)) in self.registers.iter().zip(cpu.registers()).enumerate() {
+            if old != new {
+                changes.push(format!("V{n:X} {old:02X}->{new:02X}")); 
+            }
+        }
+        if self.i != cpu.index() {
+            changes.push(format!("I {:03X}->{:03X}", self.i, cpu.index())); 
+        }
+        self.registers = *cpu.registers(); 
+        self.i = cpu.index(); 
+
+        let mnemonic = disasm::disassemble(executed.opcode); 
+        let line = format!("{:03X}: {:04X}  {mnemonic:<18}  {}", executed.pc, executed.opcode, changes.join(" ")); 
+        writeln!(self.out, "{}", line.trim_end())
+    }
+
+    pub fn into_inner(self) -> W {
+        self.out
+    }
+}
+
+impl Profiler {
+    pub fn new() -> Profiler {
+        Profiler::default()
+    }
+
+    pub fn record(&mut self, executed: &Executed) {
+        self.total += 1; 
+        let entry = self.addresses.entry(executed.pc).or_insert((0, executed.opcode)); 
+        *entry = (entry.0 + 1, executed.opcode); 
+        *self.kinds.entry(kind(executed.opcode)).or_insert(0) += 1; 
+    }
+
+    /// The number of instructions seen. 
+    pub fn total(&self) -> u64 {
+        self.total
+    }
+
+    /// The `n` most executed addresses with their counts, the hottest first. 
+    pub fn hot_addresses(&self, n: usize) -> Vec<(usize, u64)> {
+        let mut hot: Vec<(usize, u64)> = self.addresses.iter().map(|(&addr, &(count, _))| (addr, count)).collect(); 
+        hot.sort_by(|a, b| b.1.cmp(&a.1).then(a.0.cmp(&b.0))); 
+        hot.truncate(n); 
+        hot
+    }
+
+    /// How often every kind of opcode ran, the most frequent first. 
+    pub fn histogram(&self) -> Vec<(&str, u64)> {
+        let mut kinds: Vec<(&str, u64)> = self.kinds.iter().map(|(kind, &count)| (kind.as_str(), count)).collect(); 
+        kinds.sort_by(|a, b| b.1.cmp(&a.1).then(a.0.cmp(b.0))); 
+        kinds
+    }
+
+    /// The `top` hot addresses and the opcode histogram, as text. 
+    pub fn report(&self, top: usize) -> String {
+        let percent = |count: u64| count as f64 * 100.0 / self.total.max(1) as f64; 
+        let mut text = format!("{} instructions\n", self.total); 
+
+        text.push_str("hot addresses:\n"); 
+        for (addr, count) in self.hot_addresses(top) {
+            let opcode = self.addresses[&addr].1; 
+            let mnemonic = disasm::disassemble(opcode); 
+            let _ = writeln!(text, "  {addr:03X}  {count:>10}  {:5.1}%  {mnemonic}", percent(count)); 
+        }
+
+        text.push_str("opcodes:\n"); 
+        let histogram = self.histogram(); 
+        let most = histogram.first().map_or(1, |&(_, count)| count); 
+        for (kind, count) in histogram {
+            let bar = "#".repeat((count * 40).div_ceil(most) as usize); 
+            let _ = writeln!(text, "  {kind}  {count:>10}  {:5.1}%  {bar}", percent(count)); 
+        }
+        text
+    }
+}
+
+// the usual notation for the kind of `opcode`: 6xkk, 8xy4, Fx1E 
+fn kind(opcode: u16) -> String {
+    let c = opcode >> 12; 
+    match c {
+        0x0 => match opcode {
+            0x0000 | 0x00E0 | 0x00EE | 0x00FB..=0x00FF => format!("{opcode:04X}"), 
+            _ if opcode & 0xFFF0 == 0x00C0 => String::from("00Cn"), 
+            _ => String::from("0nnn"), 
+        }, 
+        0x1 | 0x2 | 0xA | 0xB => format!("{c:X}nnn"), 
+        0x3 | 0x4 | 0x6 | 0x7 | 0xC => format!("{c:X}xkk"), 
+        0x5 | 0x8 | 0x9 => format!("{c:X}xy{:X}", opcode & 0xF), 
+        0xD => String::from("Dxyn"), 
+        _ => format!("{c:X}x{:02X}", opcode & 0xFF), 
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*; 
+
+    // count V0 up to 3 in a loop, then halt 
+    fn cpu() -> CPU {
+        let program: [u16; 4] = [0x7001, 0x3003, 0x1200, 0xA300]; 
+        let rom: Vec<u8> = program.iter().flat_map(|opcode| opcode.to_be_bytes()).collect(); 
+        let mut cpu = CPU::new(); 
+        cpu.load_rom(&rom).unwrap(); 
+        cpu
+    }
+
+    #[test]
+    fn trace_shows_the_changes() {
+        let mut cpu = cpu(); 
+        let mut tracer = Tracer::new(Vec::new(), &cpu); 
+        while cpu.frame_with(|cpu, executed| tracer.record(cpu, executed).unwrap()).unwrap() {}
+
+        let trace = String::from_utf8(tracer.into_inner()).unwrap(); 
+        let lines: Vec<&str> = trace.lines().collect(); 
+        assert_eq!(lines[0], "200: 7001  ADD V0, 0x01        V0 00->01"); 
+        assert_eq!(lines[1], "202: 3003  SE V0, 0x03"); 
+        assert_eq!(lines[2], "204: 1200  JP 0x200"); 
+        assert_eq!(lines[lines.len() - 2], "206: A300  LD I, 0x300         I 000->300"); 
+        assert_eq!(lines[lines.len() - 1], "208: 0000  HALT"); 
+        assert_eq!(lines.len(), 3 * 3 - 1 + 2); 
+    }
+
+    #[test]
+    fn profile() {
+        let mut cpu = cpu(); 
+        let mut profiler = Profiler::new(); 
+        cpu.frame_with(|_, executed| profiler.record(executed)).unwrap(); 
+
+        assert_eq!(profiler.total(), 10); 
+        assert_eq!(profiler.hot_addresses(2), [(0x200, 3), (0x202, 3)]); 
+        assert_eq!(profiler.histogram(), [("3xkk", 3), ("7xkk", 3), ("1nnn", 2), ("0000", 1), ("Annn", 1)]); 
+
+        let report = profiler.report(1); 
+        assert!(report.starts_with("10 instructions\nhot addresses:\n  200           3   30.0%  ADD V0, 0x01\nopcodes:\n"), "{report}"); 
+        assert!(report.contains("  1nnn           2   20.0%  ###########################\n"), "{report}"); 
+    }
+
+    #[test]
+    fn kinds() {
+        assert_eq!(kind(0x00E0), "00E0"); 
+        assert_eq!(kind(0x00C4), "00Cn"); 
+        assert_eq!(kind(0x0123), "0nnn"); 
+        assert_eq!(kind(0x8AB4), "8xy4"); 
+        assert_eq!(kind(0xD125), "Dxyn"); 
+        assert_eq!(kind(0xF31E), "Fx1E"); 
+    }
+}
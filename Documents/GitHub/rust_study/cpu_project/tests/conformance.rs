@@ -0,0 +1,199 @@
+/*
+    conformance: the test ROMs in roms/tests, run to the end under the 
+        presets, and the screens they leave compared with the screens they 
+        should leave 
+        logo        sprites and jumps, the same under every preset 
+        flags       the 8xy_ arithmetic and VF, ticks all the way 
+        quirks      a digit for every quirk setting, so one per preset 
+        schip       the SUPER-CHIP extensions, hi-res 
+
+    the expected screens are drawn here, pixel by pixel, from what the 
+        comments of every ROM say it shows and from the COSMAC VIP font; 
+        none of them comes from running the emulator, so a mistake in the 
+        CPU cannot slip into what it is checked against 
+
+    the ROMs are this repo's own, not the well-known public suites 
+        (Timendus' chip8-test-suite, corax+, flags, quirks) the request 
+        asked for: no copy of them was at hand to vendor, and ROM bytes 
+        typed in from memory would be worse than none; that part is open 
+        until someone checks the real files in from upstream, each with 
+        its licence, as roms/tests/<name>.ch8 plus an expected screen here 
+
+    the sources (the .asm files next to them) are checked to assemble to the 
+        ROMs, so that the two never drift apart 
+*/
+
+use cpu::{
+    asm, 
+    cpu::{Quirks, CPU}, 
+    display::Display, 
+}; 
+
+// a test ROM never needs this long 
+const MAX_FRAMES: usize = 600; 
+
+// the hex digits of the COSMAC VIP, 4x5 in the high nibbles 
+const FONT: [[u8; 5]; 16] = [
+    [0xF0, 0x90, 0x90, 0x90, 0xF0], 
+    [0x20, 0x60, 0x20, 0x20, 0x70], 
+    [0xF0, 0x10, 0xF0, 0x80, 0xF0], 
+    [0xF0, 0x10, 0xF0, 0x10, 0xF0], 
+    [0x90, 0x90, 0xF0, 0x10, 0x10], 
+    [0xF0, 0x80, 0xF0, 0x10, 0xF0], 
+    [0xF0, 0x80, 0xF0, 0x90, 0xF0], 
+    [0xF0, 0x10, 0x20, 0x40, 0x40], 
+    [0xF0, 0x90, 0xF0, 0x90, 0xF0], 
+    [0xF0, 0x90, 0xF0, 0x10, 0xF0], 
+    [0xF0, 0x90, 0xF0, 0x90, 0x90], 
+    [0xE0, 0x90, 0xE0, 0x90, 0xE0], 
+    [0xF0, 0x80, 0x80, 0x80, 0xF0], 
+    [0xE0, 0x90, 0x90, 0x90, 0xE0], 
+    [0xF0, 0x80, 0xF0, 0x80, 0xF0], 
+    [0xF0, 0x80, 0xF0, 0x80, 0x80], 
+]; 
+
+// the letters of logo.asm, C H I P - 8 
+const LETTERS: [[u8; 8]; 6] = [
+    [0x7C, 0xC6, 0xC0, 0xC0, 0xC0, 0xC0, 0xC6, 0x7C], 
+    [0xC6, 0xC6, 0xC6, 0xFE, 0xC6, 0xC6, 0xC6, 0xC6], 
+    [0x7E, 0x18, 0x18, 0x18, 0x18, 0x18, 0x18, 0x7E], 
+    [0xFC, 0xC6, 0xC6, 0xFC, 0xC0, 0xC0, 0xC0, 0xC0], 
+    [0x00, 0x00, 0x00, 0x7C, 0x7C, 0x00, 0x00, 0x00], 
+    [0x7C, 0xC6, 0xC6, 0x7C, 0xC6, 0xC6, 0xC6, 0x7C], 
+]; 
+
+// the tick of flags.asm 
+const TICK: [u8; 5] = [0x08, 0x10, 0xA0, 0x40, 0x00]; 
+
+// the SUPER-CHIP big digits are the interpreter's own design, not a 
+// standard one; Fx30 has to point at them, wherever they are 
+const BIG_FONT_ADDR: usize = 0x0A0; 
+
+// run `rom` until it halts, and return its screen 
+fn run(rom: &[u8], quirks: Quirks) -> Display {
+    let mut cpu = CPU::new(); 
+    cpu.set_quirks(quirks); 
+    cpu.load_rom(rom).unwrap(); 
+    for _ in 0..MAX_FRAMES {
+        if !cpu.frame().unwrap() {
+            return cpu.display().clone(); 
+        }
+    }
+    panic!("still running after {MAX_FRAMES} frames"); 
+}
+
+fn check(name: &str, rom: &[u8], quirks: Quirks, expected: &Display) {
+    let screen = run(rom, quirks); 
+    assert!(
+        screen == *expected, 
+        "{name} under {quirks:?} left the screen\n{}\ninstead of\n{}", 
+        screen.to_half_blocks(), 
+        expected.to_half_blocks()
+    ); 
+}
+
+fn blank(hires: bool) -> Display {
+    let mut display = Display::new(); 
+    display.set_hires(hires); 
+    display
+}
+
+// light the pixels of the sprite `rows` at (x, y) 
+fn put(display: &mut Display, x: usize, y: usize, rows: &[u8]) {
+    for (dy, row) in rows.iter().enumerate() {
+        for dx in (0..8).filter(|dx| row & 0x80 >> dx != 0) {
+            display.set(x + dx, y + dy, true); 
+        }
+    }
+}
+
+#[test]
+fn logo() {
+    // the letters from (8, 12) on, and a line of three bytes at 22 
+    let mut expected = blank(false); 
+    for (n, letter) in LETTERS.iter().enumerate() {
+        put(&mut expected, 8 + 8 * n, 12, letter); 
+    }
+    for x in [8, 32, 56] {
+        put(&mut expected, x, 22, &[0xFF]); 
+    }
+
+    let rom = include_bytes!("../roms/tests/logo.ch8"); 
+    for quirks in [Quirks::COSMAC_VIP, Quirks::CHIP_48, Quirks::SUPER_CHIP] {
+        check("logo", rom, quirks, &expected); 
+    }
+}
+
+#[test]
+fn flags() {
+    // the checks 1 to A, five to a row 12 pixels apart, each with a tick 
+    let mut expected = blank(false); 
+    for (n, digit) in FONT[1..=0xA].iter().enumerate() {
+        let (x, y) = (n % 5 * 12, n / 5 * 6); 
+        put(&mut expected, x, y, digit); 
+        put(&mut expected, x + 5, y, &TICK); 
+    }
+
+    let rom = include_bytes!("../roms/tests/flags.ch8"); 
+    for quirks in [Quirks::COSMAC_VIP, Quirks::CHIP_48, Quirks::SUPER_CHIP] {
+        check("flags", rom, quirks, &expected); 
+    }
+}
+
+// the digits quirks.asm shows, and its bar at (60, 8) clipped or wrapped 
+fn quirks_screen(digits: [usize; 4], wrapped: bool) -> Display {
+    let mut expected = blank(false); 
+    for (n, &digit) in digits.iter().enumerate() {
+        put(&mut expected, 6 * n, 0, &FONT[digit]); 
+    }
+    put(&mut expected, 60, 8, &[0xF0]); 
+    if wrapped {
+        put(&mut expected, 0, 8, &[0xF0]); 
+    }
+    expected
+}
+
+#[test]
+fn quirks() {
+    let rom = include_bytes!("../roms/tests/quirks.ch8"); 
+    check("quirks", rom, Quirks::COSMAC_VIP, &quirks_screen([0x0, 0xC, 0x4, 0xA], false)); 
+    check("quirks", rom, Quirks::CHIP_48, &quirks_screen([0x5, 0x1, 0x0, 0xB], false)); 
+    // one quirk off: only its digit (or the bar) changes 
+    let no_vf_reset = Quirks { vf_reset: false, ..Quirks::COSMAC_VIP }; 
+    check("quirks", rom, no_vf_reset, &quirks_screen([0x5, 0xC, 0x4, 0xA], false)); 
+    let no_clipping = Quirks { clipping: false, ..Quirks::COSMAC_VIP }; 
+    check("quirks", rom, no_clipping, &quirks_screen([0x0, 0xC, 0x4, 0xA], true)); 
+}
+
+#[test]
+fn super_chip() {
+    // the box at (104, 20), the big digits along the top, 10 apart, and 
+    // the small 7 from the RPL flags at (60, 50) 
+    let mut expected = blank(true); 
+    let left = [0xFF, 0x80, 0x80, 0x80, 0x80, 0x80, 0x80, 0x81, 0x81, 0x80, 0x80, 0x80, 0x80, 0x80, 0x80, 0xFF]; 
+    let right = [0xFF, 0x01, 0x01, 0x01, 0x01, 0x01, 0x01, 0x81, 0x81, 0x01, 0x01, 0x01, 0x01, 0x01, 0x01, 0xFF]; 
+    put(&mut expected, 104, 20, &left); 
+    put(&mut expected, 112, 20, &right); 
+    let memory = CPU::new().memory().to_vec(); 
+    for digit in 0..10 {
+        let glyph = &memory[BIG_FONT_ADDR + 10 * digit..][..10]; 
+        put(&mut expected, 10 * digit, 0, glyph); 
+    }
+    put(&mut expected, 60, 50, &FONT[7]); 
+
+    let rom = include_bytes!("../roms/tests/schip.ch8"); 
+    check("schip", rom, Quirks::SUPER_CHIP, &expected); 
+}
+
+#[test]
+fn sources_match_the_roms() {
+    let roms: [(&str, &[u8]); 4] = [
+        (include_str!("../roms/tests/logo.asm"), include_bytes!("../roms/tests/logo.ch8")), 
+        (include_str!("../roms/tests/flags.asm"), include_bytes!("../roms/tests/flags.ch8")), 
+        (include_str!("../roms/tests/quirks.asm"), include_bytes!("../roms/tests/quirks.ch8")), 
+        (include_str!("../roms/tests/schip.asm"), include_bytes!("../roms/tests/schip.ch8")), 
+    ]; 
+    for (source, rom) in roms {
+        assert_eq!(asm::assemble(source).unwrap(), rom); 
+    }
+}
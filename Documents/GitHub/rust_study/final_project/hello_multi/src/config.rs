@@ -0,0 +1,500 @@
+/*
+    server configuration, read from a small INI-like file 
+        # comments start with a hash 
+        mode = threaded 
+        workers = 4 
+        listen = 127.0.0.1:7878 
+        templates = templates 
+        root = public 
+        listing = on 
+
+        [tls] 
+        listen = 127.0.0.1:7879 
+        cert = cert.pem 
+        key = key.pem 
+
+        [proxy /api] 
+        upstream = 127.0.0.1:9000 
+        upstream = 127.0.0.1:9001 
+        health_check = /health 
+        health_interval = 10 
+
+        [limits] 
+        rate = 20 
+        burst = 40 
+        per_ip = 8 
+        max_connections = 512 
+
+        [host blog.example.com] 
+        alias = www.blog.example.com 
+        root = sites/blog 
+        listing = off 
+        default = no 
+        route = /about -> sites/about.html 
+        proxy = /api -> 127.0.0.1:9100, 127.0.0.1:9101 
+
+        settings before the first [section] apply to the whole server; 
+        relative paths are resolved against the directory of the file, 
+        so the server can be started from anywhere; a key may repeat 
+        where a list makes sense (upstream, alias, route, proxy) 
+
+    a [host] has routes and proxies of its own, written `path -> target`: 
+        route       a GET of exactly that path answers with the file 
+        proxy       that path and everything below it go to the upstreams 
+                    (a comma-separated list), as in a [proxy] section but 
+                    checked with a plain TCP connect 
+        it needs a root, a route or a proxy; without a root, what none of 
+        them claims gets a 404 
+
+    every error names the line it was found on, and unknown keys are 
+        errors too, so that a typo does not silently fall back to a default 
+*/
+
+use std::{
+    fmt, fs, io, 
+    path::{Path, PathBuf}, 
+    time::Duration, 
+}; 
+
+use crate::{limit::Limits, server::Mode}; 
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Config {
+    pub mode: Mode, 
+    pub workers: usize, 
+    // plain HTTP listener; None to serve HTTPS only 
+    pub listen: Option<String>, 
+    // directory compiled into Templates at startup 
+    pub templates: PathBuf, 
+    // document root for static files, and whether to list directories 
+    pub root: Option<PathBuf>, 
+    pub listing: bool, 
+    pub tls: Option<TlsSettings>, 
+    pub proxies: Vec<ProxySettings>, 
+    pub limits: Option<Limits>, 
+    pub hosts: Vec<HostSettings>, 
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct TlsSettings {
+    pub listen: String, 
+    // PEM files: the certificate chain and its private key 
+    pub cert: PathBuf, 
+    pub key: PathBuf, 
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct ProxySettings {
+    // reqs for this path (or below it) are forwarded 
+    pub prefix: String, 
+    pub upstreams: Vec<String>, 
+    // GET this path to check health; None for a plain TCP connect 
+    pub health_check: Option<String>, 
+    pub health_interval: Duration, 
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct HostSettings {
+    // the section's name first, then every alias 
+    pub names: Vec<String>, 
+    pub root: Option<PathBuf>, 
+    pub listing: bool, 
+    // serve reqs for hosts nobody else claims 
+    pub default: bool, 
+    pub routes: Vec<RouteSettings>, 
+    pub proxies: Vec<ProxySettings>, 
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct RouteSettings {
+    // a GET of exactly this path is answered with the file 
+    pub path: String, 
+    pub file: PathBuf, 
+}
+
+impl Default for Config {
+    fn default() -> Config {
+        Config {
+            mode: Mode::Threaded, 
+            workers: 4, 
+            listen: Some(String::from("127.0.0.1:7878")), 
+            templates: PathBuf::from("templates"), 
+            root: None, 
+            listing: false, 
+            tls: None, 
+            proxies: Vec::new(), 
+            limits: None, 
+            hosts: Vec::new(), 
+        }
+    }
+}
+
+#[derive(Debug)]
+pub enum ConfigError {
+    Io(io::Error), 
+    // line number (starting at 1) and what is wrong with it 
+    Invalid(usize, String), 
+}
+
+impl fmt::Display for ConfigError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            ConfigError::Io(e) => write!(f, "cannot read config: {e}"), 
+            ConfigError::Invalid(line, message) => write!(f, "config line {line}: {message}"), 
+        }
+    }
+}
+
+impl std::error::Error for ConfigError {}
+
+impl From<io::Error> for ConfigError {
+    fn from(e: io::Error) -> ConfigError {
+        ConfigError::Io(e)
+    }
+}
+
+// one [name arg] block and its key = value lines 
+struct Section {
+    line: usize, 
+    name: String, 
+    arg: Option<String>, 
+    entries: Vec<(usize, String, String)>, 
+}
+
+impl Config {
+    /// Read the configuration file at `path`. 
+    pub fn load(path: impl AsRef<Path>) -> Result<Config, ConfigError> {
+        let path = path.as_ref(); 
+        let text = fs::read_to_string(path)?; 
+        let base = path.parent().unwrap_or(Path::new(".")); 
+        Config::parse(&text, base)
+    }
+
+    /// Parse configuration text; relative paths are resolved against `base`. 
+    pub fn parse(text: &str, base: &Path) -> Result<Config, ConfigError> {
+        let mut config = Config::default(); 
+
+        for section in sections(text)? {
+            match (section.name.as_str(), &section.arg) {
+                ("", _) => config.global(&section, base)?, 
+                ("tls", None) => config.tls = Some(TlsSettings::from_section(&section, base)?), 
+                ("proxy", Some(prefix)) => config
+                    .proxies
+                    .push(ProxySettings::from_section(&section, prefix)?), 
+                ("limits", None) => config.limits = Some(limits_from_section(&section)?), 
+                ("host", Some(name)) => {
+                    let host = HostSettings::from_section(&section, name, base)?; 
+                    if host.default && config.hosts.iter().any(|h| h.default) {
+                        return Err(ConfigError::Invalid(section.line, String::from("only one [host] can be the default"))); 
+                    }
+                    config.hosts.push(host); 
+                }
+                _ => {
+                    let name = &section.name; 
+                    return Err(ConfigError::Invalid(section.line, format!("unknown section [{name}]"))); 
+                }
+            }
+        }
+
+        Ok(config)
+    }
+
+    fn global(&mut self, section: &Section, base: &Path) -> Result<(), ConfigError> {
+        for (line, key, value) in &section.entries {
+            match key.as_str() {
+                "mode" => self.mode = value.parse().map_err(|e| ConfigError::Invalid(*line, e))?, 
+                "workers" => self.workers = parse_number(*line, value)?, 
+                "listen" if value == "none" => self.listen = None, 
+                "listen" => self.listen = Some(value.clone()), 
+                "templates" => self.templates = base.join(value), 
+                "root" => self.root = Some(base.join(value)), 
+                "listing" => self.listing = parse_switch(*line, value)?, 
+                _ => return Err(unknown_key(*line, key)), 
+            }
+        }
+
+        if self.workers == 0 {
+            return Err(ConfigError::Invalid(section.line, String::from("workers must be at least 1"))); 
+        }
+        Ok(())
+    }
+}
+
+impl TlsSettings {
+    fn from_section(section: &Section, base: &Path) -> Result<TlsSettings, ConfigError> {
+        let (mut listen, mut cert, mut key) = (None, None, None); 
+
+        for (line, k, value) in &section.entries {
+            match k.as_str() {
+                "listen" => listen = Some(value.clone()), 
+                "cert" => cert = Some(base.join(value)), 
+                "key" => key = Some(base.join(value)), 
+                _ => return Err(unknown_key(*line, k)), 
+            }
+        }
+
+        let missing = |what: &str| ConfigError::Invalid(section.line, format!("[tls] needs `{what}`")); 
+        Ok(TlsSettings {
+            listen: listen.ok_or_else(|| missing("listen"))?, 
+            cert: cert.ok_or_else(|| missing("cert"))?, 
+            key: key.ok_or_else(|| missing("key"))?, 
+        })
+    }
+}
+
+impl ProxySettings {
+    fn from_section(section: &Section, prefix: &str) -> Result<ProxySettings, ConfigError> {
+        if !prefix.starts_with('/') {
+            return Err(ConfigError::Invalid(section.line, format!("proxy prefix `{prefix}` must start with /"))); 
+        }
+        let mut settings = ProxySettings {
+            prefix: prefix.to_string(), 
+            upstreams: Vec::new(), 
+            health_check: None, 
+            health_interval: Duration::from_secs(10), 
+        }; 
+
+        for (line, key, value) in &section.entries {
+            match key.as_str() {
+                "upstream" => settings.upstreams.push(value.clone()), 
+                "health_check" => settings.health_check = Some(value.clone()), 
+                "health_interval" => settings.health_interval = Duration::from_secs(parse_number(*line, value)?), 
+                _ => return Err(unknown_key(*line, key)), 
+            }
+        }
+
+        if settings.upstreams.is_empty() {
+            return Err(ConfigError::Invalid(section.line, format!("[proxy {prefix}] needs an `upstream`"))); 
+        }
+        Ok(settings)
+    }
+}
+
+impl HostSettings {
+    fn from_section(section: &Section, name: &str, base: &Path) -> Result<HostSettings, ConfigError> {
+        let mut host = HostSettings {
+            names: vec![name.to_string()], 
+            root: None, 
+            listing: false, 
+            default: false, 
+            routes: Vec::new(), 
+            proxies: Vec::new(), 
+        }; 
+
+        for (line, key, value) in &section.entries {
+            match key.as_str() {
+                "alias" => host.names.push(value.clone()), 
+                "root" => host.root = Some(base.join(value)), 
+                "listing" => host.listing = parse_switch(*line, value)?, 
+                "default" => host.default = parse_switch(*line, value)?, 
+                "route" => {
+                    let (path, file) = parse_mapping(*line, value)?; 
+                    host.routes.push(RouteSettings {
+                        path, 
+                        file: base.join(file), 
+                    }); 
+                }
+                "proxy" => {
+                    let (prefix, upstreams) = parse_mapping(*line, value)?; 
+                    let upstreams: Vec<String> = upstreams.split(',').map(|u| u.trim().to_string()).collect(); 
+                    if upstreams.iter().any(String::is_empty) {
+                        return Err(ConfigError::Invalid(*line, format!("empty upstream in `{value}`"))); 
+                    }
+                    // the same prefix again adds upstreams to it 
+                    match host.proxies.iter_mut().find(|p| p.prefix == prefix) {
+                        Some(proxy) => proxy.upstreams.extend(upstreams), 
+                        None => host.proxies.push(ProxySettings {
+                            prefix, 
+                            upstreams, 
+                            health_check: None, 
+                            health_interval: Duration::from_secs(10), 
+                        }), 
+                    }
+                }
+                _ => return Err(unknown_key(*line, key)), 
+            }
+        }
+
+        if host.root.is_none() && host.routes.is_empty() && host.proxies.is_empty() {
+            return Err(ConfigError::Invalid(section.line, format!("[host {name}] needs a `root`, a `route` or a `proxy`"))); 
+        }
+        Ok(host)
+    }
+}
+
+fn limits_from_section(section: &Section) -> Result<Limits, ConfigError> {
+    let mut limits = Limits::default(); 
+
+    for (line, key, value) in &section.entries {
+        match key.as_str() {
+            "rate" => limits.rate = Some(parse_number(*line, value)?), 
+            "burst" => limits.burst = Some(parse_number(*line, value)?), 
+            "per_ip" => limits.per_ip = Some(parse_number(*line, value)?), 
+            "max_connections" => limits.max_connections = Some(parse_number(*line, value)?), 
+            _ => return Err(unknown_key(*line, key)), 
+        }
+    }
+
+    if limits.rate.is_some_and(|rate| rate <= 0.0) {
+        return Err(ConfigError::Invalid(section.line, String::from("[limits] rate must be above 0"))); 
+    }
+    Ok(limits)
+}
+
+// split the text into sections; the first one (name "") holds global keys 
+fn sections(text: &str) -> Result<Vec<Section>, ConfigError> {
+    let mut sections = vec![Section {
+        line: 0, 
+        name: String::new(), 
+        arg: None, 
+        entries: Vec::new(), 
+    }]; 
+
+    for (i, raw) in text.lines().enumerate() {
+        let line = i + 1; 
+        let content = raw.trim(); 
+        if content.is_empty() || content.starts_with('#') {
+            continue; 
+        }
+
+        if let Some(header) = content.strip_prefix('[') {
+            let header = header
+                .strip_suffix(']')
+                .ok_or_else(|| ConfigError::Invalid(line, String::from("unclosed section header")))?
+                .trim(); 
+            let (name, arg) = match header.split_once(char::is_whitespace) {
+                Some((name, arg)) => (name, Some(arg.trim().to_string())), 
+                None => (header, None), 
+            }; 
+            sections.push(Section {
+                line, 
+                name: name.to_string(), 
+                arg, 
+                entries: Vec::new(), 
+            }); 
+            continue; 
+        }
+
+        let (key, value) = content
+            .split_once('=')
+            .ok_or_else(|| ConfigError::Invalid(line, String::from("expected `key = value`")))?; 
+        let current = sections.last_mut().unwrap(); 
+        current
+            .entries
+            .push((line, key.trim().to_string(), value.trim().to_string())); 
+    }
+
+    Ok(sections)
+}
+
+fn parse_number<T: std::str::FromStr>(line: usize, value: &str) -> Result<T, ConfigError> {
+    value
+        .parse()
+        .map_err(|_| ConfigError::Invalid(line, format!("`{value}` is not a number")))
+}
+
+// `/path -> target`, the path absolute and the target not empty 
+fn parse_mapping(line: usize, value: &str) -> Result<(String, String), ConfigError> {
+    let invalid = || ConfigError::Invalid(line, format!("expected `/path -> target`, found `{value}`")); 
+    let (path, target) = value.split_once("->").ok_or_else(invalid)?; 
+    let (path, target) = (path.trim(), target.trim()); 
+    if !path.starts_with('/') || target.is_empty() {
+        return Err(invalid()); 
+    }
+    Ok((path.to_string(), target.to_string()))
+}
+
+fn parse_switch(line: usize, value: &str) -> Result<bool, ConfigError> {
+    match value {
+        "on" | "true" | "yes" => Ok(true), 
+        "off" | "false" | "no" => Ok(false), 
+        _ => Err(ConfigError::Invalid(line, format!("`{value}` is not on or off"))), 
+    }
+}
+
+fn unknown_key(line: usize, key: &str) -> ConfigError {
+    ConfigError::Invalid(line, format!("unknown key `{key}`"))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*; 
+
+    #[test]
+    fn reads_globals_and_tls() {
+        let text = "# test\nmode = event\nworkers = 2\n\n[tls]\nlisten = 0.0.0.0:8443\ncert = c.pem\nkey = /etc/k.pem\n"; 
+        let config = Config::parse(text, Path::new("/srv")).unwrap(); 
+
+        assert_eq!(config.mode, Mode::Event); 
+        assert_eq!(config.workers, 2); 
+        assert_eq!(config.listen.as_deref(), Some("127.0.0.1:7878")); 
+        let tls = config.tls.unwrap(); 
+        assert_eq!(tls.cert, PathBuf::from("/srv/c.pem")); 
+        assert_eq!(tls.key, PathBuf::from("/etc/k.pem")); 
+    }
+
+    #[test]
+    fn collects_proxy_upstreams() {
+        let text = "[proxy /api]\nupstream = a:1\nupstream = b:2\nhealth_check = /up\n"; 
+        let config = Config::parse(text, Path::new(".")).unwrap(); 
+
+        let proxy = &config.proxies[0]; 
+        assert_eq!(proxy.prefix, "/api"); 
+        assert_eq!(proxy.upstreams, vec!["a:1", "b:2"]); 
+        assert_eq!(proxy.health_check.as_deref(), Some("/up")); 
+    }
+
+    #[test]
+    fn reads_limits() {
+        let text = "[limits]\nrate = 2.5\nper_ip = 4\n"; 
+        let limits = Config::parse(text, Path::new(".")).unwrap().limits.unwrap(); 
+
+        assert_eq!(limits.rate, Some(2.5)); 
+        assert_eq!(limits.burst, None); 
+        assert_eq!(limits.per_ip, Some(4)); 
+        assert_eq!(limits.max_connections, None); 
+    }
+
+    #[test]
+    fn reads_virtual_hosts() {
+        let text = "[host a.test]\nalias = www.a.test\nroot = a\ndefault = on\n[host b.test]\nroot = /srv/b\n"; 
+        let config = Config::parse(text, Path::new("/etc/hello")).unwrap(); 
+
+        assert_eq!(config.hosts[0].names, vec!["a.test", "www.a.test"]); 
+        assert_eq!(config.hosts[0].root, Some(PathBuf::from("/etc/hello/a"))); 
+        assert!(config.hosts[0].default); 
+        assert_eq!(config.hosts[1].root, Some(PathBuf::from("/srv/b"))); 
+        assert!(!config.hosts[1].default); 
+    }
+
+    #[test]
+    fn reads_host_routes_and_proxies() {
+        let text = "[host api.test]\nroute = /about -> about.html\nproxy = /api -> a:1, b:2\nproxy = /api -> c:3\nproxy = /v2->d:4\n"; 
+        let host = &Config::parse(text, Path::new("/etc/hello")).unwrap().hosts[0]; 
+
+        assert_eq!(host.root, None); 
+        assert_eq!(host.routes, vec![RouteSettings {
+            path: String::from("/about"), 
+            file: PathBuf::from("/etc/hello/about.html"), 
+        }]); 
+        assert_eq!(host.proxies.len(), 2); 
+        assert_eq!(host.proxies[0].prefix, "/api"); 
+        assert_eq!(host.proxies[0].upstreams, vec!["a:1", "b:2", "c:3"]); 
+        assert_eq!(host.proxies[1].prefix, "/v2"); 
+        assert_eq!(host.proxies[1].upstreams, vec!["d:4"]); 
+
+        let err = Config::parse("[host a.test]\nproxy = api -> a:1\n", Path::new(".")).unwrap_err(); 
+        assert_eq!(err.to_string(), "config line 2: expected `/path -> target`, found `api -> a:1`"); 
+        let err = Config::parse("[host a.test]\nlisting = on\n", Path::new(".")).unwrap_err(); 
+        assert_eq!(err.to_string(), "config line 1: [host a.test] needs a `root`, a `route` or a `proxy`"); 
+    }
+
+    #[test]
+    fn errors_name_the_line() {
+        let err = Config::parse("workers = 4\nwrokers = 2\n", Path::new(".")).unwrap_err(); 
+        assert_eq!(err.to_string(), "config line 2: unknown key `wrokers`"); 
+
+        let err = Config::parse("[tls]\ncert = c.pem\n", Path::new(".")).unwrap_err(); 
+        assert_eq!(err.to_string(), "config line 1: [tls] needs `listen`"); 
+    }
+}
@@ -0,0 +1,496 @@
+/*
+    event-driven connection handling (epoll on Linux, through mio) 
+        the thread-per-job model ties up a worker for as long as a 
+        connection is open, so a few idle keep-alive clients can starve 
+        the pool; here one thread owns every socket instead and only 
+        wakes up when the OS reports that one of them is ready 
+
+        sockets are non-blocking: each readiness event reads everything 
+        available into the connection's input buffer, then tries to parse 
+        a complete req out of it (http::parse_request is incremental) 
+
+        a complete req is handed to the ThreadPool; the worker runs the 
+        handler and sends the serialized response back over a channel in 
+        pieces of at most PIECE bytes, using the Waker after each one to 
+        interrupt poll so the loop can write it; only PIECES of them can 
+        wait at a time, so a worker streaming a large file (or what an 
+        upstream sends) blocks until the client has taken the ones before, 
+        just as it would writing to the socket itself in threaded mode 
+
+        only one req per connection is in flight at a time, so pipelined 
+        reqs are answered in order; tokens are never reused, thus a late 
+        reply for a connection that has since gone away is just dropped 
+
+        while a req is in flight (or its response is still being written) 
+        the socket is not read: the bytes wait in the kernel, which bounds 
+        what a client pipelining without end can make us hold; as epoll 
+        is edge-triggered and will not report them again, the connection 
+        remembers that it left something unread and reads it once it is 
+        idle, and every req already in the input buffer is parsed then 
+
+        a WebSocket upgrade leaves the loop for good: the socket is taken 
+        out of the poll, switched back to blocking mode and given to a 
+        worker, which then owns it for the rest of its life 
+
+        limits are applied as in server.rs: a connection over them gets 
+        its 429/503 written once and is dropped, and a req over its rate 
+        is answered by the loop itself without troubling the pool 
+*/
+
+use std::{
+    collections::HashMap, 
+    io::{self, ErrorKind, Read, Write}, 
+    mem, net, 
+    sync::{mpsc, Arc}, 
+}; 
+
+use mio::{
+    net::{TcpListener, TcpStream}, 
+    Events, Interest, Poll, Registry, Token, Waker, 
+}; 
+
+use crate::{http, limit::Permit, router::Router, server, ThreadPool}; 
+
+const LISTENER: Token = Token(0); 
+const WAKER: Token = Token(1); 
+
+// a response is sent to the loop in pieces this large, at most this many 
+// waiting at a time 
+const PIECE: usize = 16 * 1024; 
+const PIECES: usize = 4; 
+
+struct Connection {
+    stream: TcpStream, 
+    peer: net::SocketAddr, 
+    input: Vec<u8>, 
+    output: Vec<u8>, 
+    // the pieces of the response being written, while it lasts 
+    body: Option<mpsc::Receiver<io::Result<Vec<u8>>>>, 
+    // a req from this connection is being handled by the pool, or its 
+    // response is still coming 
+    busy: bool, 
+    close_after_write: bool, 
+    peer_closed: bool, 
+    // the socket was readable while we were not reading 
+    unread: bool, 
+    // keeps this connection counted against the limits 
+    permit: Option<Permit>, 
+}
+
+// what should happen to a connection after it made progress 
+enum Next {
+    Keep, 
+    Close, 
+    // with whatever the client sent after the handshake req 
+    Upgrade(http::Request, Vec<u8>), 
+}
+
+// news about a response from a worker 
+enum Reply {
+    // the response is on its way, in pieces through `body`; it ends when 
+    // the worker drops its end, or with an error 
+    Start {
+        token: Token, 
+        body: mpsc::Receiver<io::Result<Vec<u8>>>, 
+        keep_alive: bool, 
+    }, 
+    // another piece is waiting 
+    More(Token), 
+}
+
+// the writer a worker serializes a response into: it hands the bytes to 
+// the loop a piece at a time 
+struct Pieces {
+    token: Token, 
+    buf: Vec<u8>, 
+    body: mpsc::SyncSender<io::Result<Vec<u8>>>, 
+    sender: mpsc::Sender<Reply>, 
+    waker: Arc<Waker>, 
+}
+
+// what the loop needs to hand a req over to the pool 
+struct Dispatcher<'a> {
+    pool: &'a ThreadPool, 
+    router: &'a Arc<Router>, 
+    sender: &'a mpsc::Sender<Reply>, 
+    waker: &'a Arc<Waker>, 
+}
+
+/// Serve `listener` from a readiness loop, running handlers on `pool`. 
+/// 
+/// Only returns if polling itself fails. 
+pub fn serve(listener: net::TcpListener, pool: &ThreadPool, router: Arc<Router>) -> io::Result<()> {
+    listener.set_nonblocking(true)?; 
+    let mut listener = TcpListener::from_std(listener); 
+
+    let mut poll = Poll::new()?; 
+    poll.registry()
+        .register(&mut listener, LISTENER, Interest::READABLE)?; 
+    let waker = Arc::new(Waker::new(poll.registry(), WAKER)?); 
+    let (sender, receiver) = mpsc::channel::<Reply>(); 
+
+    let dispatcher = Dispatcher {
+        pool, 
+        router: &router, 
+        sender: &sender, 
+        waker: &waker, 
+    }; 
+    let mut connections: HashMap<Token, Connection> = HashMap::new(); 
+    let mut next_token = WAKER.0 + 1; 
+    let mut events = Events::with_capacity(1024); 
+
+    loop {
+        if let Err(e) = poll.poll(&mut events, None) {
+            if e.kind() == ErrorKind::Interrupted {
+                continue; 
+            }
+            return Err(e); 
+        }
+
+        for event in events.iter() {
+            match event.token() {
+                LISTENER => loop {
+                    // accept until the backlog is empty 
+                    let (mut stream, peer) = match listener.accept() {
+                        Ok(accepted) => accepted, 
+                        Err(e) if e.kind() == ErrorKind::WouldBlock => break, 
+                        Err(e) => {
+                            eprintln!("accept failed: {e}"); 
+                            break; 
+                        }
+                    }; 
+                    let permit = match router.limiter().map(|l| l.open(peer.ip())) {
+                        Some(Ok(permit)) => Some(permit), 
+                        Some(Err(rejection)) => {
+                            // one try; a socket this fresh has room for it 
+                            let response = rejection.response().with_header("Connection", "close"); 
+                            if let Ok(bytes) = response.into_bytes() {
+                                let _ = stream.write(&bytes); 
+                            }
+                            continue; 
+                        }
+                        None => None, 
+                    }; 
+                    let token = Token(next_token); 
+                    next_token += 1; 
+
+                    // only this connection is lost, not the loop 
+                    if let Err(e) = poll.registry().register(&mut stream, token, Interest::READABLE) {
+                        eprintln!("cannot watch a connection: {e}"); 
+                        continue; 
+                    }
+                    connections.insert(token, Connection::new(stream, peer, permit)); 
+                }, 
+                WAKER => {
+                    while let Ok(reply) = receiver.try_recv() {
+                        let token = match reply {
+                            Reply::Start { token, body, keep_alive } => {
+                                let Some(conn) = connections.get_mut(&token) else {
+                                    continue; 
+                                }; 
+                                conn.body = Some(body); 
+                                conn.close_after_write = !keep_alive; 
+                                token
+                            }
+                            Reply::More(token) => token, 
+                        }; 
+                        let Some(conn) = connections.get_mut(&token) else {
+                            continue; 
+                        }; 
+                        let next = conn.process(token, poll.registry(), &dispatcher); 
+                        advance(&mut connections, token, next, poll.registry(), &dispatcher); 
+                    }
+                }
+                token => {
+                    let Some(conn) = connections.get_mut(&token) else {
+                        continue; 
+                    }; 
+                    if event.is_readable() {
+                        conn.fill(); 
+                    }
+                    let next = conn.process(token, poll.registry(), &dispatcher); 
+                    advance(&mut connections, token, next, poll.registry(), &dispatcher); 
+                }
+            }
+        }
+    }
+}
+
+fn advance(
+    connections: &mut HashMap<Token, Connection>, 
+    token: Token, 
+    next: Next, 
+    registry: &Registry, 
+    dispatcher: &Dispatcher, 
+) {
+    if let Next::Keep = next {
+        return; 
+    }
+    let Some(mut conn) = connections.remove(&token) else {
+        return; 
+    }; 
+    let _ = registry.deregister(&mut conn.stream); 
+
+    if let Next::Upgrade(request, rest) = next {
+        let stream: net::TcpStream = conn.stream.into(); 
+        if stream.set_nonblocking(false).is_ok() {
+            dispatcher.upgrade(stream, rest, request, conn.permit); 
+        }
+    }
+}
+
+impl Connection {
+    fn new(stream: TcpStream, peer: net::SocketAddr, permit: Option<Permit>) -> Connection {
+        Connection {
+            stream, 
+            peer, 
+            input: Vec::new(), 
+            output: Vec::new(), 
+            body: None, 
+            busy: false, 
+            close_after_write: false, 
+            peer_closed: false, 
+            unread: false, 
+            permit, 
+        }
+    }
+
+    // read everything the socket has for us right now, unless a req is 
+    // still being answered; then leave it for later 
+    fn fill(&mut self) {
+        if self.busy || !self.output.is_empty() {
+            self.unread = true; 
+            return; 
+        }
+        self.unread = false; 
+        let mut chunk = [0; 4096]; 
+        loop {
+            match self.stream.read(&mut chunk) {
+                Ok(0) => {
+                    self.peer_closed = true; 
+                    return; 
+                }
+                Ok(n) => self.input.extend_from_slice(&chunk[..n]), 
+                Err(e) if e.kind() == ErrorKind::WouldBlock => return, 
+                Err(e) if e.kind() == ErrorKind::Interrupted => continue, 
+                Err(_) => {
+                    self.peer_closed = true; 
+                    return; 
+                }
+            }
+        }
+    }
+
+    // write as much pending output as the socket accepts, taking the next 
+    // piece of the response whenever the last one is out 
+    fn flush(&mut self) -> io::Result<()> {
+        loop {
+            if self.output.is_empty() && !self.next_piece() {
+                return Ok(()); 
+            }
+            match self.stream.write(&self.output) {
+                Ok(0) => return Err(ErrorKind::WriteZero.into()), 
+                Ok(n) => {
+                    self.output.drain(..n); 
+                }
+                Err(e) if e.kind() == ErrorKind::WouldBlock => return Ok(()), 
+                Err(e) if e.kind() == ErrorKind::Interrupted => continue, 
+                Err(e) => return Err(e), 
+            }
+        }
+    }
+
+    // move the next piece of the response into the output, if it is there; 
+    // once the response is over the connection is free again 
+    fn next_piece(&mut self) -> bool {
+        let Some(body) = &self.body else {
+            return false; 
+        }; 
+        match body.try_recv() {
+            Ok(Ok(piece)) => {
+                self.output = piece; 
+                true
+            }
+            Err(mpsc::TryRecvError::Empty) => false, 
+            Err(mpsc::TryRecvError::Disconnected) => {
+                self.body = None; 
+                self.busy = false; 
+                false
+            }
+            // the response is cut short, all the client can learn of it 
+            // is the connection closing 
+            Ok(Err(e)) => {
+                eprintln!("response failed: {e}"); 
+                self.body = None; 
+                self.busy = false; 
+                self.close_after_write = true; 
+                false
+            }
+        }
+    }
+
+    // move the connection forward and tell the loop what to do with it 
+    fn process(&mut self, token: Token, registry: &Registry, dispatcher: &Dispatcher) -> Next {
+        loop {
+            let was_busy = self.busy || !self.output.is_empty(); 
+            let mut parsed = false; 
+            if !self.busy && self.output.is_empty() && !self.close_after_write {
+                match http::parse_request(&self.input, dispatcher.router.max_body_size()) {
+                    Ok(Some((mut request, used))) => {
+                        parsed = true; 
+                        request.remote_addr = Some(self.peer); 
+                        if let Err(rejection) = server::check_rate(&request, dispatcher.router) {
+                            // answered right here, the pool never sees it 
+                            self.input.drain(..used); 
+                            let keep_alive = request.keep_alive(); 
+                            self.output = rejection
+                                .response()
+                                .with_header("Connection", if keep_alive { "keep-alive" } else { "close" })
+                                .into_bytes_for(&request)
+                                .unwrap_or_default(); 
+                            self.close_after_write = !keep_alive; 
+                        } else if dispatcher.router.socket_handler(&request).is_some() {
+                            self.input.drain(..used); 
+                            return Next::Upgrade(request, mem::take(&mut self.input)); 
+                        } else {
+                            self.input.drain(..used); 
+                            self.busy = true; 
+                            dispatcher.dispatch(token, request); 
+                        }
+                    }
+                    // the rest of the req may be waiting in the socket 
+                    Ok(None) if self.unread => {
+                        self.fill(); 
+                        continue; 
+                    }
+                    Ok(None) => {}
+                    Err(e) => {
+                        self.output = server::error_response(&e)
+                            .with_header("Connection", "close")
+                            .into_bytes()
+                            .unwrap_or_default(); 
+                        self.close_after_write = true; 
+                    }
+                }
+            }
+
+            if self.flush().is_err() {
+                return Next::Close; 
+            }
+            // a response went out and the connection is idle again: the 
+            // next pipelined req may be in the input already, or still in 
+            // the socket, where no new event will announce it 
+            let idle = !self.busy && self.output.is_empty() && !self.close_after_write; 
+            let more = self.unread || !self.input.is_empty(); 
+            if !(idle && more && (was_busy || parsed)) {
+                break; 
+            }
+        }
+
+        if self.output.is_empty() && !self.busy && (self.close_after_write || self.peer_closed) {
+            return Next::Close; 
+        }
+
+        // only ask for writability while there is something to write 
+        let interest = if self.output.is_empty() {
+            Interest::READABLE
+        } else {
+            Interest::READABLE | Interest::WRITABLE
+        }; 
+        match registry.reregister(&mut self.stream, token, interest) {
+            Ok(()) => Next::Keep, 
+            Err(_) => Next::Close, 
+        }
+    }
+}
+
+impl Pieces {
+    // hand what was written so far to the loop, waiting for room 
+    fn send(&mut self) -> io::Result<()> {
+        let piece = mem::replace(&mut self.buf, Vec::with_capacity(PIECE)); 
+        // fails once the loop dropped the connection 
+        self.body.send(Ok(piece)).map_err(|_| io::Error::from(ErrorKind::BrokenPipe))?; 
+        let _ = self.sender.send(Reply::More(self.token)); 
+        let _ = self.waker.wake(); 
+        Ok(())
+    }
+
+    fn fail(&mut self, error: io::Error) {
+        let _ = self.body.send(Err(error)); 
+    }
+
+    // end the response: the loop learns it from the closed channel, but 
+    // has to be woken to look 
+    fn finish(self) {
+        let Pieces { token, body, sender, waker, .. } = self; 
+        drop(body); 
+        let _ = sender.send(Reply::More(token)); 
+        let _ = waker.wake(); 
+    }
+}
+
+impl Write for Pieces {
+    fn write(&mut self, bytes: &[u8]) -> io::Result<usize> {
+        let n = bytes.len().min(PIECE - self.buf.len()); 
+        self.buf.extend_from_slice(&bytes[..n]); 
+        if self.buf.len() == PIECE {
+            self.send()?; 
+        }
+        Ok(n)
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        if self.buf.is_empty() {
+            return Ok(()); 
+        }
+        self.send()
+    }
+}
+
+impl Dispatcher<'_> {
+    fn dispatch(&self, token: Token, request: http::Request) {
+        let router = Arc::clone(self.router); 
+        let sender = self.sender.clone(); 
+        let waker = Arc::clone(self.waker); 
+
+        self.pool.execute(move || {
+            let keep_alive = request.keep_alive(); 
+            let mut response = router.handle(&request); 
+            response.set_header("Connection", if keep_alive { "keep-alive" } else { "close" }); 
+
+            let (body, pieces) = mpsc::sync_channel(PIECES); 
+            // the loop may already be gone when shutting down 
+            if sender.send(Reply::Start { token, body: pieces, keep_alive }).is_err() {
+                return; 
+            }
+            let mut pieces = Pieces {
+                token, 
+                buf: Vec::with_capacity(PIECE), 
+                body, 
+                sender, 
+                waker, 
+            }; 
+            // write_for flushes at the end, which sends the last piece 
+            if let Err(e) = response.write_for(&request, &mut pieces) {
+                // the client is gone when the loop stopped listening 
+                if e.kind() != ErrorKind::BrokenPipe {
+                    pieces.fail(e); 
+                }
+            }
+            pieces.finish(); 
+        }); 
+    }
+    // hand an upgraded connection over to a worker for good 
+    fn upgrade(&self, stream: net::TcpStream, rest: Vec<u8>, request: http::Request, permit: Option<Permit>) {
+        let router = Arc::clone(self.router); 
+
+        self.pool.execute(move || {
+            let _permit = permit; 
+            if let Some(handler) = router.socket_handler(&request) {
+                if let Err(e) = server::upgrade(stream, rest, &request, handler) {
+                    eprintln!("websocket error: {e}"); 
+                }
+            }
+        }); 
+    }
+}
@@ -0,0 +1,323 @@
+/*
+    static files served from a document root 
+        GET /docs/a.txt reads <root>/docs/a.txt; the path is decoded and 
+        split into segments first, and any `..` (or a hidden `.name`) is 
+        refused, so a req can never climb out of the root 
+
+        files are streamed with a Content-Type guessed from the extension; 
+        a directory is answered with its index.html when it has one 
+
+    without an index.html, a directory can be listed (when listing is on) 
+        with the name, size and modification time of every entry: 
+            ?format=json            JSON instead of HTML (so does an 
+                                    Accept: application/json header) 
+            ?sort=name|size|modified&order=asc|desc 
+        subdirectories always come first; hidden entries are left out 
+
+    serve returns None when there is nothing to send (no such file, or a 
+        directory that may not be listed), so that the caller can answer 
+        with its own 404 page 
+*/
+
+use std::{
+    fs::{self, File}, 
+    io, 
+    path::{Path, PathBuf}, 
+    time::UNIX_EPOCH, 
+}; 
+
+use crate::{
+    form, 
+    http::{Request, Response}, 
+    template::escape, 
+}; 
+
+pub struct StaticFiles {
+    root: PathBuf, 
+    listing: bool, 
+}
+
+struct Entry {
+    name: String, 
+    is_dir: bool, 
+    size: u64, 
+    // seconds since the Unix epoch 
+    modified: u64, 
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum SortKey {
+    Name, 
+    Size, 
+    Modified, 
+}
+
+impl StaticFiles {
+    /// Serve the files below `root`; directories are not listed. 
+    pub fn new(root: impl Into<PathBuf>) -> StaticFiles {
+        StaticFiles {
+            root: root.into(), 
+            listing: false, 
+        }
+    }
+
+    /// List directories that have no index.html. 
+    pub fn with_listing(mut self, listing: bool) -> StaticFiles {
+        self.listing = listing; 
+        self
+    }
+
+    /// Answer `request` from the document root, or `None` if nothing is there. 
+    pub fn serve(&self, request: &Request) -> Option<Response> {
+        if request.method != "GET" && request.method != "HEAD" {
+            return None; 
+        }
+        let Some(path) = self.resolve(&request.path) else {
+            return Some(Response::text(403, "Forbidden")); 
+        }; 
+        let metadata = fs::metadata(&path).ok()?; 
+
+        if !metadata.is_dir() {
+            return Some(file_response(&path)); 
+        }
+        // relative links in a listing need the trailing slash; the query 
+        // (sort order, format) goes along 
+        if !request.path.ends_with('/') {
+            let location = match &request.query {
+                Some(query) => format!("{}/?{query}", request.path), 
+                None => format!("{}/", request.path), 
+            }; 
+            return Some(Response::new(301).with_header("Location", &location)); 
+        }
+
+        let index = path.join("index.html"); 
+        if index.is_file() {
+            return Some(file_response(&index)); 
+        }
+        if !self.listing {
+            return None; 
+        }
+
+        let query = form::parse_urlencoded(request.query.as_deref().unwrap_or("")); 
+        let param = |name: &str| query.iter().find(|(n, _)| n == name).map(|(_, v)| v.as_str()); 
+
+        let key = match param("sort") {
+            Some("size") => SortKey::Size, 
+            Some("modified") => SortKey::Modified, 
+            _ => SortKey::Name, 
+        }; 
+        let descending = param("order") == Some("desc"); 
+        let entries = match list(&path, key, descending) {
+            Ok(entries) => entries, 
+            Err(e) => return Some(Response::text(500, format!("cannot list directory: {e}"))), 
+        }; 
+
+        let wants_json = param("format") == Some("json")
+            || request.header("Accept").is_some_and(|a| a.contains("application/json")); 
+        Some(if wants_json {
+            Response::new(200)
+                .with_header("Content-Type", "application/json")
+                .with_body(to_json(&entries))
+        } else {
+            Response::html(200, to_html(&request.path, &entries, key, descending))
+        })
+    }
+
+    // map a req path onto the file system, refusing anything suspicious 
+    fn resolve(&self, path: &str) -> Option<PathBuf> {
+        // a + in a path is a plus, not a space 
+        let decoded = form::percent_decode(&path.replace('+', "%2B")); 
+        let mut resolved = self.root.clone(); 
+
+        for segment in decoded.split('/').filter(|s| !s.is_empty()) {
+            if segment.starts_with('.') || segment.contains('\\') || segment.contains('\0') {
+                return None; 
+            }
+            resolved.push(segment); 
+        }
+        Some(resolved)
+    }
+}
+
+// the entries of `dir`, directories first, then sorted by `key` 
+fn list(dir: &Path, key: SortKey, descending: bool) -> io::Result<Vec<Entry>> {
+    let mut entries = Vec::new(); 
+    for entry in fs::read_dir(dir)? {
+        let entry = entry?; 
+        let name = entry.file_name().to_string_lossy().into_owned(); 
+        if name.starts_with('.') {
+            continue; 
+        }
+        let metadata = entry.metadata()?; 
+        let modified = metadata
+            .modified()
+            .ok()
+            .and_then(|t| t.duration_since(UNIX_EPOCH).ok())
+            .map(|d| d.as_secs())
+            .unwrap_or(0); 
+
+        entries.push(Entry {
+            name, 
+            is_dir: metadata.is_dir(), 
+            size: if metadata.is_dir() { 0 } else { metadata.len() }, 
+            modified, 
+        }); 
+    }
+
+    entries.sort_by(|a, b| {
+        let order = match key {
+            SortKey::Name => a.name.cmp(&b.name), 
+            SortKey::Size => a.size.cmp(&b.size).then_with(|| a.name.cmp(&b.name)), 
+            SortKey::Modified => a.modified.cmp(&b.modified).then_with(|| a.name.cmp(&b.name)), 
+        }; 
+        let order = if descending { order.reverse() } else { order }; 
+        b.is_dir.cmp(&a.is_dir).then(order)
+    }); 
+    Ok(entries)
+}
+
+/// Answer with the file at `path`, streamed, whatever its name. 
+pub fn file_response(path: &Path) -> Response {
+    let file = match File::open(path) {
+        Ok(file) => file, 
+        Err(e) => return Response::text(500, format!("cannot open file: {e}")), 
+    }; 
+    let length = file.metadata().map(|m| m.len()).ok(); 
+
+    Response::new(200)
+        .with_header("Content-Type", content_type(path))
+        .with_stream(file, length)
+}
+
+/// A Content-Type for the file at `path`, from its extension. 
+pub fn content_type(path: &Path) -> &'static str {
+    let extension = path.extension().and_then(|e| e.to_str()).unwrap_or(""); 
+    match extension.to_ascii_lowercase().as_str() {
+        "html" | "htm" => "text/html; charset=utf-8", 
+        "css" => "text/css; charset=utf-8", 
+        "js" => "text/javascript; charset=utf-8", 
+        "json" => "application/json", 
+        "txt" | "md" | "rs" | "toml" => "text/plain; charset=utf-8", 
+        "png" => "image/png", 
+        "jpg" | "jpeg" => "image/jpeg", 
+        "gif" => "image/gif", 
+        "svg" => "image/svg+xml", 
+        "ico" => "image/x-icon", 
+        "pdf" => "application/pdf", 
+        "wasm" => "application/wasm", 
+        _ => "application/octet-stream", 
+    }
+}
+
+fn to_html(path: &str, entries: &[Entry], key: SortKey, descending: bool) -> String {
+    // clicking the current column again flips the order 
+    let header = |label: &str, column: SortKey, name: &str| {
+        let order = if column == key && !descending { "desc" } else { "asc" }; 
+        format!("<th><a href=\"?sort={name}&amp;order={order}\">{label}</a></th>")
+    }; 
+
+    let mut rows = String::new(); 
+    if path != "/" {
+        rows.push_str("<tr><td><a href=\"../\">../</a></td><td></td><td></td></tr>\n"); 
+    }
+    for entry in entries {
+        let slash = if entry.is_dir { "/" } else { "" }; 
+        let size = if entry.is_dir { String::from("-") } else { entry.size.to_string() }; 
+        let name = escape(&entry.name); 
+        rows.push_str(&format!(
+            "<tr><td><a href=\"{}{slash}\">{name}{slash}</a></td><td>{size}</td><td>{}</td></tr>\n", 
+            escape(&encode_segment(&entry.name)), 
+            format_time(entry.modified), 
+        )); 
+    }
+
+    let path = escape(path); 
+    format!(
+        "<!DOCTYPE html>\n<html lang=\"en\">\n<head><meta charset=\"utf-8\"><title>Index of {path}</title></head>\n\
+         <body>\n<h1>Index of {path}</h1>\n<table>\n<tr>{}{}{}</tr>\n{rows}</table>\n</body>\n</html>\n", 
+        header("Name", SortKey::Name, "name"), 
+        header("Size", SortKey::Size, "size"), 
+        header("Modified", SortKey::Modified, "modified"), 
+    )
+}
+
+fn to_json(entries: &[Entry]) -> String {
+    let items: Vec<String> = entries
+        .iter()
+        .map(|e| {
+            format!(
+                "{{\"name\":{},\"dir\":{},\"size\":{},\"modified\":{}}}", 
+                json_string(&e.name), 
+                e.is_dir, 
+                e.size, 
+                e.modified
+            )
+        })
+        .collect(); 
+    format!("[{}]", items.join(","))
+}
+
+fn json_string(s: &str) -> String {
+    let mut out = String::from("\""); 
+    for c in s.chars() {
+        match c {
+            '"' => out.push_str("\\\""), 
+            '\\' => out.push_str("\\\\"), 
+            c if (c as u32) < 0x20 => out.push_str(&format!("\\u{:04x}", c as u32)), 
+            c => out.push(c), 
+        }
+    }
+    out.push('"'); 
+    out
+}
+
+// keep a file name intact inside a link 
+fn encode_segment(name: &str) -> String {
+    let mut encoded = String::new(); 
+    for b in name.bytes() {
+        match b {
+            b'A'..=b'Z' | b'a'..=b'z' | b'0'..=b'9' | b'-' | b'_' | b'.' | b'~' => encoded.push(b as char), 
+            _ => encoded.push_str(&format!("%{b:02X}")), 
+        }
+    }
+    encoded
+}
+
+// "YYYY-MM-DD HH:MM" in UTC, from seconds since the epoch 
+fn format_time(secs: u64) -> String {
+    let days = (secs / 86_400) as i64; 
+    let rem = secs % 86_400; 
+
+    // civil date from a day count (Howard Hinnant's algorithm) 
+    let z = days + 719_468; 
+    let era = z.div_euclid(146_097); 
+    let doe = z.rem_euclid(146_097); 
+    let yoe = (doe - doe / 1460 + doe / 36_524 - doe / 146_096) / 365; 
+    let doy = doe - (365 * yoe + yoe / 4 - yoe / 100); 
+    let mp = (5 * doy + 2) / 153; 
+    let day = doy - (153 * mp + 2) / 5 + 1; 
+    let month = if mp < 10 { mp + 3 } else { mp - 9 }; 
+    let year = yoe + era * 400 + i64::from(month <= 2); 
+
+    format!("{year:04}-{month:02}-{day:02} {:02}:{:02}", rem / 3600, rem % 3600 / 60)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*; 
+
+    #[test]
+    fn refuses_to_leave_the_root() {
+        let files = StaticFiles::new("/srv/www"); 
+        assert_eq!(files.resolve("/a/b.txt"), Some(PathBuf::from("/srv/www/a/b.txt"))); 
+        assert_eq!(files.resolve("/a%20b+c"), Some(PathBuf::from("/srv/www/a b+c"))); 
+        assert_eq!(files.resolve("/../etc/passwd"), None); 
+        assert_eq!(files.resolve("/a/%2e%2e/%2e%2e/etc"), None); 
+    }
+
+    #[test]
+    fn formats_dates() {
+        assert_eq!(format_time(0), "1970-01-01 00:00"); 
+        assert_eq!(format_time(951_782_400 + 3_660), "2000-02-29 01:01"); 
+    }
+}
@@ -0,0 +1,550 @@
+/*
+    form posts and file uploads 
+        application/x-www-form-urlencoded is the query string format put 
+        into the body: name=value pairs joined by &, spaces sent as +, 
+        anything else unusual percent-encoded (%20, %2F, ...) 
+
+        multipart/form-data splits the body into parts separated by a 
+        boundary chosen by the client (given in the Content-Type header): 
+            --boundary\r\n 
+            Content-Disposition: form-data; name="file"; filename="a.txt" 
+            Content-Type: text/plain\r\n 
+            \r\n 
+            <raw bytes>\r\n 
+            --boundary--\r\n 
+        parts with a filename are uploads, the others are plain fields 
+
+    parse_multipart reads from any Read and keeps at most memory_limit 
+        bytes of a part: once a part grows past it, what has been collected 
+        is moved into a temp file and the rest copied after it; the temp 
+        file is deleted when the upload is dropped, unless it has been 
+        persisted somewhere else first 
+
+    the server does not stream bodies: a req is read whole before it is 
+        handled, so Request::form works on a body that is already in memory 
+        and spilling only saves the copy of the parts; what bounds memory 
+        is max_body_size, which should be kept small on routes that take 
+        uploads (the demo server allows 1 MB) 
+*/
+
+use std::{
+    env, fmt, fs, 
+    io::{self, Read, Write}, 
+    path::{Path, PathBuf}, 
+    process, 
+    sync::atomic::{AtomicUsize, Ordering}, 
+}; 
+
+use crate::http::{self, Request}; 
+
+// parts larger than this are spilled to disk by default 
+pub const DEFAULT_MEMORY_LIMIT: usize = 64 * 1024; 
+
+#[derive(Debug, Default)]
+pub struct Form {
+    pub fields: Vec<(String, String)>, 
+    pub files: Vec<UploadedFile>, 
+}
+
+impl Form {
+    /// The first value of the field `name`. 
+    pub fn get(&self, name: &str) -> Option<&str> {
+        self.fields
+            .iter()
+            .find(|(n, _)| n == name)
+            .map(|(_, v)| v.as_str())
+    }
+
+    /// The first upload sent as `name`. 
+    pub fn file(&self, name: &str) -> Option<&UploadedFile> {
+        self.files.iter().find(|f| f.name == name)
+    }
+}
+
+#[derive(Debug)]
+pub struct UploadedFile {
+    // the form field name 
+    pub name: String, 
+    // the name of the file on the client, if it sent one 
+    pub filename: Option<String>, 
+    pub content_type: Option<String>, 
+    pub size: u64, 
+    pub data: FileData, 
+}
+
+#[derive(Debug)]
+pub enum FileData {
+    Memory(Vec<u8>), 
+    Disk(TempFile), 
+}
+
+impl UploadedFile {
+    /// Load the whole upload into memory. 
+    pub fn bytes(&self) -> io::Result<Vec<u8>> {
+        match &self.data {
+            FileData::Memory(data) => Ok(data.clone()), 
+            FileData::Disk(file) => fs::read(file.path()), 
+        }
+    }
+
+    /// Store the upload at `path`, moving the temp file when possible. 
+    pub fn persist(self, path: impl AsRef<Path>) -> io::Result<()> {
+        match self.data {
+            FileData::Memory(data) => fs::write(path, data), 
+            FileData::Disk(file) => file.persist(path), 
+        }
+    }
+}
+
+/// A file in the temp directory that is removed when dropped. 
+#[derive(Debug)]
+pub struct TempFile {
+    path: PathBuf, 
+}
+
+impl TempFile {
+    fn create(dir: &Path) -> io::Result<(TempFile, fs::File)> {
+        static COUNTER: AtomicUsize = AtomicUsize::new(0); 
+        let n = COUNTER.fetch_add(1, Ordering::Relaxed); 
+        let path = dir.join(format!("hello-upload-{}-{n}", process::id())); 
+
+        // create_new so that we never clobber somebody else's file 
+        let file = fs::OpenOptions::new()
+            .write(true)
+            .create_new(true)
+            .open(&path)?; 
+        Ok((TempFile { path }, file))
+    }
+
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    pub fn persist(self, to: impl AsRef<Path>) -> io::Result<()> {
+        // rename fails across file systems, copy instead 
+        if fs::rename(&self.path, &to).is_err() {
+            fs::copy(&self.path, &to)?; 
+        }
+        Ok(())
+    }
+}
+
+impl Drop for TempFile {
+    fn drop(&mut self) {
+        let _ = fs::remove_file(&self.path); 
+    }
+}
+
+#[derive(Debug)]
+pub enum FormError {
+    // the body is neither urlencoded nor multipart 
+    UnsupportedContentType, 
+    Malformed(&'static str), 
+    Io(io::Error), 
+}
+
+impl fmt::Display for FormError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            FormError::UnsupportedContentType => write!(f, "unsupported form content type"), 
+            FormError::Malformed(reason) => write!(f, "malformed form: {reason}"), 
+            FormError::Io(e) => write!(f, "form I/O error: {e}"), 
+        }
+    }
+}
+
+impl std::error::Error for FormError {}
+
+impl From<io::Error> for FormError {
+    fn from(e: io::Error) -> FormError {
+        FormError::Io(e)
+    }
+}
+
+/// Where and when multipart parts are moved out of memory. 
+#[derive(Debug, Clone)]
+pub struct FormLimits {
+    pub memory_limit: usize, 
+    pub temp_dir: PathBuf, 
+}
+
+impl Default for FormLimits {
+    fn default() -> FormLimits {
+        FormLimits {
+            memory_limit: DEFAULT_MEMORY_LIMIT, 
+            temp_dir: env::temp_dir(), 
+        }
+    }
+}
+
+impl Request {
+    /// Parse the body as a form, using the default `FormLimits`. 
+    pub fn form(&self) -> Result<Form, FormError> {
+        self.form_with(&FormLimits::default())
+    }
+
+    /// Parse the body as a form, moving parts past `limits` to disk. 
+    /// 
+    /// The body has already been read whole, up to the router's 
+    /// `max_body_size`; the limits keep the parts from being copied in 
+    /// memory, not the body from being held there. 
+    pub fn form_with(&self, limits: &FormLimits) -> Result<Form, FormError> {
+        match self.content_type() {
+            Some(t) if t.eq_ignore_ascii_case("application/x-www-form-urlencoded") => {
+                let body = std::str::from_utf8(&self.body)
+                    .map_err(|_| FormError::Malformed("body is not UTF-8"))?; 
+                Ok(Form {
+                    fields: parse_urlencoded(body), 
+                    files: Vec::new(), 
+                })
+            }
+            Some(t) if t.eq_ignore_ascii_case("multipart/form-data") => {
+                let boundary = self
+                    .header("Content-Type")
+                    .and_then(|v| header_param(v, "boundary"))
+                    .ok_or(FormError::Malformed("missing boundary"))?; 
+                parse_multipart(&self.body[..], &boundary, limits)
+            }
+            _ => Err(FormError::UnsupportedContentType), 
+        }
+    }
+}
+
+/// Split `a=1&b=two+words` into decoded name/value pairs. 
+pub fn parse_urlencoded(input: &str) -> Vec<(String, String)> {
+    input
+        .split('&')
+        .filter(|pair| !pair.is_empty())
+        .map(|pair| {
+            let (name, value) = pair.split_once('=').unwrap_or((pair, "")); 
+            (percent_decode(name), percent_decode(value))
+        })
+        .collect()
+}
+
+/// Decode `%XX` escapes and `+` (as a space); bad escapes are kept as-is. 
+pub fn percent_decode(input: &str) -> String {
+    let bytes = input.as_bytes(); 
+    let mut out = Vec::with_capacity(bytes.len()); 
+    let mut i = 0; 
+
+    while i < bytes.len() {
+        match bytes[i] {
+            b'+' => out.push(b' '), 
+            b'%' if i + 2 < bytes.len() => match (hex(bytes[i + 1]), hex(bytes[i + 2])) {
+                (Some(high), Some(low)) => {
+                    out.push(high << 4 | low); 
+                    i += 2; 
+                }
+                _ => out.push(b'%'), 
+            }, 
+            byte => out.push(byte), 
+        }
+        i += 1; 
+    }
+
+    String::from_utf8_lossy(&out).into_owned()
+}
+
+fn hex(digit: u8) -> Option<u8> {
+    (digit as char).to_digit(16).map(|d| d as u8)
+}
+
+/// Parse a multipart/form-data body read from `reader`. 
+pub fn parse_multipart<R: Read>(reader: R, boundary: &str, limits: &FormLimits) -> Result<Form, FormError> {
+    let mut scanner = Scanner::new(reader); 
+    let delimiter = format!("--{boundary}").into_bytes(); 
+    let mut form = Form::default(); 
+
+    // skip the preamble up to the first boundary 
+    if !scanner.skip_past(&delimiter)? {
+        return Err(FormError::Malformed("missing first boundary")); 
+    }
+
+    // every later boundary starts on a new line 
+    let delimiter = [b"\r\n".as_slice(), &delimiter].concat(); 
+
+    loop {
+        // "--" after the boundary ends the body, "\r\n" starts a part 
+        match &scanner.take(2)?[..] {
+            b"--" => return Ok(form), 
+            b"\r\n" => {}
+            _ => return Err(FormError::Malformed("bad boundary line")), 
+        }
+
+        let head = scanner
+            .take_until(b"\r\n\r\n", http::MAX_HEAD_SIZE)?
+            .ok_or(FormError::Malformed("part headers too large"))?; 
+        let head = String::from_utf8(head).map_err(|_| FormError::Malformed("part headers are not UTF-8"))?; 
+
+        let mut disposition = None; 
+        let mut content_type = None; 
+        for line in head.split("\r\n").filter(|l| !l.is_empty()) {
+            let (name, value) = line
+                .split_once(':')
+                .ok_or(FormError::Malformed("part header without colon"))?; 
+            if name.trim().eq_ignore_ascii_case("Content-Disposition") {
+                disposition = Some(value.trim().to_string()); 
+            } else if name.trim().eq_ignore_ascii_case("Content-Type") {
+                content_type = Some(value.trim().to_string()); 
+            }
+        }
+        let disposition = disposition.ok_or(FormError::Malformed("part without Content-Disposition"))?; 
+        let name = header_param(&disposition, "name").ok_or(FormError::Malformed("part without name"))?; 
+        let filename = header_param(&disposition, "filename"); 
+
+        let mut sink = Sink::new(limits); 
+        if !scanner.stream_until(&delimiter, &mut sink)? {
+            return Err(FormError::Malformed("part is not terminated")); 
+        }
+        let (size, data) = sink.finish()?; 
+
+        // small parts without a filename are ordinary fields 
+        match (filename, data) {
+            (None, FileData::Memory(bytes)) if content_type.is_none() => match String::from_utf8(bytes) {
+                Ok(value) => form.fields.push((name, value)), 
+                Err(_) => return Err(FormError::Malformed("field is not UTF-8")), 
+            }, 
+            (filename, data) => form.files.push(UploadedFile {
+                name, 
+                filename, 
+                content_type, 
+                size, 
+                data, 
+            }), 
+        }
+    }
+}
+
+// value of `key` in `type; key=value; other="quoted value"` 
+fn header_param(header: &str, key: &str) -> Option<String> {
+    header.split(';').skip(1).find_map(|param| {
+        let (k, v) = param.split_once('=')?; 
+        if !k.trim().eq_ignore_ascii_case(key) {
+            return None; 
+        }
+        let v = v.trim(); 
+        Some(v.strip_prefix('"').and_then(|v| v.strip_suffix('"')).unwrap_or(v).to_string())
+    })
+}
+
+// collects a part in memory, then on disk once it outgrows the limit 
+struct Sink<'a> {
+    limits: &'a FormLimits, 
+    size: u64, 
+    memory: Vec<u8>, 
+    disk: Option<(TempFile, fs::File)>, 
+}
+
+impl<'a> Sink<'a> {
+    fn new(limits: &'a FormLimits) -> Sink<'a> {
+        Sink {
+            limits, 
+            size: 0, 
+            memory: Vec::new(), 
+            disk: None, 
+        }
+    }
+
+    fn write(&mut self, bytes: &[u8]) -> io::Result<()> {
+        self.size += bytes.len() as u64; 
+
+        if self.disk.is_none() && self.memory.len() + bytes.len() > self.limits.memory_limit {
+            let (temp, mut file) = TempFile::create(&self.limits.temp_dir)?; 
+            file.write_all(&self.memory)?; 
+            self.memory = Vec::new(); 
+            self.disk = Some((temp, file)); 
+        }
+
+        match &mut self.disk {
+            Some((_, file)) => file.write_all(bytes), 
+            None => {
+                self.memory.extend_from_slice(bytes); 
+                Ok(())
+            }
+        }
+    }
+
+    fn finish(self) -> io::Result<(u64, FileData)> {
+        match self.disk {
+            Some((temp, mut file)) => {
+                file.flush()?; 
+                Ok((self.size, FileData::Disk(temp)))
+            }
+            None => Ok((self.size, FileData::Memory(self.memory))), 
+        }
+    }
+}
+
+// a small read buffer that can look for byte sequences across reads 
+struct Scanner<R> {
+    reader: R, 
+    buf: Vec<u8>, 
+    eof: bool, 
+}
+
+impl<R: Read> Scanner<R> {
+    fn new(reader: R) -> Scanner<R> {
+        Scanner {
+            reader, 
+            buf: Vec::new(), 
+            eof: false, 
+        }
+    }
+
+    // read another chunk; false once the reader is exhausted 
+    fn fill(&mut self) -> io::Result<bool> {
+        if self.eof {
+            return Ok(false); 
+        }
+        let mut chunk = [0; 8192]; 
+        let n = self.reader.read(&mut chunk)?; 
+        self.buf.extend_from_slice(&chunk[..n]); 
+        self.eof = n == 0; 
+        Ok(n > 0)
+    }
+
+    fn take(&mut self, n: usize) -> io::Result<Vec<u8>> {
+        while self.buf.len() < n {
+            if !self.fill()? {
+                break; 
+            }
+        }
+        let n = n.min(self.buf.len()); 
+        Ok(self.buf.drain(..n).collect())
+    }
+
+    fn skip_past(&mut self, needle: &[u8]) -> io::Result<bool> {
+        let mut sink = io::sink(); 
+        self.stream_until(needle, &mut sink)
+    }
+
+    // everything up to `needle` (which is consumed), if found within `max` bytes 
+    fn take_until(&mut self, needle: &[u8], max: usize) -> io::Result<Option<Vec<u8>>> {
+        loop {
+            if let Some(pos) = http::find(&self.buf, needle) {
+                let out = self.buf[..pos].to_vec(); 
+                self.buf.drain(..pos + needle.len()); 
+                return Ok(Some(out)); 
+            }
+            if self.buf.len() > max || !self.fill()? {
+                return Ok(None); 
+            }
+        }
+    }
+
+    // pass everything before `needle` to `out`; false if it never shows up 
+    fn stream_until(&mut self, needle: &[u8], out: &mut impl Output) -> io::Result<bool> {
+        loop {
+            if let Some(pos) = http::find(&self.buf, needle) {
+                out.put(&self.buf[..pos])?; 
+                self.buf.drain(..pos + needle.len()); 
+                return Ok(true); 
+            }
+
+            // keep just enough to recognize a needle split across reads 
+            let keep = needle.len() - 1; 
+            if self.buf.len() > keep {
+                let flush = self.buf.len() - keep; 
+                out.put(&self.buf[..flush])?; 
+                self.buf.drain(..flush); 
+            }
+
+            if !self.fill()? {
+                return Ok(false); 
+            }
+        }
+    }
+}
+
+// where stream_until sends the bytes it skips over 
+trait Output {
+    fn put(&mut self, bytes: &[u8]) -> io::Result<()>; 
+}
+
+impl Output for io::Sink {
+    fn put(&mut self, _: &[u8]) -> io::Result<()> {
+        Ok(())
+    }
+}
+
+impl Output for Sink<'_> {
+    fn put(&mut self, bytes: &[u8]) -> io::Result<()> {
+        self.write(bytes)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*; 
+
+    fn multipart_request(body: &str) -> Request {
+        let mut request = Request::new("POST", "/upload"); 
+        request.headers.push((
+            String::from("Content-Type"), 
+            String::from("multipart/form-data; boundary=XyZ"), 
+        )); 
+        request.body = body.replace('\n', "\r\n").into_bytes(); 
+        request
+    }
+
+    #[test]
+    fn decodes_urlencoded_pairs() {
+        let fields = parse_urlencoded("name=J%C3%BCrgen+M&empty=&flag&bad=%zz"); 
+        assert_eq!(
+            fields, 
+            vec![
+                (String::from("name"), String::from("Jürgen M")), 
+                (String::from("empty"), String::new()), 
+                (String::from("flag"), String::new()), 
+                (String::from("bad"), String::from("%zz")), 
+            ]
+        ); 
+    }
+
+    #[test]
+    fn splits_fields_and_files() {
+        let request = multipart_request(
+            "preamble\n--XyZ\nContent-Disposition: form-data; name=\"title\"\n\nHello\n\
+             --XyZ\nContent-Disposition: form-data; name=\"doc\"; filename=\"a.txt\"\n\
+             Content-Type: text/plain\n\nline one\nline two\n--XyZ--\n", 
+        ); 
+        let form = request.form().unwrap(); 
+
+        assert_eq!(form.get("title"), Some("Hello")); 
+        let doc = form.file("doc").unwrap(); 
+        assert_eq!(doc.filename.as_deref(), Some("a.txt")); 
+        assert_eq!(doc.bytes().unwrap(), b"line one\r\nline two"); 
+    }
+
+    #[test]
+    fn spills_large_parts_to_disk() {
+        let payload = "x".repeat(10_000); 
+        let request = multipart_request(&format!(
+            "--XyZ\nContent-Disposition: form-data; name=\"big\"; filename=\"big.bin\"\n\n{payload}\n--XyZ--\n"
+        )); 
+        let limits = FormLimits {
+            memory_limit: 1024, 
+            ..FormLimits::default()
+        }; 
+        let form = request.form_with(&limits).unwrap(); 
+
+        let big = form.file("big").unwrap(); 
+        assert_eq!(big.size, 10_000); 
+        let FileData::Disk(temp) = &big.data else {
+            panic!("expected the part on disk"); 
+        }; 
+        let path = temp.path().to_path_buf(); 
+        assert_eq!(fs::read(&path).unwrap(), payload.as_bytes()); 
+
+        // the temp file goes away with the form 
+        drop(form); 
+        assert!(!path.exists()); 
+    }
+
+    #[test]
+    fn rejects_unterminated_parts() {
+        let request = multipart_request("--XyZ\nContent-Disposition: form-data; name=\"a\"\n\nno end"); 
+        assert!(matches!(request.form(), Err(FormError::Malformed(_)))); 
+    }
+}
@@ -0,0 +1,677 @@
+/*
+    HTTP/1.1 messages shared by every connection handling mode 
+        a req is only handed to a handler once it is complete: the head 
+        (req line + headers) ends with an empty line (\r\n\r\n), and the 
+        body is exactly Content-Length bytes long, or, with 
+        Transfer-Encoding: chunked, runs up to the 0 chunk and is decoded 
+        (chunked wins over Content-Length; any other coding gets a 501) 
+
+        parse_request works on whatever bytes have arrived so far, so it 
+        can be called again every time more data is read from a socket; 
+        Ok(None) means "not yet", Ok(Some) hands back the req and how many 
+        bytes it used (anything after that belongs to the next req) 
+
+    the body is limited too: as soon as the head announces a body larger 
+        than max_body_size, parsing fails with BodyTooLarge (answered with 
+        413) instead of waiting for bytes we would never accept; a chunked 
+        body fails the same way once its chunks add up to more 
+
+    Response is built by the handlers and serialized with write_to; the 
+        Content-Length header is always computed from the body 
+
+        the answer to a HEAD is written with write_for: the same head, 
+        Content-Length included, and no body 
+
+        a body can also be a stream (e.g. a reverse proxy relaying what 
+        an upstream sends): it is copied while writing and, if its length 
+        is not known up front, sent with Transfer-Encoding: chunked 
+
+    read_response is the other direction, for when we are the client 
+        (the proxy, the test client): the head is parsed right away and 
+        the body left in the reader, to be streamed 
+*/
+
+use std::{
+    fmt, 
+    io::{self, BufRead, ErrorKind, Read, Write}, 
+    net::SocketAddr, 
+}; 
+
+// refuse to buffer heads larger than this 
+pub const MAX_HEAD_SIZE: usize = 8 * 1024; 
+
+// default limit for req bodies, see Router::with_max_body_size 
+pub const DEFAULT_MAX_BODY_SIZE: usize = 8 * 1024 * 1024; 
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Request {
+    pub method: String, 
+    pub path: String, 
+    pub query: Option<String>, 
+    pub version: String, 
+    pub headers: Vec<(String, String)>, 
+    pub body: Vec<u8>, 
+    // the client's address, filled in by the server 
+    pub remote_addr: Option<SocketAddr>, 
+}
+
+impl Request {
+    /// Create a body-less request, mostly useful for handlers and tests. 
+    pub fn new(method: &str, target: &str) -> Request {
+        let (path, query) = split_target(target); 
+
+        Request {
+            method: method.to_string(), 
+            path, 
+            query, 
+            version: String::from("HTTP/1.1"), 
+            headers: Vec::new(), 
+            body: Vec::new(), 
+            remote_addr: None, 
+        }
+    }
+
+    /// Look up a header value; header names are case-insensitive. 
+    pub fn header(&self, name: &str) -> Option<&str> {
+        find_header(&self.headers, name)
+    }
+
+    /// The media type of the body, without parameters like `charset`. 
+    pub fn content_type(&self) -> Option<&str> {
+        self.header("Content-Type")
+            .map(|value| value.split(';').next().unwrap_or("").trim())
+    }
+
+    /// Whether the client wants the connection kept open after the response. 
+    pub fn keep_alive(&self) -> bool {
+        match self.header("Connection") {
+            Some(value) if value.eq_ignore_ascii_case("close") => false, 
+            Some(value) if value.eq_ignore_ascii_case("keep-alive") => true, 
+            // HTTP/1.1 defaults to persistent connections, 1.0 does not 
+            _ => self.version == "HTTP/1.1", 
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum ParseError {
+    // the head is not valid HTTP 
+    Malformed(&'static str), 
+    // the head did not fit into MAX_HEAD_SIZE 
+    HeadTooLarge, 
+    // the body is above the limit passed to parse_request 
+    BodyTooLarge, 
+    // a Transfer-Encoding other than chunked 
+    UnsupportedEncoding, 
+}
+
+impl fmt::Display for ParseError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            ParseError::Malformed(reason) => write!(f, "malformed request: {reason}"), 
+            ParseError::HeadTooLarge => write!(f, "request head too large"), 
+            ParseError::BodyTooLarge => write!(f, "request body too large"), 
+            ParseError::UnsupportedEncoding => write!(f, "unsupported transfer encoding"), 
+        }
+    }
+}
+
+impl std::error::Error for ParseError {}
+
+/// Try to parse one complete request from the start of `buf`. 
+/// 
+/// Returns `Ok(None)` while more bytes are needed, otherwise the request 
+/// together with the number of bytes it occupied in `buf`. Bodies longer 
+/// than `max_body_size` are rejected as soon as the head is complete. 
+pub fn parse_request(buf: &[u8], max_body_size: usize) -> Result<Option<(Request, usize)>, ParseError> {
+    let head_end = match find(buf, b"\r\n\r\n") {
+        Some(pos) => pos, 
+        None if buf.len() > MAX_HEAD_SIZE => return Err(ParseError::HeadTooLarge), 
+        None => return Ok(None), 
+    }; 
+    if head_end > MAX_HEAD_SIZE {
+        return Err(ParseError::HeadTooLarge); 
+    }
+
+    let head = std::str::from_utf8(&buf[..head_end])
+        .map_err(|_| ParseError::Malformed("head is not UTF-8"))?; 
+    let mut lines = head.split("\r\n"); 
+
+    // Method Request-URI HTTP-Version 
+    let request_line = lines.next().unwrap_or(""); 
+    let mut parts = request_line.split(' '); 
+    let (method, target, version) = match (parts.next(), parts.next(), parts.next(), parts.next()) {
+        (Some(m), Some(t), Some(v), None) if !m.is_empty() && t.starts_with('/') => (m, t, v), 
+        _ => return Err(ParseError::Malformed("bad request line")), 
+    }; 
+    if version != "HTTP/1.1" && version != "HTTP/1.0" {
+        return Err(ParseError::Malformed("unsupported HTTP version")); 
+    }
+
+    let mut headers = Vec::new(); 
+    for line in lines {
+        let (name, value) = line
+            .split_once(':')
+            .ok_or(ParseError::Malformed("header without colon"))?; 
+        headers.push((name.trim().to_string(), value.trim().to_string())); 
+    }
+
+    let body_start = head_end + 4; 
+    let (body, end) = match find_header(&headers, "Transfer-Encoding") {
+        // chunked alone; there is no other coding we could undo 
+        Some(coding) if coding.eq_ignore_ascii_case("chunked") => {
+            match read_chunked(&buf[body_start..], max_body_size)? {
+                Some((body, used)) => (body, body_start + used), 
+                None => return Ok(None), 
+            }
+        }
+        Some(_) => return Err(ParseError::UnsupportedEncoding), 
+        None => {
+            let length = match find_header(&headers, "Content-Length") {
+                Some(value) => value
+                    .parse::<usize>()
+                    .map_err(|_| ParseError::Malformed("bad Content-Length"))?, 
+                None => 0, 
+            }; 
+            if length > max_body_size {
+                return Err(ParseError::BodyTooLarge); 
+            }
+            // wait until the whole body is in the buffer 
+            if buf.len() < body_start + length {
+                return Ok(None); 
+            }
+            (buf[body_start..body_start + length].to_vec(), body_start + length)
+        }
+    }; 
+
+    let (path, query) = split_target(target); 
+    let request = Request {
+        method: method.to_string(), 
+        path, 
+        query, 
+        version: version.to_string(), 
+        headers, 
+        body, 
+        remote_addr: None, 
+    }; 
+
+    Ok(Some((request, end)))
+}
+
+// decode the chunked body at the start of `buf`, and say how many bytes it 
+// took; None while its last chunk has not arrived 
+fn read_chunked(buf: &[u8], max_body_size: usize) -> Result<Option<(Vec<u8>, usize)>, ParseError> {
+    let mut reader = ChunkedReader::new(buf); 
+    let mut body = Vec::new(); 
+    // one byte past the limit is enough to know it is too large 
+    let result = (&mut reader).take(max_body_size as u64 + 1).read_to_end(&mut body); 
+    if body.len() > max_body_size {
+        return Err(ParseError::BodyTooLarge); 
+    }
+    match result {
+        Ok(_) => Ok(Some((body, buf.len() - reader.into_inner().len()))), 
+        // tiny chunks or endless trailers must not make us buffer forever 
+        Err(e) if e.kind() == ErrorKind::UnexpectedEof && buf.len() > 2 * max_body_size + MAX_HEAD_SIZE => {
+            Err(ParseError::BodyTooLarge)
+        }
+        Err(e) if e.kind() == ErrorKind::UnexpectedEof => Ok(None), 
+        Err(_) => Err(ParseError::Malformed("bad chunked body")), 
+    }
+}
+
+pub struct Response {
+    pub status: u16, 
+    pub headers: Vec<(String, String)>, 
+    pub body: Body, 
+}
+
+pub enum Body {
+    Bytes(Vec<u8>), 
+    // copied to the client while the response is written, so it never 
+    // has to fit in memory; sent chunked when the length is not known 
+    Stream {
+        reader: Box<dyn Read + Send>, 
+        length: Option<u64>, 
+    }, 
+}
+
+impl Body {
+    /// The body if it is held in memory, `None` for a stream. 
+    pub fn as_bytes(&self) -> Option<&[u8]> {
+        match self {
+            Body::Bytes(bytes) => Some(bytes), 
+            Body::Stream { .. } => None, 
+        }
+    }
+}
+
+impl fmt::Debug for Body {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Body::Bytes(bytes) => write!(f, "Bytes({} bytes)", bytes.len()), 
+            Body::Stream { length, .. } => write!(f, "Stream({length:?})"), 
+        }
+    }
+}
+
+impl fmt::Debug for Response {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_struct("Response")
+            .field("status", &self.status)
+            .field("headers", &self.headers)
+            .field("body", &self.body)
+            .finish()
+    }
+}
+
+impl Response {
+    /// Create an empty response with the given status code. 
+    pub fn new(status: u16) -> Response {
+        Response {
+            status, 
+            headers: Vec::new(), 
+            body: Body::Bytes(Vec::new()), 
+        }
+    }
+
+    /// Create an HTML response. 
+    pub fn html(status: u16, contents: impl Into<Vec<u8>>) -> Response {
+        Response::new(status)
+            .with_header("Content-Type", "text/html; charset=utf-8")
+            .with_body(contents)
+    }
+
+    /// Create a plain text response. 
+    pub fn text(status: u16, contents: impl Into<Vec<u8>>) -> Response {
+        Response::new(status)
+            .with_header("Content-Type", "text/plain; charset=utf-8")
+            .with_body(contents)
+    }
+
+    pub fn with_header(mut self, name: &str, value: &str) -> Response {
+        self.set_header(name, value); 
+        self
+    }
+
+    pub fn with_body(mut self, body: impl Into<Vec<u8>>) -> Response {
+        self.body = Body::Bytes(body.into()); 
+        self
+    }
+
+    /// Stream the body from `reader`; `length` is sent as Content-Length if known. 
+    pub fn with_stream(mut self, reader: impl Read + Send + 'static, length: Option<u64>) -> Response {
+        self.body = Body::Stream {
+            reader: Box::new(reader), 
+            length, 
+        }; 
+        self
+    }
+
+    /// Replace (or add) a header. 
+    pub fn set_header(&mut self, name: &str, value: &str) {
+        self.headers.retain(|(n, _)| !n.eq_ignore_ascii_case(name)); 
+        self.headers.push((name.to_string(), value.to_string())); 
+    }
+
+    pub fn header(&self, name: &str) -> Option<&str> {
+        find_header(&self.headers, name)
+    }
+
+    /// Write the status line, headers and body. 
+    /// 
+    /// The framing headers (Content-Length or Transfer-Encoding) always 
+    /// follow from the body, whatever the handler put in `headers`. 
+    pub fn write_to<W: Write>(self, writer: &mut W) -> io::Result<()> {
+        self.write(writer, true)
+    }
+
+    /// Write the response to `request`: without its body if it is a HEAD. 
+    /// 
+    /// The framing headers are still the ones `write_to` would send, so the 
+    /// client learns the length of the body; a streamed body is never read. 
+    pub fn write_for<W: Write>(self, request: &Request, writer: &mut W) -> io::Result<()> {
+        self.write(writer, request.method != "HEAD")
+    }
+
+    fn write<W: Write>(self, writer: &mut W, with_body: bool) -> io::Result<()> {
+        let mut head = format!("HTTP/1.1 {} {}\r\n", self.status, reason_phrase(self.status)); 
+        for (name, value) in &self.headers {
+            if !name.eq_ignore_ascii_case("Content-Length") && !name.eq_ignore_ascii_case("Transfer-Encoding") {
+                head.push_str(&format!("{name}: {value}\r\n")); 
+            }
+        }
+
+        match self.body {
+            Body::Bytes(bytes) => {
+                head.push_str(&format!("Content-Length: {}\r\n\r\n", bytes.len())); 
+                writer.write_all(head.as_bytes())?; 
+                if with_body {
+                    writer.write_all(&bytes)?; 
+                }
+            }
+            // a HEAD: the framing the body would have, but not the body 
+            Body::Stream { length, .. } if !with_body => {
+                match length {
+                    Some(length) => head.push_str(&format!("Content-Length: {length}\r\n\r\n")), 
+                    None => head.push_str("Transfer-Encoding: chunked\r\n\r\n"), 
+                }
+                writer.write_all(head.as_bytes())?; 
+            }
+            Body::Stream { reader, length: Some(length) } => {
+                head.push_str(&format!("Content-Length: {length}\r\n\r\n")); 
+                writer.write_all(head.as_bytes())?; 
+                let copied = io::copy(&mut reader.take(length), writer)?; 
+                if copied < length {
+                    return Err(io::Error::new(ErrorKind::UnexpectedEof, "body shorter than its length")); 
+                }
+            }
+            Body::Stream { mut reader, length: None } => {
+                head.push_str("Transfer-Encoding: chunked\r\n\r\n"); 
+                writer.write_all(head.as_bytes())?; 
+                write_chunked(&mut reader, writer)?; 
+            }
+        }
+
+        writer.flush()
+    }
+
+    /// Serialize the whole response into memory. 
+    pub fn into_bytes(self) -> io::Result<Vec<u8>> {
+        let mut out = Vec::new(); 
+        self.write_to(&mut out)?; 
+        Ok(out)
+    }
+
+    /// Serialize the response to `request` into memory, see `write_for`. 
+    pub fn into_bytes_for(self, request: &Request) -> io::Result<Vec<u8>> {
+        let mut out = Vec::new(); 
+        self.write_for(request, &mut out)?; 
+        Ok(out)
+    }
+}
+
+// each chunk is its size in hex, CRLF, the data, CRLF; a 0 chunk ends it 
+fn write_chunked<R: Read + ?Sized, W: Write>(reader: &mut R, writer: &mut W) -> io::Result<()> {
+    let mut buf = [0; 8192]; 
+    loop {
+        let n = match reader.read(&mut buf) {
+            Ok(n) => n, 
+            Err(e) if e.kind() == ErrorKind::Interrupted => continue, 
+            Err(e) => return Err(e), 
+        }; 
+        if n == 0 {
+            return writer.write_all(b"0\r\n\r\n"); 
+        }
+        writer.write_all(format!("{n:x}\r\n").as_bytes())?; 
+        writer.write_all(&buf[..n])?; 
+        writer.write_all(b"\r\n")?; 
+    }
+}
+
+/// Decodes a `Transfer-Encoding: chunked` body back into plain bytes. 
+pub struct ChunkedReader<R> {
+    inner: R, 
+    // bytes left in the current chunk; None before the next size line 
+    remaining: Option<u64>, 
+    done: bool, 
+}
+
+impl<R: BufRead> ChunkedReader<R> {
+    pub fn new(inner: R) -> ChunkedReader<R> {
+        ChunkedReader {
+            inner, 
+            remaining: None, 
+            done: false, 
+        }
+    }
+
+    pub fn into_inner(self) -> R {
+        self.inner
+    }
+
+    fn read_line(&mut self) -> io::Result<String> {
+        let mut line = String::new(); 
+        // a line cut short is as good as none: the rest has not arrived 
+        if self.inner.read_line(&mut line)? == 0 || !line.ends_with('\n') {
+            return Err(io::Error::new(ErrorKind::UnexpectedEof, "chunked body ended early")); 
+        }
+        Ok(line.trim_end().to_string())
+    }
+}
+
+impl<R: BufRead> Read for ChunkedReader<R> {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        while !self.done && self.remaining.is_none() {
+            // size line, possibly with ;extensions we do not care about 
+            let line = self.read_line()?; 
+            let size = line.split(';').next().unwrap_or("").trim(); 
+            let size = u64::from_str_radix(size, 16)
+                .map_err(|_| io::Error::new(ErrorKind::InvalidData, "bad chunk size"))?; 
+
+            if size == 0 {
+                // skip trailers up to the final empty line 
+                while !self.read_line()?.is_empty() {}
+                self.done = true; 
+            } else {
+                self.remaining = Some(size); 
+            }
+        }
+        if self.done || buf.is_empty() {
+            return Ok(0); 
+        }
+
+        let remaining = self.remaining.unwrap_or(0); 
+        let max = buf.len().min(remaining as usize); 
+        let n = self.inner.read(&mut buf[..max])?; 
+        if n == 0 {
+            return Err(io::Error::new(ErrorKind::UnexpectedEof, "chunked body ended early")); 
+        }
+
+        if remaining - n as u64 == 0 {
+            // every chunk's data is followed by CRLF 
+            self.read_line()?; 
+            self.remaining = None; 
+        } else {
+            self.remaining = Some(remaining - n as u64); 
+        }
+        Ok(n)
+    }
+}
+
+/// Read a response head from `reader` and wrap the rest as a streamed body. 
+/// 
+/// The body is framed by chunked encoding, Content-Length or the end of 
+/// the stream, in that order; `head_only` is for answers to HEAD requests. 
+pub fn read_response<R: BufRead + Send + 'static>(mut reader: R, head_only: bool) -> io::Result<Response> {
+    let invalid = |what: &str| io::Error::new(ErrorKind::InvalidData, what.to_string()); 
+
+    let status_line = read_response_line(&mut reader)?; 
+    let mut parts = status_line.splitn(3, ' '); 
+    let status = match (parts.next(), parts.next()) {
+        (Some(version), Some(code)) if version.starts_with("HTTP/1.") => {
+            code.parse::<u16>().map_err(|_| invalid("bad status code"))?
+        }
+        _ => return Err(invalid("bad status line")), 
+    }; 
+
+    let mut response = Response::new(status); 
+    let mut head_size = status_line.len(); 
+    loop {
+        let line = read_response_line(&mut reader)?; 
+        if line.is_empty() {
+            break; 
+        }
+        head_size += line.len(); 
+        if head_size > MAX_HEAD_SIZE {
+            return Err(invalid("response head too large")); 
+        }
+
+        let (name, value) = line.split_once(':').ok_or_else(|| invalid("header without colon"))?; 
+        response.headers.push((name.trim().to_string(), value.trim().to_string())); 
+    }
+
+    let chunked = response
+        .header("Transfer-Encoding")
+        .map(|v| v.to_ascii_lowercase().contains("chunked"))
+        .unwrap_or(false); 
+    let length = match response.header("Content-Length") {
+        Some(value) => Some(value.parse::<u64>().map_err(|_| invalid("bad Content-Length"))?), 
+        None => None, 
+    }; 
+    let no_body = head_only || status / 100 == 1 || status == 204 || status == 304; 
+
+    Ok(match (no_body, chunked, length) {
+        (true, _, _) => response, 
+        (false, true, _) => response.with_stream(ChunkedReader::new(reader), None), 
+        (false, false, Some(length)) => response.with_stream(reader, Some(length)), 
+        // no framing at all: the body runs until the other side closes 
+        (false, false, None) => response.with_stream(reader, None), 
+    })
+}
+
+fn read_response_line<R: BufRead>(reader: &mut R) -> io::Result<String> {
+    let mut line = String::new(); 
+    if reader.read_line(&mut line)? == 0 {
+        return Err(io::Error::new(ErrorKind::UnexpectedEof, "connection closed before the response")); 
+    }
+    Ok(line.trim_end_matches(['\r', '\n']).to_string())
+}
+
+pub fn reason_phrase(status: u16) -> &'static str {
+    match status {
+        101 => "Switching Protocols", 
+        200 => "OK", 
+        201 => "Created", 
+        204 => "No Content", 
+        301 => "Moved Permanently", 
+        302 => "Found", 
+        304 => "Not Modified", 
+        400 => "Bad Request", 
+        403 => "Forbidden", 
+        404 => "Not Found", 
+        405 => "Method Not Allowed", 
+        413 => "Payload Too Large", 
+        426 => "Upgrade Required", 
+        429 => "Too Many Requests", 
+        431 => "Request Header Fields Too Large", 
+        500 => "Internal Server Error", 
+        501 => "Not Implemented", 
+        502 => "Bad Gateway", 
+        503 => "Service Unavailable", 
+        504 => "Gateway Timeout", 
+        _ => "Unknown", 
+    }
+}
+
+fn find_header<'a>(headers: &'a [(String, String)], name: &str) -> Option<&'a str> {
+    headers
+        .iter()
+        .find(|(n, _)| n.eq_ignore_ascii_case(name))
+        .map(|(_, v)| v.as_str())
+}
+
+// split "/path?query" into its two halves 
+fn split_target(target: &str) -> (String, Option<String>) {
+    match target.split_once('?') {
+        Some((path, query)) => (path.to_string(), Some(query.to_string())), 
+        None => (target.to_string(), None), 
+    }
+}
+
+pub(crate) fn find(haystack: &[u8], needle: &[u8]) -> Option<usize> {
+    haystack.windows(needle.len()).position(|w| w == needle)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*; 
+
+    #[test]
+    fn waits_for_the_whole_request() {
+        let raw = b"POST /submit?x=1 HTTP/1.1\r\nHost: a\r\nContent-Length: 5\r\n\r\nhel"; 
+        assert_eq!(parse_request(raw, 1024), Ok(None)); 
+
+        let raw = b"POST /submit?x=1 HTTP/1.1\r\nHost: a\r\nContent-Length: 5\r\n\r\nhelloGET"; 
+        let (request, used) = parse_request(raw, 1024).unwrap().unwrap(); 
+        assert_eq!(request.method, "POST"); 
+        assert_eq!(request.path, "/submit"); 
+        assert_eq!(request.query.as_deref(), Some("x=1")); 
+        assert_eq!(request.header("host"), Some("a")); 
+        assert_eq!(request.body, b"hello"); 
+        assert_eq!(&raw[used..], b"GET"); 
+    }
+
+    #[test]
+    fn decodes_chunked_bodies() {
+        let raw = b"POST / HTTP/1.1\r\nTransfer-Encoding: chunked\r\nContent-Length: 3\r\n\r\n5\r\nhello\r\n6;x=1\r\n world\r\n0\r\nX-Trailer: 1\r\n\r\nGET"; 
+        let (request, used) = parse_request(raw, 1024).unwrap().unwrap(); 
+        assert_eq!(request.body, b"hello world"); 
+        assert_eq!(&raw[used..], b"GET"); 
+
+        // every prefix is incomplete, down to the last \n 
+        let whole = raw.len() - 3; 
+        for end in 0..whole {
+            assert_eq!(parse_request(&raw[..end], 1024), Ok(None), "{end}"); 
+        }
+
+        assert_eq!(parse_request(raw, 10), Err(ParseError::BodyTooLarge)); 
+        let raw = b"POST / HTTP/1.1\r\nTransfer-Encoding: chunked\r\n\r\nzz\r\n"; 
+        assert_eq!(parse_request(raw, 1024), Err(ParseError::Malformed("bad chunked body"))); 
+        let raw = b"POST / HTTP/1.1\r\nTransfer-Encoding: gzip\r\n\r\n"; 
+        assert_eq!(parse_request(raw, 1024), Err(ParseError::UnsupportedEncoding)); 
+    }
+
+    #[test]
+    fn rejects_garbage() {
+        assert!(parse_request(b"hello\r\n\r\n", 1024).is_err()); 
+        assert_eq!(parse_request(&[b'a'; MAX_HEAD_SIZE + 1], 1024), Err(ParseError::HeadTooLarge)); 
+    }
+
+    #[test]
+    fn rejects_large_bodies_before_they_arrive() {
+        let raw = b"POST / HTTP/1.1\r\nContent-Length: 2048\r\n\r\n"; 
+        assert_eq!(parse_request(raw, 1024), Err(ParseError::BodyTooLarge)); 
+    }
+
+    #[test]
+    fn response_has_content_length() {
+        let response = Response::text(404, "nope"); 
+        let bytes = String::from_utf8(response.into_bytes().unwrap()).unwrap(); 
+        assert!(bytes.starts_with("HTTP/1.1 404 Not Found\r\n")); 
+        assert!(bytes.ends_with("Content-Length: 4\r\n\r\nnope")); 
+    }
+
+    #[test]
+    fn head_responses_keep_the_length_of_the_body() {
+        let head = Request::new("HEAD", "/"); 
+        let bytes = Response::text(200, "hello").into_bytes_for(&head).unwrap(); 
+        assert!(String::from_utf8(bytes).unwrap().ends_with("Content-Length: 5\r\n\r\n")); 
+
+        // a stream is not even read 
+        struct Unreadable; 
+        impl Read for Unreadable {
+            fn read(&mut self, _: &mut [u8]) -> io::Result<usize> {
+                panic!("the body of a HEAD response was read"); 
+            }
+        }
+        let bytes = Response::new(200).with_stream(Unreadable, Some(1234)).into_bytes_for(&head).unwrap(); 
+        assert!(String::from_utf8(bytes).unwrap().ends_with("Content-Length: 1234\r\n\r\n")); 
+
+        let get = Request::new("GET", "/"); 
+        let bytes = Response::text(200, "hello").into_bytes_for(&get).unwrap(); 
+        assert!(bytes.ends_with(b"\r\n\r\nhello")); 
+    }
+
+    #[test]
+    fn streams_of_unknown_length_are_chunked() {
+        let response = Response::new(200).with_stream(&b"hello world"[..], None); 
+        let bytes = response.into_bytes().unwrap(); 
+
+        let body_start = find(&bytes, b"\r\n\r\n").unwrap() + 4; 
+        assert!(String::from_utf8_lossy(&bytes[..body_start]).contains("Transfer-Encoding: chunked")); 
+
+        let mut decoded = String::new(); 
+        ChunkedReader::new(&bytes[body_start..]).read_to_string(&mut decoded).unwrap(); 
+        assert_eq!(decoded, "hello world"); 
+    }
+}
@@ -36,10 +36,32 @@
     
     graceful shutdown and cleanup: 
 
+    the web server itself lives in the modules below: 
+        http (req/res types), router (handlers), server (thread-per- 
+        connection mode), event (readiness loop mode), websocket 
+        (upgraded connections), files (static files), form (urlencoded/multipart bodies), 
+        tls (HTTPS listener), proxy (forwarding to upstream servers), 
+        limit (per-client rate and connection limits), template (HTML 
+        pages rendered from a context), config (the server.conf file) 
+        and testing (a client for tests, in memory or over TCP) 
 */
+pub mod config; 
+pub mod event; 
+pub mod files; 
+pub mod form; 
+pub mod http; 
+pub mod limit; 
+pub mod proxy; 
+pub mod router; 
+pub mod server; 
+pub mod template; 
+pub mod testing; 
+pub mod tls; 
+pub mod websocket; 
+
 use std::{
     sync::{mpsc, Arc, Mutex}, 
-    thread::{self, Thread}, 
+    thread, 
 }; 
 
 pub struct ThreadPool {
@@ -0,0 +1,240 @@
+/*
+    per-client limits, so one client cannot take every worker for itself 
+        the ThreadPool caps how many threads we run, but nothing stops a 
+        single client from opening enough connections (or sending enough 
+        reqs) to keep all of them busy; three limits guard against that: 
+
+            rate              token bucket per client IP: `burst` tokens, 
+                              refilled at `rate` per second, one per req 
+            per_ip            open connections from a single IP 
+            max_connections   open connections in total 
+
+        a client over its own limits gets 429 Too Many Requests, while a 
+        full server answers 503 Service Unavailable; both carry a 
+        Retry-After header telling the client how long to back off 
+
+    connection limits are checked when a connection is accepted, so a 
+        refused client never reaches a worker; Limiter::open returns a 
+        Permit that gives the slot back when it is dropped along with the 
+        connection; the rate is checked once per req 
+
+    buckets are kept per IP; when too many are tracked, the ones that 
+        have filled up again (idle clients) are forgotten 
+*/
+
+use std::{
+    collections::HashMap, 
+    net::IpAddr, 
+    sync::{Arc, Mutex}, 
+    time::Instant, 
+}; 
+
+use crate::http::Response; 
+
+// forget idle buckets once this many clients are tracked 
+const MAX_TRACKED: usize = 10_000; 
+
+/// Which limits to enforce; `None` leaves that limit off. 
+#[derive(Debug, Clone, PartialEq, Default)]
+pub struct Limits {
+    // reqs per second per IP, and how many may come at once 
+    pub rate: Option<f64>, 
+    pub burst: Option<u32>, 
+    pub per_ip: Option<usize>, 
+    pub max_connections: Option<usize>, 
+}
+
+pub struct Limiter {
+    limits: Limits, 
+    buckets: Mutex<HashMap<IpAddr, Bucket>>, 
+    connections: Mutex<Connections>, 
+}
+
+struct Bucket {
+    tokens: f64, 
+    updated: Instant, 
+}
+
+#[derive(Default)]
+struct Connections {
+    total: usize, 
+    per_ip: HashMap<IpAddr, usize>, 
+}
+
+/// Why a client was turned away. 
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Rejection {
+    // 429 for the client's own limits, 503 when the server is full 
+    pub status: u16, 
+    pub retry_after: u64, 
+}
+
+/// An open connection slot, given back when dropped. 
+pub struct Permit {
+    limiter: Arc<Limiter>, 
+    ip: IpAddr, 
+}
+
+impl Limiter {
+    pub fn new(limits: Limits) -> Limiter {
+        Limiter {
+            limits, 
+            buckets: Mutex::new(HashMap::new()), 
+            connections: Mutex::new(Connections::default()), 
+        }
+    }
+
+    /// Take a connection slot for a client at `ip`. 
+    pub fn open(self: &Arc<Self>, ip: IpAddr) -> Result<Permit, Rejection> {
+        let mut connections = self.connections.lock().unwrap(); 
+
+        if let Some(max) = self.limits.max_connections {
+            if connections.total >= max {
+                return Err(Rejection {
+                    status: 503, 
+                    retry_after: 1, 
+                }); 
+            }
+        }
+        if let Some(max) = self.limits.per_ip {
+            if connections.per_ip.get(&ip).copied().unwrap_or(0) >= max {
+                return Err(Rejection {
+                    status: 429, 
+                    retry_after: 1, 
+                }); 
+            }
+        }
+        // only admitted clients get an entry; Permit::drop removes it 
+        *connections.per_ip.entry(ip).or_insert(0) += 1; 
+        connections.total += 1; 
+
+        Ok(Permit {
+            limiter: Arc::clone(self), 
+            ip, 
+        })
+    }
+
+    /// Spend one token of the bucket for `ip`, if the rate is limited. 
+    pub fn check(&self, ip: IpAddr) -> Result<(), Rejection> {
+        let Some(rate) = self.limits.rate else {
+            return Ok(()); 
+        }; 
+        let burst = self.limits.burst.map(f64::from).unwrap_or(rate.ceil().max(1.0)); 
+        let now = Instant::now(); 
+        let mut buckets = self.buckets.lock().unwrap(); 
+
+        if buckets.len() >= MAX_TRACKED {
+            buckets.retain(|_, b| b.refilled(now, rate, burst) < burst); 
+        }
+        let bucket = buckets.entry(ip).or_insert(Bucket {
+            tokens: burst, 
+            updated: now, 
+        }); 
+        bucket.tokens = bucket.refilled(now, rate, burst); 
+        bucket.updated = now; 
+
+        if bucket.tokens >= 1.0 {
+            bucket.tokens -= 1.0; 
+            return Ok(()); 
+        }
+        // whole seconds until the next token is there 
+        let wait = ((1.0 - bucket.tokens) / rate).ceil().max(1.0); 
+        Err(Rejection {
+            status: 429, 
+            retry_after: wait as u64, 
+        })
+    }
+
+    /// Open connections right now, in total and from `ip`. 
+    pub fn connections(&self, ip: IpAddr) -> (usize, usize) {
+        let connections = self.connections.lock().unwrap(); 
+        let from_ip = connections.per_ip.get(&ip).copied().unwrap_or(0); 
+        (connections.total, from_ip)
+    }
+}
+
+impl Bucket {
+    fn refilled(&self, now: Instant, rate: f64, burst: f64) -> f64 {
+        let elapsed = now.duration_since(self.updated).as_secs_f64(); 
+        (self.tokens + elapsed * rate).min(burst)
+    }
+}
+
+impl Rejection {
+    /// The response telling the client to back off. 
+    pub fn response(&self) -> Response {
+        let message = match self.status {
+            503 => "server is busy, try again later", 
+            _ => "too many requests, slow down", 
+        }; 
+        Response::text(self.status, message).with_header("Retry-After", &self.retry_after.to_string())
+    }
+}
+
+impl Drop for Permit {
+    fn drop(&mut self) {
+        let mut connections = self.limiter.connections.lock().unwrap(); 
+        connections.total -= 1; 
+        if let Some(count) = connections.per_ip.get_mut(&self.ip) {
+            *count -= 1; 
+            if *count == 0 {
+                connections.per_ip.remove(&self.ip); 
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*; 
+
+    const CLIENT: IpAddr = IpAddr::V4(std::net::Ipv4Addr::new(10, 0, 0, 1)); 
+    const OTHER: IpAddr = IpAddr::V4(std::net::Ipv4Addr::new(10, 0, 0, 2)); 
+
+    #[test]
+    fn bucket_runs_dry_after_the_burst() {
+        let limiter = Limiter::new(Limits {
+            rate: Some(0.5), 
+            burst: Some(2), 
+            ..Limits::default()
+        }); 
+
+        assert!(limiter.check(CLIENT).is_ok()); 
+        assert!(limiter.check(CLIENT).is_ok()); 
+        let rejection = limiter.check(CLIENT).unwrap_err(); 
+        assert_eq!(rejection.status, 429); 
+        assert_eq!(rejection.retry_after, 2); 
+
+        // every IP has a bucket of its own 
+        assert!(limiter.check(OTHER).is_ok()); 
+    }
+
+    #[test]
+    fn permits_count_open_connections() {
+        let limiter = Arc::new(Limiter::new(Limits {
+            per_ip: Some(1), 
+            max_connections: Some(2), 
+            ..Limits::default()
+        })); 
+
+        let first = limiter.open(CLIENT).unwrap(); 
+        assert_eq!(limiter.open(CLIENT).err().map(|r| r.status), Some(429)); 
+        let _second = limiter.open(OTHER).unwrap(); 
+        assert_eq!(limiter.open(OTHER).err().map(|r| r.status), Some(503)); 
+
+        drop(first); 
+        assert_eq!(limiter.connections(CLIENT), (1, 0)); 
+        assert!(limiter.open(CLIENT).is_ok()); 
+    }
+
+    #[test]
+    fn refused_clients_leave_no_entry_behind() {
+        let limiter = Arc::new(Limiter::new(Limits {
+            per_ip: Some(0), 
+            ..Limits::default()
+        })); 
+
+        assert_eq!(limiter.open(CLIENT).err().map(|r| r.status), Some(429)); 
+        assert!(limiter.connections.lock().unwrap().per_ip.is_empty()); 
+    }
+}
@@ -13,72 +13,333 @@
 
         thus, process connections concurrently, increasing the throughput 
 
+    connection handling modes (pick one at startup): 
+        cargo run                   # thread-per-connection 
+        cargo run -- --mode event   # readiness loop, see event.rs 
 
+    cargo run -- --config server.conf reads listeners, workers and the 
+        [tls] certificate from a file (see config.rs); with TLS enabled, 
+        HTTP and HTTPS are served side by side from the same pool; 
+        [proxy /prefix] sections put other local services behind us, 
+        and [limits] keeps a single client from hogging the workers 
+
+    ws://127.0.0.1:7878/echo sends every WebSocket message back 
+
+    with root = <dir> in the config, other paths are files from that 
+        directory, and listing = on lists directories without index.html; 
+        [host name] sections serve other sites, each from its own root, 
+        routes and proxies, to clients asking for them in the Host header 
+
+    /greet?name=kim renders templates/greet.html (see template.rs); the 
+        templates directory is compiled once, before the first req 
+
+    POST /upload accepts urlencoded or multipart forms of up to 1 MB and 
+        describes them: 
+        curl -F title=hi -F doc=@hello.html http://127.0.0.1:7878/upload 
 */
 
 use std::{
+    env, 
+    error::Error, 
     fs, 
-    io::{prelude::*, BufReader}, 
-    net::{TcpListener, TcpStream}, 
+    net::TcpListener, 
+    path::PathBuf, 
+    process, 
+    sync::Arc, 
     thread, 
     time::Duration, 
 }; 
 
-use hello::ThreadPool; 
+use hello::{
+    config::{Config, HostSettings, ProxySettings}, 
+    event, 
+    files::{self, StaticFiles}, 
+    form, 
+    http::{Request, Response}, 
+    proxy::Proxy, 
+    router::Router, 
+    server::{self, Mode}, 
+    template::{Context, Templates}, 
+    tls, 
+    websocket::Message, 
+    ThreadPool, 
+}; 
 
 fn main() {
-    let listener = TcpListener::bind("127.0.0.1:7878").unwrap(); 
-    let pool = ThreadPool::new(4); 
+    let args = parse_args(env::args().skip(1)).unwrap_or_else(|err| {
+        eprintln!("Problem parsing arguments: {err}"); 
+        process::exit(1); 
+    }); 
 
-    // to accept only two reqs before gracefully shutting down: 
-    for stream in listener.incoming().take(2) {
-        let stream = stream.unwrap(); 
+    let mut config = match &args.config {
+        Some(path) => Config::load(path).unwrap_or_else(|err| {
+            eprintln!("{}: {err}", path.display()); 
+            process::exit(1); 
+        }), 
+        None => Config::default(), 
+    }; 
+    // the command line wins over the config file 
+    if let Some(mode) = args.mode {
+        config.mode = mode; 
+    }
 
-        // takes the closure and gives it to a thread in the pool
-        pool.execute(|| {
-            handle_connection(stream); 
-        }); 
+    if let Err(e) = run(config) {
+        eprintln!("Application error: {e}"); 
+        process::exit(1); 
     }
 
     println!("Shutting down.")
 }
 
-fn handle_connection(mut stream: TcpStream) {
-    let buf_reader = BufReader::new(&stream); 
-    // collect the lines of the req the browser sends 
-    // let http_request: Vec<_> = buf_reader 
-    //    .lines() 
-    //    .map(|result| result.unwrap()) 
-    //    .take_while(|line| !line.is_empty()) // till empty string 
-    //    .collect(); 
-    let request_line = buf_reader.lines().next().unwrap().unwrap(); 
-        // only look at the first line of the HTTP req 
-        // first unwrap takes care of the Option 
-        // second unwrap handles the Result 
-
-
-    // println!("Request: {http_request:#?}"); 
-
-    // #3: response that uses HTTP version 1.1 
-    // let response = "HTTP/1.1 200 OK\r\n\r\n"; 
-
-    // #5: handling requests to / 
-    let (status_line, filename) = match &request_line[..] {
-        "GET / HTTP/1.1" => ("HTTP/1.1 200 OK", "hello.html"),
-        "GET /sleep HTTP/1.1" => {
+// bind every configured listener and serve them all from one pool 
+fn run(config: Config) -> Result<(), Box<dyn Error>> {
+    let templates = Templates::load(&config.templates)
+        .map_err(|e| format!("{}: {e}", config.templates.display()))?; 
+    templates.install(); 
+
+    let pool = ThreadPool::new(config.workers); 
+    let router = Arc::new(app(&config)); 
+
+    let http = match &config.listen {
+        Some(addr) => Some(TcpListener::bind(addr)?), 
+        None => None, 
+    }; 
+    let https = match &config.tls {
+        Some(settings) => Some((
+            TcpListener::bind(&settings.listen)?, 
+            tls::load_config(&settings.cert, &settings.key)?, 
+        )), 
+        None => None, 
+    }; 
+
+    // scoped threads may borrow the pool, which outlives them 
+    thread::scope(|s| {
+        if let Some((listener, tls_config)) = https {
+            let router = Arc::clone(&router); 
+            let pool = &pool; 
+            s.spawn(move || tls::serve(listener, pool, router, tls_config)); 
+        }
+
+        match (http, config.mode) {
+            (Some(listener), Mode::Threaded) => server::serve(listener, &pool, router), 
+            (Some(listener), Mode::Event) => {
+                if let Err(e) = event::serve(listener, &pool, router) {
+                    eprintln!("event loop failed: {e}"); 
+                }
+            }
+            (None, _) => {}
+        }
+    }); 
+
+    Ok(())
+}
+
+#[derive(Default)]
+struct Args {
+    config: Option<PathBuf>, 
+    mode: Option<Mode>, 
+}
+
+// [--config <file>] [--mode <threaded|event>], also as --flag=value 
+fn parse_args(mut args: impl Iterator<Item = String>) -> Result<Args, String> {
+    let mut parsed = Args::default(); 
+
+    while let Some(arg) = args.next() {
+        let (flag, value) = match arg.split_once('=') {
+            Some((flag, value)) => (flag.to_string(), value.to_string()), 
+            None => {
+                let value = args.next().ok_or(format!("{arg} needs a value"))?; 
+                (arg, value)
+            }
+        }; 
+
+        match flag.as_str() {
+            "--config" => parsed.config = Some(PathBuf::from(value)), 
+            "--mode" => parsed.mode = Some(value.parse()?), 
+            _ => return Err(format!("unknown argument `{flag}`")), 
+        }
+    }
+
+    Ok(parsed)
+}
+
+// #5: handling requests to / 
+fn app(config: &Config) -> Router {
+    let files = config
+        .root
+        .as_ref()
+        .map(|root| StaticFiles::new(root).with_listing(config.listing)); 
+
+    let mut router = Router::new()
+        .get("/", |_| page(200, "hello.html"))
+        .get("/sleep", |_| {
             // server will sleep for 5 secs 
             thread::sleep(Duration::from_secs(5)); 
-            ("HTTP/1.1 200 OK", "hello.html") 
+            page(200, "hello.html")
+        })
+        .get("/greet", greet)
+        .post("/upload", upload)
+        .websocket("/echo", |_, mut ws| {
+            // runs until the client closes the connection 
+            while let Ok(message) = ws.recv() {
+                let echo = match message {
+                    Message::Text(_) | Message::Binary(_) => message, 
+                    Message::Close(_) => break, 
+                    _ => continue, 
+                }; 
+                if ws.send(echo).is_err() {
+                    break; 
+                }
+            }
+        })
+        .fallback(move |request| {
+            let response = files.as_ref().and_then(|files| files.serve(request)); 
+            response.unwrap_or_else(|| page(404, "404.html"))
+        })
+        // bodies are read whole before they are handled, uploads included 
+        .with_max_body_size(1024 * 1024); 
+
+    // [proxy /prefix] sections forward to other local services 
+    for settings in &config.proxies {
+        let proxy = proxy(settings); 
+        router = router.prefix(&settings.prefix, move |request| proxy.handle(request)); 
+    }
+    // [host name] sections are sites of their own, found by Host header 
+    for settings in &config.hosts {
+        let names: Vec<&str> = settings.names.iter().map(String::as_str).collect(); 
+        router = router.host(&names, site(settings)); 
+        if settings.default {
+            router = router.with_default_host(&settings.names[0]); 
         }
-        _ => ("HTTP/1.1 404 NOT FOUND", "404.html"),
+    }
+    if let Some(limits) = &config.limits {
+        router = router.with_limits(limits.clone()); 
+    }
+
+    router
+}
+
+// the Router of a [host] section: its routes, its proxies, then its files 
+fn site(settings: &HostSettings) -> Router {
+    let mut site = Router::new(); 
+    for route in &settings.routes {
+        let file = route.file.clone(); 
+        site = site.get(&route.path, move |_| files::file_response(&file)); 
+    }
+    for proxy_settings in &settings.proxies {
+        let proxy = proxy(proxy_settings); 
+        site = site.prefix(&proxy_settings.prefix, move |request| proxy.handle(request)); 
+    }
+
+    let files = settings
+        .root
+        .as_ref()
+        .map(|root| StaticFiles::new(root).with_listing(settings.listing)); 
+    site.fallback(move |request| {
+        let response = files.as_ref().and_then(|files| files.serve(request)); 
+        response.unwrap_or_else(|| page(404, "404.html"))
+    })
+}
+
+// a Proxy to the upstreams of `settings`, checked in the background 
+fn proxy(settings: &ProxySettings) -> Arc<Proxy> {
+    let mut proxy = Proxy::new(settings.upstreams.clone()); 
+    if let Some(path) = &settings.health_check {
+        proxy = proxy.with_health_check(path); 
+    }
+    let proxy = Arc::new(proxy); 
+    Proxy::spawn_health_checks(&proxy, settings.health_interval); 
+    proxy
+}
+
+// a page from templates/greet.html, listing the req's headers 
+fn greet(request: &Request) -> Response {
+    let query = request.query.as_deref().unwrap_or(""); 
+    let name = form::parse_urlencoded(query)
+        .into_iter()
+        .find(|(key, _)| key == "name")
+        .map(|(_, value)| value)
+        .unwrap_or_else(|| String::from("stranger")); 
+
+    let headers: Vec<Context> = request
+        .headers
+        .iter()
+        .map(|(name, value)| Context::new().with("name", name.as_str()).with("value", value.as_str()))
+        .collect(); 
+
+    Response::render("greet.html", Context::new().with("name", name).with("headers", headers))
+}
+
+// list what was posted: fields with their values, files with their sizes 
+fn upload(request: &Request) -> Response {
+    let form = match request.form() {
+        Ok(form) => form, 
+        Err(e) => return Response::text(400, e.to_string()), 
     }; 
 
-    let contents = fs::read_to_string(filename).unwrap(); 
-    let length = contents.len(); 
+    let mut summary = String::new(); 
+    for (name, value) in &form.fields {
+        summary.push_str(&format!("field {name} = {value}\n")); 
+    }
+    for file in &form.files {
+        let filename = file.filename.as_deref().unwrap_or("-"); 
+        summary.push_str(&format!("file {} ({filename}): {} bytes\n", file.name, file.size)); 
+    }
 
-    // #4: sending hello.html as the body of the response 
-    let response = 
-        format!("{status_line}\r\nContent-Length: {length}\r\n\r\n{contents}"); 
+    Response::text(200, summary)
+}
 
-    stream.write_all(response.as_bytes()).unwrap(); 
+// #4: sending an html file as the body of the response 
+fn page(status: u16, filename: &str) -> Response {
+    match fs::read_to_string(filename) {
+        Ok(contents) => Response::html(status, contents), 
+        Err(e) => Response::text(500, format!("cannot read {filename}: {e}")), 
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*; 
+    use hello::testing::TestClient; 
+    use std::io::{Read, Write}; 
+
+    #[test]
+    fn host_sections_serve_their_routes_and_proxies() {
+        // a one-shot upstream behind the site's proxy 
+        let upstream = TcpListener::bind("127.0.0.1:0").unwrap(); 
+        let addr = upstream.local_addr().unwrap(); 
+        let handle = thread::spawn(move || {
+            let (mut stream, _) = upstream.accept().unwrap(); 
+            let mut buf = vec![0; 4096]; 
+            let n = stream.read(&mut buf).unwrap(); 
+            stream.write_all(b"HTTP/1.1 200 OK\r\nContent-Length: 8\r\n\r\nupstream").unwrap(); 
+            String::from_utf8_lossy(&buf[..n]).into_owned()
+        }); 
+
+        let dir = env::temp_dir().join(format!("hello-site-test-{}", process::id())); 
+        fs::create_dir_all(&dir).unwrap(); 
+        fs::write(dir.join("about.html"), "<p>about the blog</p>").unwrap(); 
+        let text = format!("[host blog.test]\nroute = /about -> about.html\nproxy = /api -> {addr}\n"); 
+        let config = Config::parse(&text, &dir).unwrap(); 
+        let client = TestClient::new(app(&config)); 
+
+        client
+            .get("/about")
+            .header("Host", "blog.test")
+            .send()
+            .assert_status(200)
+            .assert_body("<p>about the blog</p>"); 
+        client
+            .get("/api/posts?page=2")
+            .header("Host", "blog.test:7878")
+            .send()
+            .assert_status(200)
+            .assert_body("upstream"); 
+        let sent = handle.join().unwrap(); 
+        assert!(sent.starts_with("GET /api/posts?page=2 HTTP/1.1\r\n"), "{sent}"); 
+
+        // the main site has neither 
+        client.get("/about").send().assert_status(404); 
+        fs::remove_dir_all(&dir).unwrap(); 
+    }
 }
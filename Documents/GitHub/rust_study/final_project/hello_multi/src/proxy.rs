@@ -0,0 +1,333 @@
+/*
+    reverse proxy: forward reqs for a path prefix to other servers 
+        the req goes out almost unchanged over a fresh connection to the 
+        upstream, with a few headers rewritten on the way: 
+            Host                 the upstream's address 
+            X-Forwarded-For      the client's IP appended to any chain 
+            X-Forwarded-Host     the Host the client originally asked for 
+        hop-by-hop headers (Connection, Keep-Alive, Transfer-Encoding...) 
+        only describe one connection, so they are never passed along, in 
+        either direction; nor are the headers the Connection header names, 
+        which the sender declared hop-by-hop too (RFC 9110, 7.6.1) 
+
+        the upstream's response body is not buffered: it becomes a 
+        streamed Body that is copied to the client as it arrives, in 
+        either mode (chunked upstream bodies are decoded and re-chunked) 
+
+        the req body is not streamed: it is read whole into memory before 
+        the handler runs, so a proxied upload is limited to the Router's 
+        max_body_size (1 MiB from server.conf), and a bigger one is 
+        answered 413 without reaching the upstream 
+
+    several upstreams can serve the same prefix: they take turns (round 
+        robin), skipping the ones marked unhealthy; an upstream is marked 
+        unhealthy when connecting to it fails, and a background thread 
+        checks every upstream periodically (a TCP connect, or a GET of a 
+        health check path answering 2xx/3xx) to bring it back 
+*/
+
+use std::{
+    io::{self, BufReader, ErrorKind, Write}, 
+    net::{TcpStream, ToSocketAddrs}, 
+    sync::{
+        atomic::{AtomicBool, AtomicUsize, Ordering}, 
+        Arc, Weak, 
+    }, 
+    thread, 
+    time::Duration, 
+}; 
+
+use crate::http::{self, Request, Response}; 
+
+const CONNECT_TIMEOUT: Duration = Duration::from_secs(3); 
+const READ_TIMEOUT: Duration = Duration::from_secs(30); 
+
+// headers that only apply to a single connection 
+const HOP_BY_HOP: [&str; 8] = [
+    "Connection", 
+    "Keep-Alive", 
+    "Proxy-Authenticate", 
+    "Proxy-Authorization", 
+    "TE", 
+    "Trailer", 
+    "Transfer-Encoding", 
+    "Upgrade", 
+]; 
+
+pub struct Proxy {
+    upstreams: Vec<Upstream>, 
+    next: AtomicUsize, 
+    health_check: Option<String>, 
+}
+
+struct Upstream {
+    addr: String, 
+    healthy: AtomicBool, 
+}
+
+impl Proxy {
+    /// Create a proxy spreading requests over `upstreams` (`host:port` each). 
+    /// 
+    /// # Panics 
+    /// 
+    /// The `new` function will panic if `upstreams` is empty. 
+    pub fn new(upstreams: Vec<String>) -> Proxy {
+        assert!(!upstreams.is_empty()); 
+
+        Proxy {
+            upstreams: upstreams
+                .into_iter()
+                .map(|addr| Upstream {
+                    addr, 
+                    healthy: AtomicBool::new(true), 
+                })
+                .collect(), 
+            next: AtomicUsize::new(0), 
+            health_check: None, 
+        }
+    }
+
+    /// Check health with a GET of `path` instead of a bare TCP connect. 
+    pub fn with_health_check(mut self, path: &str) -> Proxy {
+        self.health_check = Some(path.to_string()); 
+        self
+    }
+
+    /// Check every upstream each `interval` on a background thread. 
+    /// 
+    /// The thread only holds a weak reference and stops with the proxy. 
+    pub fn spawn_health_checks(proxy: &Arc<Proxy>, interval: Duration) {
+        let proxy: Weak<Proxy> = Arc::downgrade(proxy); 
+
+        thread::spawn(move || loop {
+            thread::sleep(interval); 
+            let Some(proxy) = proxy.upgrade() else {
+                return; 
+            }; 
+            for upstream in &proxy.upstreams {
+                let healthy = proxy.check(upstream); 
+                if healthy != upstream.healthy.swap(healthy, Ordering::Relaxed) {
+                    let state = if healthy { "healthy" } else { "unhealthy" }; 
+                    println!("upstream {} is {state}", upstream.addr); 
+                }
+            }
+        }); 
+    }
+
+    /// Whether the upstream at `addr` is currently considered healthy. 
+    pub fn is_healthy(&self, addr: &str) -> Option<bool> {
+        self.upstreams
+            .iter()
+            .find(|u| u.addr == addr)
+            .map(|u| u.healthy.load(Ordering::Relaxed))
+    }
+
+    /// Forward `request` to the next healthy upstream. 
+    pub fn handle(&self, request: &Request) -> Response {
+        let count = self.upstreams.len(); 
+        let start = self.next.fetch_add(1, Ordering::Relaxed); 
+
+        for i in 0..count {
+            let upstream = &self.upstreams[(start + i) % count]; 
+            if !upstream.healthy.load(Ordering::Relaxed) {
+                continue; 
+            }
+
+            let stream = match connect(&upstream.addr) {
+                Ok(stream) => stream, 
+                Err(e) => {
+                    // leave it to the health checks to bring it back 
+                    eprintln!("upstream {} unreachable: {e}", upstream.addr); 
+                    upstream.healthy.store(false, Ordering::Relaxed); 
+                    continue; 
+                }
+            }; 
+
+            return match forward(stream, &upstream.addr, request) {
+                Ok(response) => response, 
+                Err(e) => Response::text(502, format!("bad response from upstream: {e}")), 
+            }; 
+        }
+
+        Response::text(502, "no healthy upstream")
+    }
+
+    fn check(&self, upstream: &Upstream) -> bool {
+        let Ok(stream) = connect(&upstream.addr) else {
+            return false; 
+        }; 
+        let Some(path) = &self.health_check else {
+            return true; 
+        }; 
+
+        match forward(stream, &upstream.addr, &Request::new("GET", path)) {
+            Ok(response) => (200..400).contains(&response.status), 
+            Err(_) => false, 
+        }
+    }
+}
+
+fn connect(addr: &str) -> io::Result<TcpStream> {
+    let addr = addr
+        .to_socket_addrs()?
+        .next()
+        .ok_or_else(|| io::Error::new(ErrorKind::NotFound, "address did not resolve"))?; 
+
+    let stream = TcpStream::connect_timeout(&addr, CONNECT_TIMEOUT)?; 
+    stream.set_read_timeout(Some(READ_TIMEOUT))?; 
+    Ok(stream)
+}
+
+// send the (buffered) req to the upstream and turn its answer into a streamed Response 
+fn forward(mut stream: TcpStream, upstream: &str, request: &Request) -> io::Result<Response> {
+    let target = match &request.query {
+        Some(query) => format!("{}?{query}", request.path), 
+        None => request.path.clone(), 
+    }; 
+    let mut head = format!("{} {target} HTTP/1.1\r\nHost: {upstream}\r\n", request.method); 
+
+    let named = connection_options(&request.headers); 
+    for (name, value) in &request.headers {
+        let skip = ["Host", "Content-Length", "X-Forwarded-For", "X-Forwarded-Host"]; 
+        if !is_hop_by_hop(name, &named) && !skip.iter().any(|s| name.eq_ignore_ascii_case(s)) {
+            head.push_str(&format!("{name}: {value}\r\n")); 
+        }
+    }
+
+    let forwarded_for = match (request.header("X-Forwarded-For"), request.remote_addr) {
+        (Some(chain), Some(addr)) => Some(format!("{chain}, {}", addr.ip())), 
+        (Some(chain), None) => Some(chain.to_string()), 
+        (None, Some(addr)) => Some(addr.ip().to_string()), 
+        (None, None) => None, 
+    }; 
+    if let Some(value) = forwarded_for {
+        head.push_str(&format!("X-Forwarded-For: {value}\r\n")); 
+    }
+    if let Some(host) = request.header("Host") {
+        head.push_str(&format!("X-Forwarded-Host: {host}\r\n")); 
+    }
+    head.push_str(&format!("Content-Length: {}\r\nConnection: close\r\n\r\n", request.body.len())); 
+
+    stream.write_all(head.as_bytes())?; 
+    stream.write_all(&request.body)?; 
+    stream.flush()?; 
+
+    let mut response = http::read_response(BufReader::new(stream), request.method == "HEAD")?; 
+    if request.method == "HEAD" {
+        // no body follows, but the client is told the length a GET would get 
+        let length = response.header("Content-Length").and_then(|v| v.parse().ok()); 
+        response = response.with_stream(io::empty(), length); 
+    }
+    // the body is framed again when it is written to our client 
+    let named = connection_options(&response.headers); 
+    response.headers.retain(|(name, _)| {
+        !is_hop_by_hop(name, &named) && !name.eq_ignore_ascii_case("Content-Length")
+    }); 
+    Ok(response)
+}
+
+// the header names listed in the Connection headers, `Connection: close, X-Private` 
+fn connection_options(headers: &[(String, String)]) -> Vec<String> {
+    headers
+        .iter()
+        .filter(|(name, _)| name.eq_ignore_ascii_case("Connection"))
+        .flat_map(|(_, value)| value.split(','))
+        .map(|option| option.trim().to_string())
+        .filter(|option| !option.is_empty())
+        .collect()
+}
+
+fn is_hop_by_hop(name: &str, named: &[String]) -> bool {
+    HOP_BY_HOP.iter().any(|h| name.eq_ignore_ascii_case(h))
+        || named.iter().any(|n| name.eq_ignore_ascii_case(n))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*; 
+    use crate::http::ChunkedReader; 
+    use std::{io::Read, net::TcpListener}; 
+
+    // a one-shot upstream that records the req it got and answers `reply` 
+    fn upstream(reply: &'static str) -> (String, thread::JoinHandle<String>) {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap(); 
+        let addr = listener.local_addr().unwrap().to_string(); 
+
+        let handle = thread::spawn(move || {
+            let (mut stream, _) = listener.accept().unwrap(); 
+            let mut buf = vec![0; 4096]; 
+            let n = stream.read(&mut buf).unwrap(); 
+            stream.write_all(reply.as_bytes()).unwrap(); 
+            String::from_utf8_lossy(&buf[..n]).into_owned()
+        }); 
+        (addr, handle)
+    }
+
+    #[test]
+    fn rewrites_headers_and_streams_the_body() {
+        let (addr, handle) = upstream("HTTP/1.1 200 OK\r\nTransfer-Encoding: chunked\r\nX-Up: 1\r\n\r\n5\r\nhello\r\n0\r\n\r\n"); 
+        let proxy = Proxy::new(vec![addr.clone()]); 
+
+        let mut request = Request::new("GET", "/api/items?page=2"); 
+        request.headers.push((String::from("Host"), String::from("example.com"))); 
+        request.headers.push((String::from("X-Forwarded-For"), String::from("10.0.0.1"))); 
+        request.remote_addr = Some("192.168.1.5:4000".parse().unwrap()); 
+
+        let response = proxy.handle(&request); 
+        assert_eq!(response.status, 200); 
+        assert_eq!(response.header("X-Up"), Some("1")); 
+        assert_eq!(response.header("Transfer-Encoding"), None); 
+
+        let bytes = response.into_bytes().unwrap(); 
+        let body_start = http::find(&bytes, b"\r\n\r\n").unwrap() + 4; 
+        let mut body = String::new(); 
+        ChunkedReader::new(&bytes[body_start..]).read_to_string(&mut body).unwrap(); 
+        assert_eq!(body, "hello"); 
+
+        let sent = handle.join().unwrap(); 
+        assert!(sent.starts_with("GET /api/items?page=2 HTTP/1.1\r\n")); 
+        assert!(sent.contains(&format!("Host: {addr}\r\n"))); 
+        assert!(sent.contains("X-Forwarded-For: 10.0.0.1, 192.168.1.5\r\n")); 
+        assert!(sent.contains("X-Forwarded-Host: example.com\r\n")); 
+    }
+
+    #[test]
+    fn drops_the_headers_named_in_connection() {
+        let (addr, handle) = upstream("HTTP/1.1 200 OK\r\nConnection: X-Up-Private\r\nX-Up-Private: 1\r\nX-Up: 1\r\nContent-Length: 0\r\n\r\n"); 
+        let proxy = Proxy::new(vec![addr]); 
+
+        let mut request = Request::new("GET", "/"); 
+        request.headers.push((String::from("Connection"), String::from("keep-alive, x-private"))); 
+        request.headers.push((String::from("Connection"), String::from("Old-Option"))); 
+        request.headers.push((String::from("X-Private"), String::from("secret"))); 
+        request.headers.push((String::from("Old-Option"), String::from("1"))); 
+        request.headers.push((String::from("X-Public"), String::from("1"))); 
+
+        let response = proxy.handle(&request); 
+        assert_eq!(response.header("X-Up"), Some("1")); 
+        assert_eq!(response.header("X-Up-Private"), None); 
+        assert_eq!(response.header("Connection"), None); 
+
+        let sent = handle.join().unwrap(); 
+        assert!(sent.contains("X-Public: 1\r\n")); 
+        assert!(!sent.contains("X-Private"), "{sent}"); 
+        assert!(!sent.contains("Old-Option"), "{sent}"); 
+        assert!(!sent.contains("keep-alive"), "{sent}"); 
+    }
+
+    #[test]
+    fn skips_upstreams_that_are_down() {
+        // bind then drop, so nothing listens on this port any more 
+        let dead = TcpListener::bind("127.0.0.1:0").unwrap().local_addr().unwrap().to_string(); 
+        let (alive, handle) = upstream("HTTP/1.1 204 No Content\r\n\r\n"); 
+        let proxy = Proxy::new(vec![dead.clone(), alive]); 
+
+        assert_eq!(proxy.handle(&Request::new("GET", "/")).status, 204); 
+        assert_eq!(proxy.is_healthy(&dead), Some(false)); 
+        handle.join().unwrap(); 
+
+        // with every upstream down the client gets a 502 
+        let proxy = Proxy::new(vec![dead]); 
+        assert_eq!(proxy.handle(&Request::new("GET", "/")).status, 502); 
+    }
+}
@@ -0,0 +1,256 @@
+/*
+    Router maps a req to the handler that builds its response 
+        handlers are stored as trait objects so that every route can 
+        hold a different closure; they must be Send + Sync because one 
+        Router is shared (via Arc) by all the workers of the ThreadPool 
+
+        routes are matched on the method and the exact path, in the order 
+        they were added; then prefix routes (any method, the path itself 
+        or anything below it), the longest prefix first; if nothing 
+        matches, the fallback handler runs 
+
+        socket handlers are kept apart: they receive the upgraded 
+        connection instead of returning a Response, and only see reqs 
+        that actually ask for a WebSocket upgrade 
+
+        virtual hosts are whole Routers of their own, picked by the Host 
+        header (port removed, case ignored, *.example.com for any 
+        subdomain) before anything else is matched; a req for an unknown 
+        host goes to the default host, if one is set, or else to the 
+        routes of this Router itself; a req is read and its rate checked 
+        before its host is known, so the body size and limits of the top 
+        Router apply to every host, and those set on a host's own Router 
+        are never looked at 
+
+        the Router also decides how large a req body its handlers are 
+        willing to receive; bigger reqs never reach them (413), and it 
+        carries the per-client limits the servers enforce (see limit.rs) 
+*/
+
+use std::sync::Arc; 
+
+use crate::{
+    http::{Request, Response, DEFAULT_MAX_BODY_SIZE}, 
+    limit::{Limiter, Limits}, 
+    websocket::{self, WebSocket}, 
+}; 
+
+pub type Handler = Box<dyn Fn(&Request) -> Response + Send + Sync + 'static>; 
+pub type SocketHandler = Box<dyn Fn(&Request, WebSocket) + Send + Sync + 'static>; 
+
+struct Route {
+    method: String, 
+    path: String, 
+    handler: Handler, 
+}
+
+pub struct Router {
+    routes: Vec<Route>, 
+    prefixes: Vec<(String, Handler)>, 
+    sockets: Vec<(String, SocketHandler)>, 
+    fallback: Handler, 
+    max_body_size: usize, 
+    limiter: Option<Arc<Limiter>>, 
+    // each virtual host's names (lowercase) and routes 
+    hosts: Vec<(Vec<String>, Router)>, 
+    default_host: Option<String>, 
+}
+
+impl Router {
+    /// Create a Router whose fallback answers 404 Not Found. 
+    pub fn new() -> Router {
+        Router {
+            routes: Vec::new(), 
+            prefixes: Vec::new(), 
+            sockets: Vec::new(), 
+            fallback: Box::new(|_| Response::text(404, "Not Found")), 
+            max_body_size: DEFAULT_MAX_BODY_SIZE, 
+            limiter: None, 
+            hosts: Vec::new(), 
+            default_host: None, 
+        }
+    }
+
+    /// Add a handler for `method` requests to exactly `path`. 
+    pub fn route<F>(mut self, method: &str, path: &str, handler: F) -> Router
+    where
+        F: Fn(&Request) -> Response + Send + Sync + 'static, 
+    {
+        self.routes.push(Route {
+            method: method.to_string(), 
+            path: path.to_string(), 
+            handler: Box::new(handler), 
+        }); 
+        self
+    }
+
+    pub fn get<F>(self, path: &str, handler: F) -> Router
+    where
+        F: Fn(&Request) -> Response + Send + Sync + 'static, 
+    {
+        self.route("GET", path, handler)
+    }
+
+    pub fn post<F>(self, path: &str, handler: F) -> Router
+    where
+        F: Fn(&Request) -> Response + Send + Sync + 'static, 
+    {
+        self.route("POST", path, handler)
+    }
+
+    /// Send every request for `prefix` or a path below it to `handler`. 
+    pub fn prefix<F>(mut self, prefix: &str, handler: F) -> Router
+    where
+        F: Fn(&Request) -> Response + Send + Sync + 'static, 
+    {
+        self.prefixes.push((prefix.trim_end_matches('/').to_string(), Box::new(handler))); 
+        // keep the most specific prefix first 
+        self.prefixes.sort_by_key(|(p, _)| std::cmp::Reverse(p.len())); 
+        self
+    }
+
+    /// Accept WebSocket upgrades on `path` and pass the connection to `handler`. 
+    pub fn websocket<F>(mut self, path: &str, handler: F) -> Router
+    where
+        F: Fn(&Request, WebSocket) + Send + Sync + 'static, 
+    {
+        self.sockets.push((path.to_string(), Box::new(handler))); 
+        self
+    }
+
+    /// Serve requests for any of `names` with `router` instead. 
+    /// 
+    /// Body size and limits still come from this Router, not from `router`. 
+    pub fn host(mut self, names: &[&str], router: Router) -> Router {
+        let names = names.iter().map(|name| name.to_ascii_lowercase()).collect(); 
+        self.hosts.push((names, router)); 
+        self
+    }
+
+    /// Send requests for unknown hosts to the virtual host called `name`. 
+    pub fn with_default_host(mut self, name: &str) -> Router {
+        self.default_host = Some(name.to_ascii_lowercase()); 
+        self
+    }
+
+    /// Replace the handler used when no route matches. 
+    pub fn fallback<F>(mut self, handler: F) -> Router
+    where
+        F: Fn(&Request) -> Response + Send + Sync + 'static, 
+    {
+        self.fallback = Box::new(handler); 
+        self
+    }
+
+    /// Limit the size of request bodies; larger requests get 413. 
+    /// 
+    /// A body is held whole in memory before any handler runs, so this 
+    /// bounds the memory each request may take; `FormLimits` only decides 
+    /// which multipart parts are spilled to disk after that. 
+    pub fn with_max_body_size(mut self, bytes: usize) -> Router {
+        self.max_body_size = bytes; 
+        self
+    }
+
+    pub fn max_body_size(&self) -> usize {
+        self.max_body_size
+    }
+
+    /// Enforce per-client rate and connection limits; see `Limits`. 
+    pub fn with_limits(mut self, limits: Limits) -> Router {
+        self.limiter = Some(Arc::new(Limiter::new(limits))); 
+        self
+    }
+
+    pub fn limiter(&self) -> Option<&Arc<Limiter>> {
+        self.limiter.as_ref()
+    }
+
+    /// The Router serving the host `request` is for (maybe this one). 
+    pub fn select(&self, request: &Request) -> &Router {
+        if self.hosts.is_empty() {
+            return self; 
+        }
+        let host = request.header("Host").map(host_name).unwrap_or_default(); 
+
+        let find = |host: &str| {
+            self.hosts
+                .iter()
+                .find(|(names, _)| names.iter().any(|name| host_matches(name, host)))
+                .map(|(_, router)| router)
+        }; 
+        find(&host)
+            .or_else(|| self.default_host.as_deref().and_then(find))
+            .unwrap_or(self)
+    }
+
+    /// Run the handler matching the request. 
+    pub fn handle(&self, request: &Request) -> Response {
+        let router = self.select(request); 
+        if !std::ptr::eq(router, self) {
+            return router.handle(request); 
+        }
+
+        let route = self
+            .routes
+            .iter()
+            .find(|r| r.method == request.method && r.path == request.path); 
+
+        if let Some(route) = route {
+            return (route.handler)(request); 
+        }
+
+        let prefix = self.prefixes.iter().find(|(prefix, _)| {
+            match request.path.strip_prefix(prefix.as_str()) {
+                Some(rest) => rest.is_empty() || rest.starts_with('/'), 
+                None => false, 
+            }
+        }); 
+
+        match prefix {
+            Some((_, handler)) => handler(request), 
+            None => (self.fallback)(request), 
+        }
+    }
+
+    /// The socket handler for an upgrade request, if one is registered. 
+    pub fn socket_handler(&self, request: &Request) -> Option<&SocketHandler> {
+        if !websocket::is_upgrade(request) {
+            return None; 
+        }
+        let router = self.select(request); 
+        if !std::ptr::eq(router, self) {
+            return router.socket_handler(request); 
+        }
+        self.sockets
+            .iter()
+            .find(|(path, _)| *path == request.path)
+            .map(|(_, handler)| handler)
+    }
+}
+
+// the host name from a Host header: lowercase, without port or final dot 
+fn host_name(header: &str) -> String {
+    let header = header.trim(); 
+    let name = match header.strip_prefix('[') {
+        // [::1]:7878 
+        Some(rest) => rest.split(']').next().unwrap_or(""), 
+        None => header.split(':').next().unwrap_or(""), 
+    }; 
+    name.trim_end_matches('.').to_ascii_lowercase()
+}
+
+fn host_matches(pattern: &str, host: &str) -> bool {
+    match pattern.strip_prefix("*.") {
+        Some(domain) => host
+            .strip_suffix(domain)
+            .is_some_and(|sub| sub.len() > 1 && sub.ends_with('.')), 
+        None => pattern == host, 
+    }
+}
+
+impl Default for Router {
+    fn default() -> Router {
+        Router::new()
+    }
+}
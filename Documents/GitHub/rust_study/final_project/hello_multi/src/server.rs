@@ -0,0 +1,224 @@
+/*
+    connection handling modes, selected at startup 
+        Threaded: every accepted connection becomes a job for the pool; 
+        the worker blocks reading the req, runs the handler, writes the 
+        response and closes the connection (one req per connection) 
+
+        Event: a single thread owns every socket and waits for readiness 
+        (see event.rs), so idle keep-alive clients cost no worker at all; 
+        only complete reqs are handed to the pool 
+
+    handle_connection is generic over Read + Write so that it does not 
+        care whether it talks to a TcpStream or something else 
+
+    a req for a path registered with Router::websocket is not answered 
+        here: after the handshake, the connection is given to the socket 
+        handler, which keeps the worker until it returns 
+
+    with limits set on the Router, a connection over them is refused 
+        right on the accept thread (a 429/503 written straight away), and 
+        every req spends a token of its client's bucket before it is run 
+*/
+
+use std::{
+    io::{self, Read, Write}, 
+    net::{SocketAddr, TcpListener, TcpStream}, 
+    str::FromStr, 
+    sync::Arc, 
+}; 
+
+use crate::{
+    http::{self, ParseError, Request, Response}, 
+    limit::{Permit, Rejection}, 
+    router::{Router, SocketHandler}, 
+    websocket::{self, WebSocket}, 
+    ThreadPool, 
+}; 
+
+/// Anything a connection can be served over. 
+pub trait Stream: Read + Write + Send + 'static {}
+
+impl<T: Read + Write + Send + 'static> Stream for T {}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Mode {
+    Threaded, 
+    Event, 
+}
+
+impl FromStr for Mode {
+    type Err = String; 
+
+    fn from_str(s: &str) -> Result<Mode, String> {
+        match s {
+            "threaded" | "thread" => Ok(Mode::Threaded), 
+            "event" | "epoll" => Ok(Mode::Event), 
+            _ => Err(format!("unknown mode `{s}` (expected `threaded` or `event`)")), 
+        }
+    }
+}
+
+/// Accept connections forever, handing each one to the pool as a job. 
+pub fn serve(listener: TcpListener, pool: &ThreadPool, router: Arc<Router>) {
+    for stream in listener.incoming() {
+        let stream = match stream {
+            Ok(stream) => stream, 
+            Err(e) => {
+                eprintln!("accept failed: {e}"); 
+                continue; 
+            }
+        }; 
+        let permit = match admit(&stream, &router) {
+            Ok(permit) => permit, 
+            Err(rejection) => {
+                let _ = rejection.response().with_header("Connection", "close").write_to(&mut &stream); 
+                continue; 
+            }
+        }; 
+        let router = Arc::clone(&router); 
+        let peer = stream.peer_addr().ok(); 
+
+        // takes the closure and gives it to a thread in the pool 
+        pool.execute(move || {
+            // the slot stays taken until the connection is done 
+            let _permit = permit; 
+            if let Err(e) = handle_connection(stream, peer, &router) {
+                eprintln!("connection error: {e}"); 
+            }
+        }); 
+    }
+}
+
+/// Take a connection slot for a freshly accepted `stream`, if limits are set. 
+pub fn admit(stream: &TcpStream, router: &Router) -> Result<Option<Permit>, Rejection> {
+    match (router.limiter(), stream.peer_addr()) {
+        (Some(limiter), Ok(peer)) => limiter.open(peer.ip()).map(Some), 
+        _ => Ok(None), 
+    }
+}
+
+/// Read one request from `stream` (coming from `peer`), answer it and close. 
+pub fn handle_connection<S: Stream>(mut stream: S, peer: Option<SocketAddr>, router: &Router) -> io::Result<()> {
+    let (mut request, rest) = match read_request(&mut stream, router.max_body_size())? {
+        Ok(Some(read)) => read, 
+        // the client went away before sending a full req 
+        Ok(None) => return Ok(()), 
+        Err(e) => {
+            return error_response(&e)
+                .with_header("Connection", "close")
+                .write_to(&mut stream); 
+        }
+    }; 
+
+    request.remote_addr = peer; 
+    if let Err(rejection) = check_rate(&request, router) {
+        return rejection
+            .response()
+            .with_header("Connection", "close")
+            .write_for(&request, &mut stream); 
+    }
+    if let Some(handler) = router.socket_handler(&request) {
+        return upgrade(stream, rest, &request, handler); 
+    }
+
+    router
+        .handle(&request)
+        .with_header("Connection", "close")
+        .write_for(&request, &mut stream)
+}
+
+/// Answer the WebSocket handshake and run `handler` on the connection. 
+/// 
+/// `rest` holds whatever the client sent after the handshake request, 
+/// which the socket reads before anything else. 
+pub fn upgrade<S: Stream>(
+    mut stream: S, 
+    rest: Vec<u8>, 
+    request: &Request, 
+    handler: &SocketHandler, 
+) -> io::Result<()> {
+    match websocket::handshake(request) {
+        Ok(response) => {
+            response.write_to(&mut stream)?; 
+            let stream = Unread { rest: io::Cursor::new(rest), stream }; 
+            handler(request, WebSocket::new(stream)); 
+            Ok(())
+        }
+        Err(response) => response
+            .with_header("Connection", "close")
+            .write_to(&mut stream), 
+    }
+}
+
+/// A request and the bytes read past its end. 
+pub type Received = (Request, Vec<u8>); 
+
+/// A stream with bytes already read off it put back in front. 
+struct Unread<S> {
+    rest: io::Cursor<Vec<u8>>, 
+    stream: S, 
+}
+
+impl<S: Read> Read for Unread<S> {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        match self.rest.read(buf)? {
+            0 => self.stream.read(buf), 
+            n => Ok(n), 
+        }
+    }
+}
+
+impl<S: Write> Write for Unread<S> {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        self.stream.write(buf)
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        self.stream.flush()
+    }
+}
+
+/// Read from `stream` until one complete request has arrived. 
+/// 
+/// The outer `Result` carries I/O errors, the inner one parse errors; 
+/// `Ok(None)` means the peer closed the connection first. Bytes read 
+/// past the end of the request come back alongside it. 
+pub fn read_request<S: Read>(
+    stream: &mut S, 
+    max_body_size: usize, 
+) -> io::Result<Result<Option<Received>, ParseError>> {
+    let mut buf = Vec::new(); 
+    let mut chunk = [0; 4096]; 
+
+    loop {
+        match http::parse_request(&buf, max_body_size) {
+            Ok(Some((request, used))) => return Ok(Ok(Some((request, buf.split_off(used))))), 
+            Ok(None) => {}
+            Err(e) => return Ok(Err(e)), 
+        }
+
+        let n = stream.read(&mut chunk)?; 
+        if n == 0 {
+            return Ok(Ok(None)); 
+        }
+        buf.extend_from_slice(&chunk[..n]); 
+    }
+}
+
+/// Spend a token of the request's client, if its rate is limited. 
+pub fn check_rate(request: &Request, router: &Router) -> Result<(), Rejection> {
+    match (router.limiter(), request.remote_addr) {
+        (Some(limiter), Some(peer)) => limiter.check(peer.ip()), 
+        _ => Ok(()), 
+    }
+}
+
+/// The response sent back when a request could not be parsed. 
+pub fn error_response(error: &ParseError) -> Response {
+    match error {
+        ParseError::HeadTooLarge => Response::text(431, error.to_string()), 
+        ParseError::BodyTooLarge => Response::text(413, error.to_string()), 
+        ParseError::Malformed(_) => Response::text(400, error.to_string()), 
+        ParseError::UnsupportedEncoding => Response::text(501, error.to_string()), 
+    }
+}
@@ -0,0 +1,674 @@
+/*
+    HTML templates: pages with holes filled in per req 
+        {{ user.name }}             a value from the context, HTML-escaped 
+        {{ snippet | raw }}         the same without escaping 
+        {% if cond %}..{% else %}..{% endif %}     also {% if not cond %} 
+        {% for item in items %}..{% endfor %}      loop.index (from 1), 
+                                                   loop.first, loop.last 
+        {% include "nav.html" %}    another template, same context 
+        {% extends "layout.html" %} with {% block name %}..{% endblock %}: 
+            the page is the layout, with the blocks the child redefines 
+        {# a comment #} 
+
+        values missing from the context are empty (and false); escaping 
+        by default means a value can never sneak markup into the page 
+
+    every template is parsed once, at startup (Templates::load), into a 
+        tree of nodes; a syntax error or an include of a template that 
+        does not exist stops the server before it serves anything 
+
+        the loaded set is installed once for the whole process, so that 
+        any handler can answer with Response::render(name, context) 
+*/
+
+use std::{
+    collections::{BTreeMap, HashMap}, 
+    fmt, fs, io, 
+    path::Path, 
+    sync::OnceLock, 
+}; 
+
+use crate::http::Response; 
+
+// includes and layouts nested deeper than this are taken as a cycle 
+const MAX_DEPTH: usize = 32; 
+
+static INSTALLED: OnceLock<Templates> = OnceLock::new(); 
+
+/// A value that can be put into a template. 
+#[derive(Debug, Clone, PartialEq)]
+pub enum Value {
+    Null, 
+    Bool(bool), 
+    Number(f64), 
+    Str(String), 
+    List(Vec<Value>), 
+    Map(BTreeMap<String, Value>), 
+}
+
+/// The named values a template is rendered with. 
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct Context {
+    values: BTreeMap<String, Value>, 
+}
+
+#[derive(Debug)]
+pub enum TemplateError {
+    Io(io::Error), 
+    // template name, line (starting at 1) and what is wrong 
+    Syntax(String, usize, String), 
+    Render(String, String), 
+}
+
+#[derive(Default)]
+pub struct Templates {
+    templates: HashMap<String, Template>, 
+}
+
+struct Template {
+    nodes: Vec<Node>, 
+    extends: Option<String>, 
+    // every include/extends with the line it is on, checked after loading 
+    references: Vec<(String, usize)>, 
+}
+
+enum Node {
+    Text(String), 
+    Var { path: Vec<String>, raw: bool }, 
+    If { negate: bool, path: Vec<String>, then: Vec<Node>, otherwise: Vec<Node> }, 
+    For { var: String, path: Vec<String>, body: Vec<Node> }, 
+    Include(String), 
+    Block(String, Vec<Node>), 
+}
+
+enum Token {
+    Text(String), 
+    Expr(String, usize), 
+    Tag(String, usize), 
+}
+
+// the tag that ended a run of nodes, and its line 
+type EndTag = (String, usize); 
+
+// loop variables, innermost last, on top of the context 
+struct Scope<'a> {
+    context: &'a Context, 
+    locals: Vec<(String, Value)>, 
+}
+
+impl Context {
+    pub fn new() -> Context {
+        Context::default()
+    }
+
+    /// Add (or replace) the value called `name`. 
+    pub fn with(mut self, name: &str, value: impl Into<Value>) -> Context {
+        self.values.insert(name.to_string(), value.into()); 
+        self
+    }
+}
+
+impl Templates {
+    pub fn new() -> Templates {
+        Templates::default()
+    }
+
+    /// Compile every file in `dir` (and below it), named by relative path. 
+    pub fn load(dir: impl AsRef<Path>) -> Result<Templates, TemplateError> {
+        let mut templates = Templates::new(); 
+        templates.add_dir(dir.as_ref(), "")?; 
+        templates.check()?; 
+        Ok(templates)
+    }
+
+    fn add_dir(&mut self, dir: &Path, prefix: &str) -> Result<(), TemplateError> {
+        for entry in fs::read_dir(dir)? {
+            let entry = entry?; 
+            let name = format!("{prefix}{}", entry.file_name().to_string_lossy()); 
+            if entry.file_type()?.is_dir() {
+                self.add_dir(&entry.path(), &format!("{name}/"))?; 
+            } else {
+                let source = fs::read_to_string(entry.path())?; 
+                self.add(&name, &source)?; 
+            }
+        }
+        Ok(())
+    }
+
+    /// Compile `source` and keep it as `name`. 
+    pub fn add(&mut self, name: &str, source: &str) -> Result<(), TemplateError> {
+        let tokens = tokenize(name, source)?; 
+        let mut template = Template {
+            nodes: Vec::new(), 
+            extends: None, 
+            references: Vec::new(), 
+        }; 
+        let mut pos = 0; 
+        let (nodes, end) = parse(name, &tokens, &mut pos, &[], &mut template)?; 
+        if let Some((tag, line)) = end {
+            return Err(syntax(name, line, format!("unexpected {{% {tag} %}}"))); 
+        }
+        template.nodes = nodes; 
+        self.templates.insert(name.to_string(), template); 
+        Ok(())
+    }
+
+    /// Make sure every include and layout refers to a known template. 
+    pub fn check(&self) -> Result<(), TemplateError> {
+        for (name, template) in &self.templates {
+            for (reference, line) in &template.references {
+                if !self.templates.contains_key(reference) {
+                    return Err(syntax(name, *line, format!("no template named \"{reference}\""))); 
+                }
+            }
+        }
+        Ok(())
+    }
+
+    /// Make these the templates used by `Response::render`. 
+    /// 
+    /// # Panics 
+    /// 
+    /// The `install` function will panic if templates were already installed. 
+    pub fn install(self) {
+        if INSTALLED.set(self).is_err() {
+            panic!("templates are already installed"); 
+        }
+    }
+
+    /// Render the template `name` with `context`. 
+    pub fn render(&self, name: &str, context: &Context) -> Result<String, TemplateError> {
+        let mut scope = Scope {
+            context, 
+            locals: Vec::new(), 
+        }; 
+        let mut out = String::new(); 
+        self.render_template(name, &mut scope, &mut out, 0)?; 
+        Ok(out)
+    }
+
+    fn get(&self, name: &str) -> Result<&Template, TemplateError> {
+        self.templates
+            .get(name)
+            .ok_or_else(|| TemplateError::Render(name.to_string(), String::from("no such template")))
+    }
+
+    fn render_template(&self, name: &str, scope: &mut Scope, out: &mut String, depth: usize) -> Result<(), TemplateError> {
+        if depth > MAX_DEPTH {
+            return Err(TemplateError::Render(name.to_string(), String::from("includes or layouts nest too deep"))); 
+        }
+
+        // walk up the layouts; the blocks of the most derived page win 
+        let mut blocks: HashMap<&str, &[Node]> = HashMap::new(); 
+        let mut template = self.get(name)?; 
+        let mut levels = 0; 
+        while let Some(parent) = &template.extends {
+            collect_blocks(&template.nodes, &mut blocks); 
+            template = self.get(parent)?; 
+            levels += 1; 
+            if levels > MAX_DEPTH {
+                return Err(TemplateError::Render(name.to_string(), String::from("layouts extend each other"))); 
+            }
+        }
+
+        self.render_nodes(&template.nodes, &blocks, scope, out, depth)
+    }
+
+    fn render_nodes(
+        &self, 
+        nodes: &[Node], 
+        blocks: &HashMap<&str, &[Node]>, 
+        scope: &mut Scope, 
+        out: &mut String, 
+        depth: usize, 
+    ) -> Result<(), TemplateError> {
+        for node in nodes {
+            match node {
+                Node::Text(text) => out.push_str(text), 
+                Node::Var { path, raw } => {
+                    let text = scope.lookup(path).to_string(); 
+                    if *raw {
+                        out.push_str(&text); 
+                    } else {
+                        out.push_str(&escape(&text)); 
+                    }
+                }
+                Node::If { negate, path, then, otherwise } => {
+                    let branch = if scope.lookup(path).is_truthy() != *negate { then } else { otherwise }; 
+                    self.render_nodes(branch, blocks, scope, out, depth)?; 
+                }
+                Node::For { var, path, body } => {
+                    let items = match scope.lookup(path) {
+                        Value::List(items) => items.clone(), 
+                        _ => Vec::new(), 
+                    }; 
+                    let count = items.len(); 
+                    for (i, item) in items.into_iter().enumerate() {
+                        let mut info = BTreeMap::new(); 
+                        info.insert(String::from("index"), Value::from(i + 1)); 
+                        info.insert(String::from("first"), Value::Bool(i == 0)); 
+                        info.insert(String::from("last"), Value::Bool(i + 1 == count)); 
+
+                        scope.locals.push((String::from("loop"), Value::Map(info))); 
+                        scope.locals.push((var.clone(), item)); 
+                        let result = self.render_nodes(body, blocks, scope, out, depth); 
+                        scope.locals.truncate(scope.locals.len() - 2); 
+                        result?; 
+                    }
+                }
+                Node::Include(name) => self.render_template(name, scope, out, depth + 1)?, 
+                Node::Block(name, default) => {
+                    let body = blocks.get(name.as_str()).copied().unwrap_or(default); 
+                    self.render_nodes(body, blocks, scope, out, depth)?; 
+                }
+            }
+        }
+        Ok(())
+    }
+}
+
+// every block in `nodes`, unless a more derived template defined it already 
+fn collect_blocks<'a>(nodes: &'a [Node], blocks: &mut HashMap<&'a str, &'a [Node]>) {
+    for node in nodes {
+        if let Node::Block(name, body) = node {
+            blocks.entry(name.as_str()).or_insert(body.as_slice()); 
+            collect_blocks(body, blocks); 
+        }
+    }
+}
+
+impl Scope<'_> {
+    fn lookup(&self, path: &[String]) -> &Value {
+        let (first, rest) = path.split_first().expect("paths are never empty"); 
+        let local = self.locals.iter().rev().find(|(name, _)| name == first); 
+        let mut value = match local {
+            Some((_, value)) => value, 
+            None => self.context.values.get(first).unwrap_or(&Value::Null), 
+        }; 
+
+        for key in rest {
+            value = match value {
+                Value::Map(map) => map.get(key).unwrap_or(&Value::Null), 
+                _ => &Value::Null, 
+            }; 
+        }
+        value
+    }
+}
+
+impl Response {
+    /// Render the installed template `name` into a 200 HTML response. 
+    /// 
+    /// Rendering failures are logged and answered with 500. 
+    pub fn render(name: &str, context: Context) -> Response {
+        let Some(templates) = INSTALLED.get() else {
+            return Response::text(500, "no templates installed"); 
+        }; 
+        match templates.render(name, &context) {
+            Ok(html) => Response::html(200, html), 
+            Err(e) => {
+                eprintln!("{e}"); 
+                Response::text(500, e.to_string())
+            }
+        }
+    }
+}
+
+// split the source into text, {{ expressions }} and {% tags %} 
+fn tokenize(name: &str, source: &str) -> Result<Vec<Token>, TemplateError> {
+    let mut tokens = Vec::new(); 
+    let mut rest = source; 
+    let mut line = 1; 
+
+    while let Some(start) = rest.find('{') {
+        let close = match rest[start..].chars().nth(1) {
+            Some('{') => "}}", 
+            Some('%') => "%}", 
+            Some('#') => "#}", 
+            _ => {
+                // a lone brace is plain text 
+                tokens.push(Token::Text(rest[..=start].to_string())); 
+                line += rest[..=start].matches('\n').count(); 
+                rest = &rest[start + 1..]; 
+                continue; 
+            }
+        }; 
+
+        let text = &rest[..start]; 
+        if !text.is_empty() {
+            tokens.push(Token::Text(text.to_string())); 
+        }
+        line += text.matches('\n').count(); 
+
+        let inner_start = start + 2; 
+        let inner_len = rest[inner_start..]
+            .find(close)
+            .ok_or_else(|| syntax(name, line, format!("missing `{close}`")))?; 
+        let inner = &rest[inner_start..inner_start + inner_len]; 
+        match close {
+            "}}" => tokens.push(Token::Expr(inner.trim().to_string(), line)), 
+            "%}" => tokens.push(Token::Tag(inner.trim().to_string(), line)), 
+            _ => {}
+        }
+        line += inner.matches('\n').count(); 
+        rest = &rest[inner_start + inner_len + 2..]; 
+    }
+
+    if !rest.is_empty() {
+        tokens.push(Token::Text(rest.to_string())); 
+    }
+    Ok(tokens)
+}
+
+// parse nodes until one of the `ends` tags, returned along with its line 
+fn parse(
+    name: &str, 
+    tokens: &[Token], 
+    pos: &mut usize, 
+    ends: &[&str], 
+    template: &mut Template, 
+) -> Result<(Vec<Node>, Option<EndTag>), TemplateError> {
+    let mut nodes = Vec::new(); 
+
+    while let Some(token) = tokens.get(*pos) {
+        *pos += 1; 
+        let (tag, line) = match token {
+            Token::Text(text) => {
+                nodes.push(Node::Text(text.clone())); 
+                continue; 
+            }
+            Token::Expr(expr, line) => {
+                let (expr, raw) = match expr.split_once('|') {
+                    Some((expr, filter)) if filter.trim() == "raw" => (expr.trim(), true), 
+                    Some((_, filter)) => {
+                        return Err(syntax(name, *line, format!("unknown filter `{}`", filter.trim()))); 
+                    }
+                    None => (expr.as_str(), false), 
+                }; 
+                nodes.push(Node::Var {
+                    path: parse_path(name, *line, expr)?, 
+                    raw, 
+                }); 
+                continue; 
+            }
+            Token::Tag(tag, line) => (tag.as_str(), *line), 
+        }; 
+
+        let (keyword, arg) = tag.split_once(char::is_whitespace).unwrap_or((tag, "")); 
+        let arg = arg.trim(); 
+        if ends.contains(&keyword) {
+            return Ok((nodes, Some((keyword.to_string(), line)))); 
+        }
+
+        match keyword {
+            "if" => {
+                let (negate, cond) = match arg.strip_prefix("not ") {
+                    Some(cond) => (true, cond.trim()), 
+                    None => (false, arg), 
+                }; 
+                let path = parse_path(name, line, cond)?; 
+                let (then, end) = parse(name, tokens, pos, &["else", "endif"], template)?; 
+                let otherwise = match end {
+                    Some((end, _)) if end == "else" => parse(name, tokens, pos, &["endif"], template)
+                        .and_then(|(nodes, end)| closed(name, line, "if", end).map(|_| nodes))?, 
+                    end => {
+                        closed(name, line, "if", end)?; 
+                        Vec::new()
+                    }
+                }; 
+                nodes.push(Node::If {
+                    negate, 
+                    path, 
+                    then, 
+                    otherwise, 
+                }); 
+            }
+            "for" => {
+                let (var, list) = arg
+                    .split_once(" in ")
+                    .ok_or_else(|| syntax(name, line, String::from("expected {% for item in list %}")))?; 
+                let var = var.trim(); 
+                if !is_identifier(var) {
+                    return Err(syntax(name, line, format!("`{var}` is not a name"))); 
+                }
+                let path = parse_path(name, line, list.trim())?; 
+                let (body, end) = parse(name, tokens, pos, &["endfor"], template)?; 
+                closed(name, line, "for", end)?; 
+                nodes.push(Node::For {
+                    var: var.to_string(), 
+                    path, 
+                    body, 
+                }); 
+            }
+            "block" => {
+                if !is_identifier(arg) {
+                    return Err(syntax(name, line, format!("`{arg}` is not a block name"))); 
+                }
+                let (body, end) = parse(name, tokens, pos, &["endblock"], template)?; 
+                closed(name, line, "block", end)?; 
+                nodes.push(Node::Block(arg.to_string(), body)); 
+            }
+            "include" | "extends" => {
+                let target = arg
+                    .strip_prefix('"')
+                    .and_then(|a| a.strip_suffix('"'))
+                    .ok_or_else(|| syntax(name, line, format!("expected {{% {keyword} \"name\" %}}")))?; 
+                template.references.push((target.to_string(), line)); 
+                if keyword == "include" {
+                    nodes.push(Node::Include(target.to_string())); 
+                } else if template.extends.replace(target.to_string()).is_some() {
+                    return Err(syntax(name, line, String::from("only one {% extends %} per template"))); 
+                }
+            }
+            _ => return Err(syntax(name, line, format!("unknown tag `{keyword}`"))), 
+        }
+    }
+
+    Ok((nodes, None))
+}
+
+// the tag opened on `line` must have been closed 
+fn closed(name: &str, line: usize, tag: &str, end: Option<EndTag>) -> Result<(), TemplateError> {
+    match end {
+        Some(_) => Ok(()), 
+        None => Err(syntax(name, line, format!("{{% {tag} %}} is never closed"))), 
+    }
+}
+
+fn parse_path(name: &str, line: usize, expr: &str) -> Result<Vec<String>, TemplateError> {
+    let path: Vec<String> = expr.split('.').map(str::to_string).collect(); 
+    if path.iter().all(|p| is_identifier(p)) {
+        Ok(path)
+    } else {
+        Err(syntax(name, line, format!("`{expr}` is not a name")))
+    }
+}
+
+fn is_identifier(s: &str) -> bool {
+    !s.is_empty() && s.chars().all(|c| c.is_ascii_alphanumeric() || c == '_')
+}
+
+fn syntax(name: &str, line: usize, message: String) -> TemplateError {
+    TemplateError::Syntax(name.to_string(), line, message)
+}
+
+/// Replace the characters that mean something in HTML. 
+pub fn escape(text: &str) -> String {
+    let mut escaped = String::with_capacity(text.len()); 
+    for c in text.chars() {
+        match c {
+            '&' => escaped.push_str("&amp;"), 
+            '<' => escaped.push_str("&lt;"), 
+            '>' => escaped.push_str("&gt;"), 
+            '"' => escaped.push_str("&quot;"), 
+            '\'' => escaped.push_str("&#39;"), 
+            _ => escaped.push(c), 
+        }
+    }
+    escaped
+}
+
+impl Value {
+    // empty things, zero, false and missing values are false 
+    fn is_truthy(&self) -> bool {
+        match self {
+            Value::Null => false, 
+            Value::Bool(b) => *b, 
+            Value::Number(n) => *n != 0.0, 
+            Value::Str(s) => !s.is_empty(), 
+            Value::List(items) => !items.is_empty(), 
+            Value::Map(map) => !map.is_empty(), 
+        }
+    }
+}
+
+impl fmt::Display for Value {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Value::Null | Value::Map(_) => Ok(()), 
+            Value::Bool(b) => write!(f, "{b}"), 
+            Value::Number(n) => write!(f, "{n}"), 
+            Value::Str(s) => f.write_str(s), 
+            Value::List(items) => {
+                for (i, item) in items.iter().enumerate() {
+                    if i > 0 {
+                        f.write_str(", ")?; 
+                    }
+                    write!(f, "{item}")?; 
+                }
+                Ok(())
+            }
+        }
+    }
+}
+
+impl From<&str> for Value {
+    fn from(s: &str) -> Value {
+        Value::Str(s.to_string())
+    }
+}
+
+impl From<String> for Value {
+    fn from(s: String) -> Value {
+        Value::Str(s)
+    }
+}
+
+impl From<bool> for Value {
+    fn from(b: bool) -> Value {
+        Value::Bool(b)
+    }
+}
+
+impl From<i64> for Value {
+    fn from(n: i64) -> Value {
+        Value::Number(n as f64)
+    }
+}
+
+impl From<usize> for Value {
+    fn from(n: usize) -> Value {
+        Value::Number(n as f64)
+    }
+}
+
+impl From<f64> for Value {
+    fn from(n: f64) -> Value {
+        Value::Number(n)
+    }
+}
+
+impl<T: Into<Value>> From<Vec<T>> for Value {
+    fn from(items: Vec<T>) -> Value {
+        Value::List(items.into_iter().map(Into::into).collect())
+    }
+}
+
+impl<T: Into<Value>> From<Option<T>> for Value {
+    fn from(value: Option<T>) -> Value {
+        value.map(Into::into).unwrap_or(Value::Null)
+    }
+}
+
+impl From<Context> for Value {
+    fn from(context: Context) -> Value {
+        Value::Map(context.values)
+    }
+}
+
+impl fmt::Display for TemplateError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            TemplateError::Io(e) => write!(f, "cannot read templates: {e}"), 
+            TemplateError::Syntax(name, line, message) => write!(f, "{name}:{line}: {message}"), 
+            TemplateError::Render(name, message) => write!(f, "{name}: {message}"), 
+        }
+    }
+}
+
+impl std::error::Error for TemplateError {}
+
+impl From<io::Error> for TemplateError {
+    fn from(e: io::Error) -> TemplateError {
+        TemplateError::Io(e)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*; 
+
+    fn templates(sources: &[(&str, &str)]) -> Templates {
+        let mut templates = Templates::new(); 
+        for (name, source) in sources {
+            templates.add(name, source).unwrap(); 
+        }
+        templates.check().unwrap(); 
+        templates
+    }
+
+    #[test]
+    fn fills_in_escaped_values_and_loops() {
+        let templates = templates(&[(
+            "page.html", 
+            "<h1>{{ title }}</h1>{% if not items %}none{% endif %}\
+             {% for item in items %}{{ loop.index }}={{ item.name }}{% if not loop.last %},{% endif %}{% endfor %}\
+             {{ html | raw }}", 
+        )]); 
+
+        let context = Context::new()
+            .with("title", "<Tom & Jerry>")
+            .with("items", vec![Context::new().with("name", "a"), Context::new().with("name", "b")])
+            .with("html", "<b>hi</b>"); 
+        assert_eq!(
+            templates.render("page.html", &context).unwrap(), 
+            "<h1>&lt;Tom &amp; Jerry&gt;</h1>1=a,2=b<b>hi</b>"
+        ); 
+
+        let empty = Context::new().with("title", "t"); 
+        assert_eq!(templates.render("page.html", &empty).unwrap(), "<h1>t</h1>none"); 
+    }
+
+    #[test]
+    fn layouts_and_includes() {
+        let templates = templates(&[
+            ("layout.html", "<title>{% block title %}site{% endblock %}</title>{% block body %}{% endblock %}"), 
+            ("nav.html", "[nav {{ user }}]"), 
+            ("page.html", "{% extends \"layout.html\" %}{% block body %}{% include \"nav.html\" %}body{% endblock %}"), 
+        ]); 
+
+        let html = templates.render("page.html", &Context::new().with("user", "kim")).unwrap(); 
+        assert_eq!(html, "<title>site</title>[nav kim]body"); 
+    }
+
+    #[test]
+    fn syntax_errors_name_the_line() {
+        let mut templates = Templates::new(); 
+        let err = templates.add("bad.html", "line 1\n{% if x %}\nnever closed").unwrap_err(); 
+        assert_eq!(err.to_string(), "bad.html:2: {% if %} is never closed"); 
+
+        templates.add("inc.html", "{% include \"missing.html\" %}").unwrap(); 
+        let err = templates.check().unwrap_err(); 
+        assert_eq!(err.to_string(), "inc.html:1: no template named \"missing.html\""); 
+    }
+}
@@ -0,0 +1,359 @@
+/*
+    testing the server without binding port 7878 by hand 
+        TestClient::new(router) drives handle_connection over an in-memory 
+        stream: the req bytes are read from a buffer and the response is 
+        written into another one, so no socket is involved at all 
+
+        TestServer::start(mode, router) runs the real accept loop (either 
+        mode) on an ephemeral port of 127.0.0.1, and its client() talks 
+        to it over TCP; the server lives until the test process exits 
+
+        client() opens a connection per req and asks for it to be closed; 
+        keep_alive_client() sends every req over the same connection as 
+        long as the server keeps it open, and counts the connections it 
+        had to open, so a test can tell whether the server kept them 
+
+    both clients build reqs the same fluent way and hand back the whole 
+        response, whose assert_* methods chain: 
+            client.get("/").header("Accept", "text/html").send() 
+                .assert_status(200) 
+                .assert_header("Content-Type", "text/html; charset=utf-8") 
+                .assert_body_contains("Hello!"); 
+*/
+
+use std::{
+    io::{self, BufReader, Cursor, Read, Write}, 
+    net::{SocketAddr, TcpListener, TcpStream}, 
+    sync::{Arc, Mutex}, 
+    thread, 
+    time::Duration, 
+}; 
+
+use crate::{
+    event, 
+    http::{self, Response}, 
+    router::Router, 
+    server::{self, Mode}, 
+    ThreadPool, 
+}; 
+
+// how long a TCP client waits for the server before failing the test 
+const TIMEOUT: Duration = Duration::from_secs(10); 
+
+pub struct TestClient {
+    target: Target, 
+}
+
+enum Target {
+    Memory(Arc<Router>), 
+    Tcp(SocketAddr), 
+    KeepAlive(SocketAddr, Mutex<Connection>), 
+}
+
+// the connection a keep-alive client is using, if the server left it open 
+#[derive(Default)]
+struct Connection {
+    stream: Option<TcpStream>, 
+    opened: usize, 
+}
+
+pub struct TestServer {
+    addr: SocketAddr, 
+}
+
+/// A request being put together; `send` runs it. 
+pub struct TestRequest<'a> {
+    client: &'a TestClient, 
+    method: String, 
+    target: String, 
+    headers: Vec<(String, String)>, 
+    body: Vec<u8>, 
+}
+
+/// A complete response, body included. 
+#[derive(Debug)]
+pub struct TestResponse {
+    pub status: u16, 
+    pub headers: Vec<(String, String)>, 
+    pub body: Vec<u8>, 
+}
+
+// an in-memory connection: reads come from `input`, writes go to `output` 
+struct MemoryStream {
+    input: Cursor<Vec<u8>>, 
+    output: Arc<Mutex<Vec<u8>>>, 
+}
+
+impl TestClient {
+    /// A client handing requests straight to `router`, without a socket. 
+    pub fn new(router: Router) -> TestClient {
+        TestClient {
+            target: Target::Memory(Arc::new(router)), 
+        }
+    }
+
+    pub fn get(&self, target: &str) -> TestRequest<'_> {
+        self.request("GET", target)
+    }
+
+    pub fn post(&self, target: &str) -> TestRequest<'_> {
+        self.request("POST", target)
+    }
+
+    /// Start a `method` request for `target` (a path, maybe with a query). 
+    pub fn request(&self, method: &str, target: &str) -> TestRequest<'_> {
+        TestRequest {
+            client: self, 
+            method: method.to_string(), 
+            target: target.to_string(), 
+            headers: Vec::new(), 
+            body: Vec::new(), 
+        }
+    }
+
+    /// The connections a keep-alive client has opened so far, 0 for others. 
+    pub fn connections(&self) -> usize {
+        match &self.target {
+            Target::Memory(_) | Target::Tcp(_) => 0, 
+            Target::KeepAlive(_, connection) => connection.lock().unwrap().opened, 
+        }
+    }
+
+    fn keeps_alive(&self) -> bool {
+        matches!(self.target, Target::KeepAlive(..))
+    }
+
+    // send raw req bytes and read back the response 
+    fn exchange(&self, bytes: Vec<u8>, head_only: bool) -> io::Result<TestResponse> {
+        match &self.target {
+            Target::Memory(router) => {
+                let output = Arc::new(Mutex::new(Vec::new())); 
+                let stream = MemoryStream {
+                    input: Cursor::new(bytes), 
+                    output: Arc::clone(&output), 
+                }; 
+                let peer = SocketAddr::from(([127, 0, 0, 1], 0)); 
+                server::handle_connection(stream, Some(peer), router)?; 
+
+                let written = output.lock().unwrap().clone(); 
+                TestResponse::read(Cursor::new(written), head_only)
+            }
+            Target::Tcp(addr) => {
+                let mut stream = TcpStream::connect_timeout(addr, TIMEOUT)?; 
+                stream.set_read_timeout(Some(TIMEOUT))?; 
+                stream.write_all(&bytes)?; 
+                TestResponse::read(BufReader::new(stream), head_only)
+            }
+            Target::KeepAlive(addr, connection) => {
+                let mut connection = connection.lock().unwrap(); 
+                let mut stream = match connection.stream.take() {
+                    Some(stream) => stream, 
+                    None => {
+                        let stream = TcpStream::connect_timeout(addr, TIMEOUT)?; 
+                        stream.set_read_timeout(Some(TIMEOUT))?; 
+                        connection.opened += 1; 
+                        stream
+                    }
+                }; 
+                stream.write_all(&bytes)?; 
+
+                // a byte at a time, so that nothing of the next response 
+                // is read ahead and lost with the reader 
+                let reader = BufReader::with_capacity(1, stream.try_clone()?); 
+                let response = TestResponse::read(reader, head_only)?; 
+                if !response.header("Connection").is_some_and(|v| v.eq_ignore_ascii_case("close")) {
+                    connection.stream = Some(stream); 
+                }
+                Ok(response)
+            }
+        }
+    }
+}
+
+impl TestServer {
+    /// Serve `router` in `mode` on an ephemeral port. 
+    /// 
+    /// # Panics 
+    /// 
+    /// The `start` function will panic if no local port can be bound. 
+    pub fn start(mode: Mode, router: Router) -> TestServer {
+        let listener = TcpListener::bind("127.0.0.1:0").expect("cannot bind a local port"); 
+        let addr = listener.local_addr().unwrap(); 
+        let router = Arc::new(router); 
+
+        thread::spawn(move || {
+            let pool = ThreadPool::new(2); 
+            match mode {
+                Mode::Threaded => server::serve(listener, &pool, router), 
+                Mode::Event => {
+                    if let Err(e) = event::serve(listener, &pool, router) {
+                        eprintln!("event loop failed: {e}"); 
+                    }
+                }
+            }
+        }); 
+
+        TestServer { addr }
+    }
+
+    pub fn addr(&self) -> SocketAddr {
+        self.addr
+    }
+
+    /// A client sending its requests to this server over TCP. 
+    pub fn client(&self) -> TestClient {
+        TestClient {
+            target: Target::Tcp(self.addr), 
+        }
+    }
+
+    /// A client reusing one connection for as long as the server allows. 
+    pub fn keep_alive_client(&self) -> TestClient {
+        TestClient {
+            target: Target::KeepAlive(self.addr, Mutex::new(Connection::default())), 
+        }
+    }
+}
+
+impl TestRequest<'_> {
+    pub fn header(mut self, name: &str, value: &str) -> Self {
+        self.headers.push((name.to_string(), value.to_string())); 
+        self
+    }
+
+    pub fn body(mut self, body: impl Into<Vec<u8>>) -> Self {
+        self.body = body.into(); 
+        self
+    }
+
+    /// Send `fields` as an urlencoded form body. 
+    pub fn form(self, fields: &[(&str, &str)]) -> Self {
+        let body = fields
+            .iter()
+            .map(|(name, value)| format!("{}={}", encode(name), encode(value)))
+            .collect::<Vec<_>>()
+            .join("&"); 
+        self.header("Content-Type", "application/x-www-form-urlencoded")
+            .body(body)
+    }
+
+    /// Send the request and read the whole response. 
+    /// 
+    /// # Panics 
+    /// 
+    /// The `send` function will panic if the exchange fails at the I/O level. 
+    #[track_caller]
+    pub fn send(self) -> TestResponse {
+        match self.try_send() {
+            Ok(response) => response, 
+            Err(e) => panic!("{} {} failed: {e}", self.method, self.target), 
+        }
+    }
+
+    pub fn try_send(&self) -> io::Result<TestResponse> {
+        let mut head = format!("{} {} HTTP/1.1\r\n", self.method, self.target); 
+        if !self.headers.iter().any(|(name, _)| name.eq_ignore_ascii_case("Host")) {
+            head.push_str("Host: localhost\r\n"); 
+        }
+        for (name, value) in &self.headers {
+            head.push_str(&format!("{name}: {value}\r\n")); 
+        }
+        head.push_str(&format!("Content-Length: {}\r\n", self.body.len())); 
+        if !self.client.keeps_alive() {
+            head.push_str("Connection: close\r\n"); 
+        }
+        head.push_str("\r\n"); 
+
+        let mut bytes = head.into_bytes(); 
+        bytes.extend_from_slice(&self.body); 
+        self.client.exchange(bytes, self.method == "HEAD")
+    }
+}
+
+impl TestResponse {
+    fn read<R: io::BufRead + Send + 'static>(reader: R, head_only: bool) -> io::Result<TestResponse> {
+        let Response { status, headers, body } = http::read_response(reader, head_only)?; 
+        let body = match body {
+            http::Body::Bytes(bytes) => bytes, 
+            http::Body::Stream { reader, length } => {
+                // no further than the body, the connection may be kept 
+                let mut bytes = Vec::new(); 
+                reader.take(length.unwrap_or(u64::MAX)).read_to_end(&mut bytes)?; 
+                bytes
+            }
+        }; 
+        Ok(TestResponse { status, headers, body })
+    }
+
+    pub fn header(&self, name: &str) -> Option<&str> {
+        self.headers
+            .iter()
+            .find(|(n, _)| n.eq_ignore_ascii_case(name))
+            .map(|(_, v)| v.as_str())
+    }
+
+    /// The body as text (invalid UTF-8 replaced). 
+    pub fn text(&self) -> String {
+        String::from_utf8_lossy(&self.body).into_owned()
+    }
+
+    #[track_caller]
+    pub fn assert_status(self, status: u16) -> Self {
+        assert_eq!(self.status, status, "unexpected status, body: {}", self.text()); 
+        self
+    }
+
+    #[track_caller]
+    pub fn assert_header(self, name: &str, value: &str) -> Self {
+        assert_eq!(self.header(name), Some(value), "header {name}"); 
+        self
+    }
+
+    #[track_caller]
+    pub fn assert_no_header(self, name: &str) -> Self {
+        assert_eq!(self.header(name), None, "header {name}"); 
+        self
+    }
+
+    #[track_caller]
+    pub fn assert_body(self, body: &str) -> Self {
+        assert_eq!(self.text(), body); 
+        self
+    }
+
+    #[track_caller]
+    pub fn assert_body_contains(self, part: &str) -> Self {
+        let text = self.text(); 
+        assert!(text.contains(part), "body does not contain {part:?}: {text}"); 
+        self
+    }
+}
+
+impl Read for MemoryStream {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        self.input.read(buf)
+    }
+}
+
+impl Write for MemoryStream {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        self.output.lock().unwrap().extend_from_slice(buf); 
+        Ok(buf.len())
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        Ok(())
+    }
+}
+
+// percent-encode everything but unreserved characters 
+fn encode(s: &str) -> String {
+    let mut encoded = String::new(); 
+    for b in s.bytes() {
+        match b {
+            b'A'..=b'Z' | b'a'..=b'z' | b'0'..=b'9' | b'-' | b'_' | b'.' | b'~' => encoded.push(b as char), 
+            _ => encoded.push_str(&format!("%{b:02X}")), 
+        }
+    }
+    encoded
+}
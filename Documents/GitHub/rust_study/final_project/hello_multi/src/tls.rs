@@ -0,0 +1,180 @@
+/*
+    HTTPS: TLS termination with rustls 
+        the certificate chain and private key are read from PEM files 
+        (paths from the [tls] section of the config); for local testing 
+        a self-signed pair can be generated with 
+            openssl req -x509 -newkey rsa:2048 -nodes -days 365 \ 
+                -keyout key.pem -out cert.pem -subj /CN=localhost 
+        and used with curl --cacert cert.pem https://localhost:7879/ 
+
+    TLS connections are always handled thread-per-connection: the accept 
+        loop runs on its own thread and gives each connection to the same 
+        ThreadPool the plain HTTP listener uses, so both share the workers 
+
+        rustls::StreamOwned does the handshake on the first read, then 
+        encrypts and decrypts transparently, thus handle_connection can 
+        serve it like any other Read + Write stream 
+
+        a connection over the limits never reaches the pool: the 429/503 
+        can only be sent after a handshake, so a thread of its own does 
+        the handshake, reads the req and answers (Retry-After included), 
+        giving up after REJECT_TIMEOUT; at most MAX_REJECTING of those run 
+        at once, and a refusal beyond that is just shut down, so the client 
+        sees its handshake fail without any status 
+*/
+
+use std::{
+    fmt, 
+    io::{self, Read, Write}, 
+    net::{Shutdown, TcpListener, TcpStream}, 
+    path::Path, 
+    sync::{
+        atomic::{AtomicUsize, Ordering}, 
+        Arc, 
+    }, 
+    thread, 
+    time::Duration, 
+}; 
+
+use rustls::{
+    pki_types::{pem::PemObject, CertificateDer, PrivateKeyDer}, 
+    ServerConfig, ServerConnection, StreamOwned, 
+}; 
+
+use crate::{limit::Rejection, router::Router, server, ThreadPool}; 
+
+// refusals answered at once, each on a thread of its own 
+const MAX_REJECTING: usize = 4; 
+// how long a refused client gets for its handshake and req 
+const REJECT_TIMEOUT: Duration = Duration::from_secs(2); 
+
+/// Build a rustls server configuration from PEM certificate and key files. 
+pub fn load_config(cert: &Path, key: &Path) -> io::Result<Arc<ServerConfig>> {
+    let invalid = |what: &Path, e: &dyn fmt::Display| {
+        io::Error::new(io::ErrorKind::InvalidData, format!("{}: {e}", what.display()))
+    }; 
+
+    let certs = CertificateDer::pem_file_iter(cert)
+        .map_err(|e| invalid(cert, &e))?
+        .collect::<Result<Vec<_>, _>>()
+        .map_err(|e| invalid(cert, &e))?; 
+    if certs.is_empty() {
+        return Err(invalid(cert, &"no certificates found")); 
+    }
+    let key_der = PrivateKeyDer::from_pem_file(key).map_err(|e| invalid(key, &e))?; 
+
+    let config = ServerConfig::builder_with_provider(Arc::new(rustls::crypto::ring::default_provider()))
+        .with_safe_default_protocol_versions()
+        .map_err(|e| invalid(cert, &e))?
+        .with_no_client_auth()
+        .with_single_cert(certs, key_der)
+        .map_err(|e| invalid(cert, &e))?; 
+
+    Ok(Arc::new(config))
+}
+
+/// Accept TLS connections forever, handing each one to the pool as a job. 
+pub fn serve(listener: TcpListener, pool: &ThreadPool, router: Arc<Router>, config: Arc<ServerConfig>) {
+    let rejecting = Arc::new(AtomicUsize::new(0)); 
+
+    for stream in listener.incoming() {
+        let stream = match stream {
+            Ok(stream) => stream, 
+            Err(e) => {
+                eprintln!("accept failed: {e}"); 
+                continue; 
+            }
+        }; 
+        let permit = match server::admit(&stream, &router) {
+            Ok(permit) => permit, 
+            // never reaches the pool, see the top of the file 
+            Err(rejection) => {
+                reject(stream, rejection, &config, &rejecting); 
+                continue; 
+            }
+        }; 
+        let config = Arc::clone(&config); 
+        let router = Arc::clone(&router); 
+        let peer = stream.peer_addr().ok(); 
+
+        pool.execute(move || {
+            let _permit = permit; 
+            let result = TlsStream::new(config, stream)
+                .and_then(|stream| server::handle_connection(stream, peer, &router)); 
+            if let Err(e) = result {
+                eprintln!("tls connection error: {e}"); 
+            }
+        }); 
+    }
+}
+
+// answer a refused connection on a thread of its own, if the budget allows 
+fn reject(stream: TcpStream, rejection: Rejection, config: &Arc<ServerConfig>, rejecting: &Arc<AtomicUsize>) {
+    if rejecting.fetch_add(1, Ordering::SeqCst) >= MAX_REJECTING {
+        rejecting.fetch_sub(1, Ordering::SeqCst); 
+        let _ = stream.shutdown(Shutdown::Both); 
+        return; 
+    }
+    let config = Arc::clone(config); 
+    let rejecting = Arc::clone(rejecting); 
+
+    thread::spawn(move || {
+        let answer = || -> io::Result<()> {
+            stream.set_read_timeout(Some(REJECT_TIMEOUT))?; 
+            stream.set_write_timeout(Some(REJECT_TIMEOUT))?; 
+            let mut stream = TlsStream::new(config, stream)?; 
+            // read the req first (its body is not wanted), so it is not 
+            // left unread when the socket closes, which would reset it 
+            let _ = server::read_request(&mut stream, 0)?; 
+            rejection
+                .response()
+                .with_header("Connection", "close")
+                .write_to(&mut stream)
+        }; 
+        let _ = answer(); 
+        rejecting.fetch_sub(1, Ordering::SeqCst); 
+    }); 
+}
+
+/// A server-side TLS connection that says goodbye properly when dropped. 
+pub struct TlsStream {
+    inner: StreamOwned<ServerConnection, TcpStream>, 
+}
+
+impl TlsStream {
+    pub fn new(config: Arc<ServerConfig>, stream: TcpStream) -> io::Result<TlsStream> {
+        let conn = ServerConnection::new(config).map_err(io::Error::other)?; 
+        Ok(TlsStream {
+            inner: StreamOwned::new(conn, stream), 
+        })
+    }
+}
+
+impl Read for TlsStream {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        self.inner.read(buf)
+    }
+}
+
+impl Write for TlsStream {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        self.inner.write(buf)
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        self.inner.flush()
+    }
+}
+
+impl Drop for TlsStream {
+    fn drop(&mut self) {
+        // send close_notify so clients can tell a finished response 
+        // from a truncated one 
+        self.inner.conn.send_close_notify(); 
+        while self.inner.conn.wants_write() {
+            if self.inner.conn.write_tls(&mut self.inner.sock).is_err() {
+                break; 
+            }
+        }
+    }
+}
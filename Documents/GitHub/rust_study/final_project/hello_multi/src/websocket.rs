@@ -0,0 +1,636 @@
+/*
+    WebSocket (RFC 6455) on top of an upgraded HTTP connection 
+        the client asks for an upgrade with a normal GET req carrying 
+            Upgrade: websocket 
+            Connection: Upgrade 
+            Sec-WebSocket-Key: <base64 nonce> 
+            Sec-WebSocket-Version: 13 
+        and the server agrees with 101 Switching Protocols, proving that 
+        it understood the req by answering with 
+            Sec-WebSocket-Accept: base64(sha1(key + GUID)) 
+
+        after the handshake the connection carries frames instead of HTTP: 
+            byte 0: FIN bit, 3 reserved bits, 4-bit opcode 
+            byte 1: MASK bit, 7-bit payload length (126 -> next 2 bytes 
+                    hold the length, 127 -> next 8 bytes do) 
+            4-byte masking key if MASK is set, then the payload 
+        clients must mask every frame they send, servers must not 
+
+        a message can be split into fragments: the first frame has the 
+        real opcode and FIN unset, the rest are Continuation frames and 
+        the last one has FIN set; control frames (close, ping, pong) are 
+        never fragmented but may arrive in between the fragments 
+
+    the handler registered with Router::websocket owns the connection 
+        for as long as it runs, so it keeps one worker of the pool busy 
+*/
+
+use std::{
+    fmt, 
+    io::{self, ErrorKind, Read, Write}, 
+}; 
+
+use crate::{
+    http::{Request, Response}, 
+    server::Stream, 
+}; 
+
+// appended to the client's key before hashing (RFC 6455 section 1.3) 
+const GUID: &str = "258EAFA5-E914-47DA-95CA-C5AB0DC85B11"; 
+
+// refuse messages larger than this (close code 1009) 
+pub const MAX_MESSAGE_SIZE: usize = 16 * 1024 * 1024; 
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Opcode {
+    Continuation, 
+    Text, 
+    Binary, 
+    Close, 
+    Ping, 
+    Pong, 
+}
+
+impl Opcode {
+    fn from_u8(bits: u8) -> Option<Opcode> {
+        match bits {
+            0x0 => Some(Opcode::Continuation), 
+            0x1 => Some(Opcode::Text), 
+            0x2 => Some(Opcode::Binary), 
+            0x8 => Some(Opcode::Close), 
+            0x9 => Some(Opcode::Ping), 
+            0xA => Some(Opcode::Pong), 
+            _ => None, 
+        }
+    }
+
+    fn bits(self) -> u8 {
+        match self {
+            Opcode::Continuation => 0x0, 
+            Opcode::Text => 0x1, 
+            Opcode::Binary => 0x2, 
+            Opcode::Close => 0x8, 
+            Opcode::Ping => 0x9, 
+            Opcode::Pong => 0xA, 
+        }
+    }
+
+    fn is_control(self) -> bool {
+        matches!(self, Opcode::Close | Opcode::Ping | Opcode::Pong)
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Frame {
+    pub fin: bool, 
+    pub opcode: Opcode, 
+    // whether the frame arrived masked (the payload is already unmasked) 
+    pub masked: bool, 
+    pub payload: Vec<u8>, 
+}
+
+impl Frame {
+    pub fn new(opcode: Opcode, payload: impl Into<Vec<u8>>) -> Frame {
+        Frame {
+            fin: true, 
+            opcode, 
+            masked: false, 
+            payload: payload.into(), 
+        }
+    }
+}
+
+/// A complete message, after fragments have been put back together. 
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Message {
+    Text(String), 
+    Binary(Vec<u8>), 
+    Ping(Vec<u8>), 
+    Pong(Vec<u8>), 
+    // status code and reason, if the peer sent any 
+    Close(Option<(u16, String)>), 
+}
+
+#[derive(Debug)]
+pub enum Error {
+    Io(io::Error), 
+    // the peer broke the protocol; the u16 is the close code we answer with 
+    Protocol(u16, &'static str), 
+    // the connection has been closed (by either side) 
+    Closed, 
+}
+
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Error::Io(e) => write!(f, "websocket I/O error: {e}"), 
+            Error::Protocol(code, reason) => write!(f, "websocket protocol error {code}: {reason}"), 
+            Error::Closed => write!(f, "websocket closed"), 
+        }
+    }
+}
+
+impl std::error::Error for Error {}
+
+impl From<io::Error> for Error {
+    fn from(e: io::Error) -> Error {
+        Error::Io(e)
+    }
+}
+
+/// Whether `request` asks to be upgraded to a WebSocket. 
+pub fn is_upgrade(request: &Request) -> bool {
+    let has_token = |name: &str, token: &str| {
+        request
+            .header(name)
+            .map(|v| v.split(',').any(|t| t.trim().eq_ignore_ascii_case(token)))
+            .unwrap_or(false)
+    }; 
+
+    request.method == "GET" && has_token("Upgrade", "websocket") && has_token("Connection", "upgrade")
+}
+
+/// Build the 101 response accepting the upgrade, or the error to send instead. 
+pub fn handshake(request: &Request) -> Result<Response, Response> {
+    if !is_upgrade(request) {
+        return Err(Response::text(400, "expected a WebSocket upgrade")); 
+    }
+    if request.header("Sec-WebSocket-Version") != Some("13") {
+        return Err(Response::text(426, "unsupported WebSocket version")
+            .with_header("Sec-WebSocket-Version", "13")); 
+    }
+    let key = match request.header("Sec-WebSocket-Key") {
+        Some(key) if !key.is_empty() => key, 
+        _ => return Err(Response::text(400, "missing Sec-WebSocket-Key")), 
+    }; 
+
+    Ok(Response::new(101)
+        .with_header("Upgrade", "websocket")
+        .with_header("Connection", "Upgrade")
+        .with_header("Sec-WebSocket-Accept", &accept_key(key)))
+}
+
+/// The Sec-WebSocket-Accept value for a client's Sec-WebSocket-Key. 
+pub fn accept_key(key: &str) -> String {
+    let mut input = key.trim().as_bytes().to_vec(); 
+    input.extend_from_slice(GUID.as_bytes()); 
+    base64_encode(&sha1(&input))
+}
+
+/// Read one frame, unmasking its payload. 
+pub fn read_frame<R: Read>(reader: &mut R) -> Result<Frame, Error> {
+    let mut head = [0; 2]; 
+    reader.read_exact(&mut head)?; 
+
+    let fin = head[0] & 0x80 != 0; 
+    if head[0] & 0x70 != 0 {
+        return Err(Error::Protocol(1002, "reserved bits set")); 
+    }
+    let opcode = Opcode::from_u8(head[0] & 0x0F).ok_or(Error::Protocol(1002, "unknown opcode"))?; 
+    let masked = head[1] & 0x80 != 0; 
+
+    let length = match head[1] & 0x7F {
+        126 => {
+            let mut buf = [0; 2]; 
+            reader.read_exact(&mut buf)?; 
+            u16::from_be_bytes(buf) as u64
+        }
+        127 => {
+            let mut buf = [0; 8]; 
+            reader.read_exact(&mut buf)?; 
+            u64::from_be_bytes(buf)
+        }
+        n => n as u64, 
+    }; 
+
+    if opcode.is_control() && (!fin || length > 125) {
+        return Err(Error::Protocol(1002, "bad control frame")); 
+    }
+    if length > MAX_MESSAGE_SIZE as u64 {
+        return Err(Error::Protocol(1009, "frame too large")); 
+    }
+
+    let mut mask = [0; 4]; 
+    if masked {
+        reader.read_exact(&mut mask)?; 
+    }
+
+    let mut payload = vec![0; length as usize]; 
+    reader.read_exact(&mut payload)?; 
+    if masked {
+        apply_mask(&mut payload, mask); 
+    }
+
+    Ok(Frame {
+        fin, 
+        opcode, 
+        masked, 
+        payload, 
+    })
+}
+
+/// Write one frame; clients pass a masking key, servers pass `None`. 
+pub fn write_frame<W: Write>(writer: &mut W, frame: &Frame, mask: Option<[u8; 4]>) -> io::Result<()> {
+    let mut out = Vec::with_capacity(frame.payload.len() + 14); 
+    out.push(if frame.fin { 0x80 } else { 0 } | frame.opcode.bits()); 
+
+    let mask_bit = if mask.is_some() { 0x80 } else { 0 }; 
+    let length = frame.payload.len(); 
+    if length < 126 {
+        out.push(mask_bit | length as u8); 
+    } else if length <= u16::MAX as usize {
+        out.push(mask_bit | 126); 
+        out.extend_from_slice(&(length as u16).to_be_bytes()); 
+    } else {
+        out.push(mask_bit | 127); 
+        out.extend_from_slice(&(length as u64).to_be_bytes()); 
+    }
+
+    let start = out.len(); 
+    if let Some(mask) = mask {
+        out.extend_from_slice(&mask); 
+    }
+    out.extend_from_slice(&frame.payload); 
+    if let Some(mask) = mask {
+        apply_mask(&mut out[start + 4..], mask); 
+    }
+
+    writer.write_all(&out)?; 
+    writer.flush()
+}
+
+// xor the payload with the 4-byte key (masking and unmasking are the same) 
+fn apply_mask(payload: &mut [u8], mask: [u8; 4]) {
+    for (i, byte) in payload.iter_mut().enumerate() {
+        *byte ^= mask[i % 4]; 
+    }
+}
+
+/// The server side of an upgraded connection. 
+pub struct WebSocket {
+    stream: Box<dyn Stream>, 
+    // the opcode and data of a fragmented message being collected 
+    partial: Option<(Opcode, Vec<u8>)>, 
+    // the two halves of the closing handshake 
+    close_sent: bool, 
+    close_received: bool, 
+}
+
+impl WebSocket {
+    /// Wrap a connection on which the handshake has already been done. 
+    pub fn new(stream: impl Stream) -> WebSocket {
+        WebSocket {
+            stream: Box::new(stream), 
+            partial: None, 
+            close_sent: false, 
+            close_received: false, 
+        }
+    }
+
+    /// Wait for the next message. 
+    /// 
+    /// Pings are answered automatically but still returned, so handlers 
+    /// can see them; a Close is echoed back before being returned, after 
+    /// which every call fails with `Error::Closed`. 
+    /// 
+    /// After `close`, keep calling `recv` until the peer's Close comes 
+    /// back: that completes the closing handshake. 
+    pub fn recv(&mut self) -> Result<Message, Error> {
+        if self.close_received {
+            return Err(Error::Closed); 
+        }
+
+        match self.recv_message() {
+            Err(Error::Protocol(code, reason)) => {
+                // nothing more can be made of the stream after this 
+                let _ = self.close(code, reason); 
+                self.close_received = true; 
+                Err(Error::Protocol(code, reason))
+            }
+            other => other, 
+        }
+    }
+
+    fn recv_message(&mut self) -> Result<Message, Error> {
+        loop {
+            let frame = read_frame(&mut self.stream)?; 
+            if !frame.masked {
+                return Err(Error::Protocol(1002, "client frames must be masked")); 
+            }
+
+            match frame.opcode {
+                Opcode::Ping => {
+                    // no more frames may follow our Close, not even a pong 
+                    if !self.close_sent {
+                        self.send_frame(&Frame::new(Opcode::Pong, frame.payload.clone()))?; 
+                    }
+                    return Ok(Message::Ping(frame.payload)); 
+                }
+                Opcode::Pong => return Ok(Message::Pong(frame.payload)), 
+                Opcode::Close => {
+                    let reason = parse_close(&frame.payload)?; 
+                    self.close_received = true; 
+                    if !self.close_sent {
+                        // echo the status code back, as the RFC asks 
+                        let echo = frame.payload.get(..2).unwrap_or(&[]).to_vec(); 
+                        self.send_frame(&Frame::new(Opcode::Close, echo))?; 
+                        self.close_sent = true; 
+                    }
+                    return Ok(Message::Close(reason)); 
+                }
+                Opcode::Text | Opcode::Binary => {
+                    if self.partial.is_some() {
+                        return Err(Error::Protocol(1002, "expected a continuation frame")); 
+                    }
+                    if frame.fin {
+                        return to_message(frame.opcode, frame.payload); 
+                    }
+                    self.partial = Some((frame.opcode, frame.payload)); 
+                }
+                Opcode::Continuation => {
+                    let Some((opcode, mut data)) = self.partial.take() else {
+                        return Err(Error::Protocol(1002, "unexpected continuation frame")); 
+                    }; 
+                    if data.len() + frame.payload.len() > MAX_MESSAGE_SIZE {
+                        return Err(Error::Protocol(1009, "message too large")); 
+                    }
+                    data.extend_from_slice(&frame.payload); 
+
+                    if frame.fin {
+                        return to_message(opcode, data); 
+                    }
+                    self.partial = Some((opcode, data)); 
+                }
+            }
+        }
+    }
+
+    /// Send a message as a single frame. 
+    pub fn send(&mut self, message: Message) -> Result<(), Error> {
+        let frame = match message {
+            Message::Text(text) => Frame::new(Opcode::Text, text), 
+            Message::Binary(data) => Frame::new(Opcode::Binary, data), 
+            Message::Ping(data) => Frame::new(Opcode::Ping, data), 
+            Message::Pong(data) => Frame::new(Opcode::Pong, data), 
+            Message::Close(reason) => {
+                let (code, reason) = reason.unwrap_or((1000, String::new())); 
+                return self.close(code, &reason); 
+            }
+        }; 
+        self.send_frame(&frame)
+    }
+
+    pub fn send_text(&mut self, text: &str) -> Result<(), Error> {
+        self.send(Message::Text(text.to_string()))
+    }
+
+    /// Send a raw frame, e.g. one fragment of a larger message. 
+    pub fn send_frame(&mut self, frame: &Frame) -> Result<(), Error> {
+        if self.close_sent {
+            return Err(Error::Closed); 
+        }
+        write_frame(&mut self.stream, frame, None)?; 
+        Ok(())
+    }
+
+    /// Start the closing handshake with a status code and reason. 
+    /// 
+    /// Nothing can be sent afterwards, but `recv` still returns what the 
+    /// peer sent until its own Close arrives. 
+    pub fn close(&mut self, code: u16, reason: &str) -> Result<(), Error> {
+        let mut payload = code.to_be_bytes().to_vec(); 
+        payload.extend_from_slice(reason.as_bytes()); 
+
+        self.send_frame(&Frame::new(Opcode::Close, payload))?; 
+        self.close_sent = true; 
+        Ok(())
+    }
+}
+
+fn to_message(opcode: Opcode, data: Vec<u8>) -> Result<Message, Error> {
+    match opcode {
+        Opcode::Text => String::from_utf8(data)
+            .map(Message::Text)
+            .map_err(|_| Error::Protocol(1007, "text message is not UTF-8")), 
+        _ => Ok(Message::Binary(data)), 
+    }
+}
+
+fn parse_close(payload: &[u8]) -> Result<Option<(u16, String)>, Error> {
+    match payload.len() {
+        0 => Ok(None), 
+        1 => Err(Error::Protocol(1002, "bad close payload")), 
+        _ => {
+            let code = u16::from_be_bytes([payload[0], payload[1]]); 
+            let reason = String::from_utf8(payload[2..].to_vec())
+                .map_err(|_| Error::Protocol(1007, "close reason is not UTF-8"))?; 
+            Ok(Some((code, reason)))
+        }
+    }
+}
+
+impl From<Error> for io::Error {
+    fn from(e: Error) -> io::Error {
+        match e {
+            Error::Io(e) => e, 
+            Error::Closed => io::Error::new(ErrorKind::BrokenPipe, e.to_string()), 
+            Error::Protocol(..) => io::Error::new(ErrorKind::InvalidData, e.to_string()), 
+        }
+    }
+}
+
+// SHA-1 (FIPS 180-4); only used to compute the handshake accept key 
+fn sha1(data: &[u8]) -> [u8; 20] {
+    let mut h: [u32; 5] = [0x67452301, 0xEFCDAB89, 0x98BADCFE, 0x10325476, 0xC3D2E1F0]; 
+
+    // pad with a 1 bit, zeros, then the bit length as a 64-bit integer 
+    let mut message = data.to_vec(); 
+    message.push(0x80); 
+    while message.len() % 64 != 56 {
+        message.push(0); 
+    }
+    message.extend_from_slice(&((data.len() as u64) * 8).to_be_bytes()); 
+
+    for block in message.chunks(64) {
+        let mut w = [0u32; 80]; 
+        for i in 0..16 {
+            w[i] = u32::from_be_bytes([block[4 * i], block[4 * i + 1], block[4 * i + 2], block[4 * i + 3]]); 
+        }
+        for i in 16..80 {
+            w[i] = (w[i - 3] ^ w[i - 8] ^ w[i - 14] ^ w[i - 16]).rotate_left(1); 
+        }
+
+        let [mut a, mut b, mut c, mut d, mut e] = h; 
+        for (i, word) in w.iter().enumerate() {
+            let (f, k) = match i {
+                0..=19 => ((b & c) | (!b & d), 0x5A827999), 
+                20..=39 => (b ^ c ^ d, 0x6ED9EBA1), 
+                40..=59 => ((b & c) | (b & d) | (c & d), 0x8F1BBCDC), 
+                _ => (b ^ c ^ d, 0xCA62C1D6), 
+            }; 
+            let temp = a
+                .rotate_left(5)
+                .wrapping_add(f)
+                .wrapping_add(e)
+                .wrapping_add(k)
+                .wrapping_add(*word); 
+            e = d; 
+            d = c; 
+            c = b.rotate_left(30); 
+            b = a; 
+            a = temp; 
+        }
+
+        for (h, v) in h.iter_mut().zip([a, b, c, d, e]) {
+            *h = h.wrapping_add(v); 
+        }
+    }
+
+    let mut out = [0; 20]; 
+    for (i, word) in h.iter().enumerate() {
+        out[4 * i..4 * i + 4].copy_from_slice(&word.to_be_bytes()); 
+    }
+    out
+}
+
+fn base64_encode(data: &[u8]) -> String {
+    const ALPHABET: &[u8; 64] = b"ABCDEFGHIJKLMNOPQRSTUVWXYZabcdefghijklmnopqrstuvwxyz0123456789+/"; 
+    let mut out = String::new(); 
+
+    for chunk in data.chunks(3) {
+        let n = match chunk.len() {
+            3 => (chunk[0] as u32) << 16 | (chunk[1] as u32) << 8 | chunk[2] as u32, 
+            2 => (chunk[0] as u32) << 16 | (chunk[1] as u32) << 8, 
+            _ => (chunk[0] as u32) << 16, 
+        }; 
+        for i in 0..4 {
+            if i <= chunk.len() {
+                out.push(ALPHABET[(n >> (18 - 6 * i) & 0x3F) as usize] as char); 
+            } else {
+                out.push('='); 
+            }
+        }
+    }
+
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*; 
+    use std::{
+        io::Cursor, 
+        sync::{Arc, Mutex}, 
+    }; 
+
+    // in-memory connection: reads from a script, records what is written 
+    struct Pipe {
+        input: Cursor<Vec<u8>>, 
+        output: Arc<Mutex<Vec<u8>>>, 
+    }
+
+    impl Read for Pipe {
+        fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+            self.input.read(buf)
+        }
+    }
+
+    impl Write for Pipe {
+        fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+            self.output.lock().unwrap().write(buf)
+        }
+
+        fn flush(&mut self) -> io::Result<()> {
+            Ok(())
+        }
+    }
+
+    fn client_frames(frames: &[Frame]) -> Vec<u8> {
+        let mut out = Vec::new(); 
+        for frame in frames {
+            write_frame(&mut out, frame, Some([1, 2, 3, 4])).unwrap(); 
+        }
+        out
+    }
+
+    #[test]
+    fn accept_key_matches_rfc_example() {
+        assert_eq!(accept_key("dGhlIHNhbXBsZSBub25jZQ=="), "s3pPLMBiTxaQ9kYGzzhZRbK+xOo="); 
+    }
+
+    #[test]
+    fn masked_frames_round_trip() {
+        let frame = Frame::new(Opcode::Binary, vec![7; 300]); 
+        let mut bytes = Vec::new(); 
+        write_frame(&mut bytes, &frame, Some([9, 8, 7, 6])).unwrap(); 
+        assert_eq!(bytes[1], 0x80 | 126); 
+
+        let read = read_frame(&mut &bytes[..]).unwrap(); 
+        assert!(read.masked); 
+        assert_eq!(read.payload, frame.payload); 
+    }
+
+    #[test]
+    fn reassembles_fragments_around_a_ping() {
+        let mut first = Frame::new(Opcode::Text, "Hel"); 
+        first.fin = false; 
+        let input = client_frames(&[
+            first, 
+            Frame::new(Opcode::Ping, "hi"), 
+            Frame::new(Opcode::Continuation, "lo"), 
+        ]); 
+        let output = Arc::new(Mutex::new(Vec::new())); 
+        let mut ws = WebSocket::new(Pipe {
+            input: Cursor::new(input), 
+            output: Arc::clone(&output), 
+        }); 
+
+        assert_eq!(ws.recv().unwrap(), Message::Ping(b"hi".to_vec())); 
+        assert_eq!(ws.recv().unwrap(), Message::Text(String::from("Hello"))); 
+
+        // the ping was answered with an unmasked pong 
+        let written = output.lock().unwrap().clone(); 
+        let pong = read_frame(&mut &written[..]).unwrap(); 
+        assert_eq!(pong.opcode, Opcode::Pong); 
+        assert!(!pong.masked); 
+    }
+
+    #[test]
+    fn rejects_unmasked_client_frames() {
+        let mut input = Vec::new(); 
+        write_frame(&mut input, &Frame::new(Opcode::Text, "x"), None).unwrap(); 
+        let mut ws = WebSocket::new(Pipe {
+            input: Cursor::new(input), 
+            output: Arc::new(Mutex::new(Vec::new())), 
+        }); 
+
+        assert!(matches!(ws.recv(), Err(Error::Protocol(1002, _)))); 
+        assert!(matches!(ws.recv(), Err(Error::Closed))); 
+    }
+
+    #[test]
+    fn reads_until_the_peer_answers_our_close() {
+        let input = client_frames(&[
+            Frame::new(Opcode::Text, "late"), 
+            Frame::new(Opcode::Close, 1000u16.to_be_bytes()), 
+        ]); 
+        let output = Arc::new(Mutex::new(Vec::new())); 
+        let mut ws = WebSocket::new(Pipe {
+            input: Cursor::new(input), 
+            output: Arc::clone(&output), 
+        }); 
+
+        ws.close(1001, "going away").unwrap(); 
+        assert!(matches!(ws.send_text("more"), Err(Error::Closed))); 
+        assert_eq!(ws.recv().unwrap(), Message::Text(String::from("late"))); 
+        assert_eq!(ws.recv().unwrap(), Message::Close(Some((1000, String::new())))); 
+        assert!(matches!(ws.recv(), Err(Error::Closed))); 
+
+        // our Close went out once and was not echoed again 
+        let written = output.lock().unwrap().clone(); 
+        let mut rest = &written[..]; 
+        let close = read_frame(&mut rest).unwrap(); 
+        assert_eq!(close.opcode, Opcode::Close); 
+        assert!(rest.is_empty()); 
+    }
+}
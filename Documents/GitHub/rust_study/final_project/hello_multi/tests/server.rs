@@ -0,0 +1,341 @@
+/*
+    the server as a client sees it, through hello::testing 
+        most tests hand reqs to the Router in memory; the ones that care 
+        about the connection itself (keep-alive, limits, both modes) use 
+        a real server on an ephemeral port 
+
+    keep-alive: the threaded mode answers one req per connection and says 
+        so (Connection: close), the event mode keeps the connection open; 
+        a keep-alive client sees both through the connections it opened 
+*/
+
+use std::{
+    env, fs, 
+    io::{Cursor, Read, Write}, 
+    net::TcpStream, 
+    process, 
+    time::Duration, 
+}; 
+
+use hello::{
+    files::StaticFiles, 
+    http::Response, 
+    limit::Limits, 
+    router::Router, 
+    server::Mode, 
+    testing::{TestClient, TestServer}, 
+    websocket::{self, Frame, Message, Opcode}, 
+}; 
+
+const BIG: &[u8] = &[b'x'; 300_000]; 
+
+fn router() -> Router {
+    Router::new()
+        .get("/", |_| Response::html(200, "<h1>Hello!</h1>"))
+        .post("/echo", |request| {
+            let form = request.form().unwrap(); 
+            Response::text(200, format!("name={}", form.get("name").unwrap_or("")))
+        })
+        .get("/stream", |_| {
+            Response::new(200).with_stream(Cursor::new(b"streamed body".to_vec()), None)
+        })
+        // many pieces, with and without a known length 
+        .get("/big", |request| {
+            let length = request.query.is_some().then_some(BIG.len() as u64); 
+            Response::new(200).with_stream(Cursor::new(BIG), length)
+        })
+        .prefix("/files", |request| Response::text(200, request.path.clone()))
+        .with_max_body_size(64)
+}
+
+#[test]
+fn routes_and_fallback() {
+    let client = TestClient::new(router()); 
+
+    client
+        .get("/")
+        .send()
+        .assert_status(200)
+        .assert_header("Content-Type", "text/html; charset=utf-8")
+        .assert_body("<h1>Hello!</h1>"); 
+    client.get("/files/a/b.txt").send().assert_body("/files/a/b.txt"); 
+    client.get("/nowhere").send().assert_status(404); 
+    // exact routes match the method too 
+    client.post("/").send().assert_status(404); 
+}
+
+#[test]
+fn forms_and_body_limits() {
+    let client = TestClient::new(router()); 
+
+    client
+        .post("/echo")
+        .form(&[("name", "Kim & Lee")])
+        .send()
+        .assert_status(200)
+        .assert_body("name=Kim & Lee"); 
+    client.post("/echo").body(vec![b'x'; 65]).send().assert_status(413); 
+}
+
+#[test]
+fn streamed_bodies_arrive_whole() {
+    TestClient::new(router())
+        .get("/stream")
+        .send()
+        .assert_header("Transfer-Encoding", "chunked")
+        .assert_body("streamed body"); 
+}
+
+#[test]
+fn both_modes_over_tcp() {
+    for mode in [Mode::Threaded, Mode::Event] {
+        let server = TestServer::start(mode, router()); 
+        let client = server.client(); 
+
+        client.get("/").send().assert_status(200).assert_header("Connection", "close"); 
+        client.get("/stream").send().assert_body("streamed body"); 
+        client.get("/missing").send().assert_status(404); 
+    }
+}
+
+#[test]
+fn keep_alive_in_both_modes() {
+    for (mode, connection, opened) in [(Mode::Threaded, "close", 3), (Mode::Event, "keep-alive", 1)] {
+        let server = TestServer::start(mode, router()); 
+        let client = server.keep_alive_client(); 
+
+        client.get("/").send().assert_status(200).assert_header("Connection", connection); 
+        // a chunked body, then a body after a form post 
+        client.get("/stream").send().assert_body("streamed body"); 
+        client
+            .post("/echo")
+            .form(&[("name", "Kim")])
+            .send()
+            .assert_header("Connection", connection)
+            .assert_body("name=Kim"); 
+        assert_eq!(client.connections(), opened, "{mode:?}"); 
+    }
+}
+
+#[test]
+fn head_requests_get_the_length_but_no_body() {
+    let root = env::temp_dir().join(format!("hello-head-test-{}", process::id())); 
+    fs::create_dir_all(&root).unwrap(); 
+    fs::write(root.join("big.txt"), "hello there").unwrap(); 
+    let files = StaticFiles::new(&root); 
+    let router = router().fallback(move |request| {
+        files.serve(request).unwrap_or_else(|| Response::text(404, "nothing here"))
+    }); 
+
+    // a body sent anyway would be read as the start of the next response 
+    let server = TestServer::start(Mode::Event, router); 
+    let client = server.keep_alive_client(); 
+    client
+        .request("HEAD", "/big.txt")
+        .send()
+        .assert_status(200)
+        .assert_header("Content-Length", "11")
+        .assert_body(""); 
+    client.get("/big.txt").send().assert_body("hello there"); 
+    client
+        .request("HEAD", "/nope.txt")
+        .send()
+        .assert_status(404)
+        .assert_header("Content-Length", "12"); 
+    client.get("/").send().assert_body("<h1>Hello!</h1>"); 
+    assert_eq!(client.connections(), 1); 
+
+    fs::remove_dir_all(&root).unwrap(); 
+}
+
+#[test]
+fn large_streams_arrive_whole_in_both_modes() {
+    for mode in [Mode::Threaded, Mode::Event] {
+        let server = TestServer::start(mode, router()); 
+        let client = server.keep_alive_client(); 
+
+        let chunked = client.get("/big").send().assert_header("Transfer-Encoding", "chunked"); 
+        assert_eq!(chunked.body, BIG, "{mode:?}"); 
+        let sized = client.get("/big?sized").send().assert_header("Content-Length", "300000"); 
+        assert_eq!(sized.body, BIG, "{mode:?}"); 
+        client.get("/").send().assert_body("<h1>Hello!</h1>"); 
+    }
+}
+
+#[test]
+fn chunked_request_bodies_keep_the_connection_in_step() {
+    let server = TestServer::start(Mode::Event, router()); 
+    let mut stream = TcpStream::connect(server.addr()).unwrap(); 
+    stream.set_read_timeout(Some(Duration::from_secs(10))).unwrap(); 
+    stream
+        .write_all(
+            b"POST /echo HTTP/1.1\r\nHost: localhost\r\nContent-Type: application/x-www-form-urlencoded\r\n\
+              Transfer-Encoding: chunked\r\n\r\n5\r\nname=\r\n3\r\nKim\r\n0\r\n\r\n\
+              GET /files/after HTTP/1.1\r\nHost: localhost\r\nConnection: close\r\n\r\n", 
+        )
+        .unwrap(); 
+
+    let mut answer = String::new(); 
+    stream.read_to_string(&mut answer).unwrap(); 
+    assert_eq!(answer.matches("HTTP/1.1 200").count(), 2, "{answer}"); 
+    let echo = answer.find("name=Kim").expect("no form in the first response"); 
+    let after = answer.find("/files/after").expect("no second response"); 
+    assert!(echo < after, "{answer}"); 
+}
+
+#[test]
+fn pipelined_requests_are_answered_in_order() {
+    // the threaded mode answers one req per connection, only the event 
+    // loop keeps connections open 
+    let server = TestServer::start(Mode::Event, router()); 
+    let mut stream = TcpStream::connect(server.addr()).unwrap(); 
+    stream.set_read_timeout(Some(Duration::from_secs(10))).unwrap(); 
+    // both in one write, so the second is already buffered when the first 
+    // is answered 
+    stream
+        .write_all(
+            b"GET /files/first HTTP/1.1\r\nHost: localhost\r\n\r\n\
+              GET /files/second HTTP/1.1\r\nHost: localhost\r\nConnection: close\r\n\r\n", 
+        )
+        .unwrap(); 
+
+    let mut answer = String::new(); 
+    stream.read_to_string(&mut answer).unwrap(); 
+    let first = answer.find("/files/first").expect("no first response"); 
+    let second = answer.find("/files/second").expect("no second response"); 
+    assert!(first < second, "{answer}"); 
+    assert_eq!(answer.matches("HTTP/1.1 200").count(), 2, "{answer}"); 
+}
+
+#[test]
+fn upgraded_connections_keep_what_came_with_the_handshake() {
+    for mode in [Mode::Threaded, Mode::Event] {
+        let router = router().websocket("/ws", |_, mut ws| {
+            while let Ok(message) = ws.recv() {
+                match message {
+                    Message::Text(text) => ws.send_text(&text).unwrap(), 
+                    Message::Close(_) => break, 
+                    _ => {}
+                }
+            }
+        }); 
+        let server = TestServer::start(mode, router); 
+        let mut stream = TcpStream::connect(server.addr()).unwrap(); 
+        stream.set_read_timeout(Some(Duration::from_secs(10))).unwrap(); 
+
+        // the first frame goes out in the same write as the handshake 
+        let mut hello = b"GET /ws HTTP/1.1\r\nHost: localhost\r\nUpgrade: websocket\r\n\
+                          Connection: Upgrade\r\nSec-WebSocket-Key: dGhlIHNhbXBsZSBub25jZQ==\r\n\
+                          Sec-WebSocket-Version: 13\r\n\r\n"
+            .to_vec(); 
+        websocket::write_frame(&mut hello, &Frame::new(Opcode::Text, "early"), Some([1, 2, 3, 4])).unwrap(); 
+        stream.write_all(&hello).unwrap(); 
+
+        let mut head = Vec::new(); 
+        while !head.ends_with(b"\r\n\r\n") {
+            let mut byte = [0]; 
+            stream.read_exact(&mut byte).unwrap(); 
+            head.push(byte[0]); 
+        }
+        let head = String::from_utf8(head).unwrap(); 
+        assert!(head.starts_with("HTTP/1.1 101"), "{mode:?}: {head}"); 
+        assert!(head.contains("s3pPLMBiTxaQ9kYGzzhZRbK+xOo="), "{mode:?}: {head}"); 
+
+        let echo = websocket::read_frame(&mut stream).unwrap(); 
+        assert_eq!((echo.opcode, &echo.payload[..]), (Opcode::Text, &b"early"[..]), "{mode:?}"); 
+
+        let close = Frame::new(Opcode::Close, 1000u16.to_be_bytes()); 
+        websocket::write_frame(&mut stream, &close, Some([5, 6, 7, 8])).unwrap(); 
+        let answer = websocket::read_frame(&mut stream).unwrap(); 
+        assert_eq!(answer.opcode, Opcode::Close, "{mode:?}"); 
+        assert_eq!(stream.read(&mut [0; 16]).unwrap(), 0, "{mode:?}"); 
+    }
+}
+
+#[test]
+fn rate_limited_clients_are_told_to_wait() {
+    let limits = Limits {
+        rate: Some(0.1), 
+        burst: Some(2), 
+        ..Limits::default()
+    }; 
+    let server = TestServer::start(Mode::Event, router().with_limits(limits)); 
+    let client = server.client(); 
+
+    client.get("/").send().assert_status(200); 
+    client.get("/").send().assert_status(200); 
+    client
+        .get("/")
+        .send()
+        .assert_status(429)
+        .assert_header("Retry-After", "10"); 
+}
+
+#[test]
+fn static_files_and_listings() {
+    let root = env::temp_dir().join(format!("hello-files-test-{}", process::id())); 
+    fs::create_dir_all(root.join("docs/sub")).unwrap(); 
+    fs::create_dir_all(root.join("site")).unwrap(); 
+    fs::write(root.join("docs/small.txt"), "hi").unwrap(); 
+    fs::write(root.join("docs/big.txt"), "hello there").unwrap(); 
+    fs::write(root.join("docs/.hidden"), "secret").unwrap(); 
+    fs::write(root.join("site/index.html"), "<p>index</p>").unwrap(); 
+
+    let files = StaticFiles::new(&root).with_listing(true); 
+    let client = TestClient::new(Router::new().fallback(move |request| {
+        files.serve(request).unwrap_or_else(|| Response::text(404, "nothing here"))
+    })); 
+
+    client
+        .get("/docs/big.txt")
+        .send()
+        .assert_header("Content-Type", "text/plain; charset=utf-8")
+        .assert_body("hello there"); 
+    client.get("/site/").send().assert_body("<p>index</p>"); 
+    client.get("/site").send().assert_status(301).assert_header("Location", "/site/"); 
+    client
+        .get("/docs?sort=size&order=desc")
+        .send()
+        .assert_status(301)
+        .assert_header("Location", "/docs/?sort=size&order=desc"); 
+    client.get("/docs/.hidden").send().assert_status(403); 
+    client.get("/docs/nope.txt").send().assert_status(404); 
+
+    // directories first, then by size, largest first 
+    let listing = client.get("/docs/?format=json&sort=size&order=desc").send().assert_status(200); 
+    let text = listing.text(); 
+    let names: Vec<&str> = text.split("\"name\":\"").skip(1).filter_map(|s| s.split('"').next()).collect(); 
+    assert_eq!(names, ["sub", "big.txt", "small.txt"]); 
+
+    client
+        .get("/docs/")
+        .send()
+        .assert_header("Content-Type", "text/html; charset=utf-8")
+        .assert_body_contains("<a href=\"small.txt\">small.txt</a>"); 
+
+    fs::remove_dir_all(&root).unwrap(); 
+}
+
+#[test]
+fn virtual_hosts_by_host_header() {
+    let site = |name: &'static str| Router::new().get("/", move |_| Response::text(200, name)); 
+    let router = router()
+        .host(&["a.test", "www.a.test"], site("site a"))
+        .host(&["*.b.test"], site("site b")); 
+    let client = TestClient::new(router); 
+
+    client.get("/").header("Host", "a.test").send().assert_body("site a"); 
+    client.get("/").header("Host", "WWW.A.test:7878").send().assert_body("site a"); 
+    client.get("/").header("Host", "blog.b.test").send().assert_body("site b"); 
+    // b.test itself is not a subdomain of b.test 
+    client.get("/").header("Host", "b.test").send().assert_body("<h1>Hello!</h1>"); 
+
+    let router = Router::new()
+        .host(&["a.test"], site("site a"))
+        .with_default_host("a.test"); 
+    TestClient::new(router)
+        .get("/")
+        .header("Host", "unknown.test")
+        .send()
+        .assert_body("site a"); 
+}
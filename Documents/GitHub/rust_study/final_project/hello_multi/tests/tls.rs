@@ -0,0 +1,94 @@
+/*
+    HTTPS end to end with a self-signed certificate generated on the fly 
+        the server side is the real tls::serve on an ephemeral port; 
+        the client trusts exactly that one certificate 
+
+    a client over the limits is refused without a worker: with the only 
+        one stuck on a silent client, the 429 still comes at once 
+*/
+
+use std::{
+    env, fs, 
+    io::{Read, Write}, 
+    net::{TcpListener, TcpStream}, 
+    sync::Arc, 
+    thread, 
+    time::{Duration, Instant}, 
+}; 
+
+use hello::{http::Response, limit::Limits, router::Router, tls, ThreadPool}; 
+use rustls::{pki_types::ServerName, ClientConfig, ClientConnection, RootCertStore, StreamOwned}; 
+
+// a tls::serve on an ephemeral port, and a client config trusting it 
+fn start(router: Router, workers: usize) -> (u16, Arc<ClientConfig>) {
+    let generated = rcgen::generate_simple_self_signed(vec![String::from("localhost")]).unwrap(); 
+
+    // load_config reads PEM files, just like the server does 
+    let dir = env::temp_dir().join(format!("hello-tls-test-{}-{}", std::process::id(), workers)); 
+    fs::create_dir_all(&dir).unwrap(); 
+    let (cert_path, key_path) = (dir.join("cert.pem"), dir.join("key.pem")); 
+    fs::write(&cert_path, generated.cert.pem()).unwrap(); 
+    fs::write(&key_path, generated.signing_key.serialize_pem()).unwrap(); 
+    let server_config = tls::load_config(&cert_path, &key_path).unwrap(); 
+    fs::remove_dir_all(&dir).unwrap(); 
+
+    let listener = TcpListener::bind("127.0.0.1:0").unwrap(); 
+    let port = listener.local_addr().unwrap().port(); 
+    let router = Arc::new(router); 
+    thread::spawn(move || {
+        let pool = ThreadPool::new(workers); 
+        tls::serve(listener, &pool, router, server_config); 
+    }); 
+
+    let mut roots = RootCertStore::empty(); 
+    roots.add(generated.cert.der().clone()).unwrap(); 
+    let client_config = ClientConfig::builder_with_provider(Arc::new(rustls::crypto::ring::default_provider()))
+        .with_safe_default_protocol_versions()
+        .unwrap()
+        .with_root_certificates(roots)
+        .with_no_client_auth(); 
+    (port, Arc::new(client_config))
+}
+
+#[test]
+fn serves_https_with_a_self_signed_certificate() {
+    let router = Router::new().get("/", |_| Response::text(200, "secret")); 
+    let (port, client_config) = start(router, 2); 
+    let conn = ClientConnection::new(client_config, ServerName::try_from("localhost").unwrap()).unwrap(); 
+    let mut stream = StreamOwned::new(conn, TcpStream::connect(("127.0.0.1", port)).unwrap()); 
+
+    stream.write_all(b"GET / HTTP/1.1\r\nHost: localhost\r\n\r\n").unwrap(); 
+    let mut response = String::new(); 
+    // succeeds only if the server ends the session with close_notify 
+    stream.read_to_string(&mut response).unwrap(); 
+
+    assert!(response.starts_with("HTTP/1.1 200 OK\r\n")); 
+    assert!(response.ends_with("\r\n\r\nsecret")); 
+}
+
+#[test]
+fn refused_connections_do_not_wait_for_a_worker() {
+    let limits = Limits {
+        per_ip: Some(1), 
+        ..Limits::default()
+    }; 
+    let router = Router::new().get("/", |_| Response::text(200, "secret")).with_limits(limits); 
+    let (port, client_config) = start(router, 1); 
+
+    // takes the only slot and the only worker, which waits for a handshake 
+    let _idle = TcpStream::connect(("127.0.0.1", port)).unwrap(); 
+    thread::sleep(Duration::from_millis(100)); 
+
+    // turned away without the pool: answered at once 
+    let conn = ClientConnection::new(client_config, ServerName::try_from("localhost").unwrap()).unwrap(); 
+    let socket = TcpStream::connect(("127.0.0.1", port)).unwrap(); 
+    socket.set_read_timeout(Some(Duration::from_secs(5))).unwrap(); 
+    let mut stream = StreamOwned::new(conn, socket); 
+    let started = Instant::now(); 
+    stream.write_all(b"GET / HTTP/1.1\r\nHost: localhost\r\n\r\n").unwrap(); 
+    let mut response = String::new(); 
+    stream.read_to_string(&mut response).unwrap(); 
+    assert!(response.starts_with("HTTP/1.1 429 "), "{response}"); 
+    assert!(response.contains("Retry-After: 1\r\n"), "{response}"); 
+    assert!(started.elapsed() < Duration::from_secs(4)); 
+}